@@ -0,0 +1,92 @@
+// Performance budget: a realistic ILS Depesche of about 2 KB against 40 configured RICs, parse and
+// deduplication below 1 ms per alarm. Run with
+// cargo bench --bench parse
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fireplan_alarm_divera::{dedup, parser, Configuration, SubmitPayload};
+use std::hint::black_box;
+
+fn depesche() -> String {
+    let mut text = String::from(
+        "Einsatznummer: 2024-123456\nAlarmzeit: 14.03.2024 03:12:45\nStichwort: B3 Wohnhaus\n\
+         Schlagwort: Brand im Dachstuhl, Personen vermisst\nOrt: Musterstadt\nOrtsteil: Nord\n\
+         Straße: Hauptstraße 12a\nObjekt: Mehrfamilienhaus Hauptstraße\nKoordinaten: 49.0094, 8.4044\n\
+         Meldender: Nachbar, Rückruf unter 0721 123456\n",
+    );
+    for i in 0..20 {
+        text.push_str(&format!("Bemerkung {}: Anfahrt über Nebenstraße, Hydrant vor Haus {}, Drehleiter Aufstellfläche prüfen\n", i, i));
+    }
+    text.push_str("Einsatzmittel: ");
+    let units: Vec<String> = (0..40).step_by(3).map(|i| format!("FL Musterstadt {} HLF {}", i, i)).collect();
+    text.push_str(&units.join(", "));
+    text.push_str(", UW 1/46-1, UW 11");
+    text
+}
+
+fn configuration_with_40_rics() -> Configuration {
+    let rics: Vec<String> = (0..40)
+        .map(|i| {
+            format!(
+                r#"{{ text = "FL Musterstadt {} HLF {}", ric = "{}", subric = "B", umlaut_tolerant = {} }}"#,
+                i,
+                i,
+                100000 + i,
+                i % 4 == 0
+            )
+        })
+        .collect();
+    toml::from_str(&format!(
+        r#"
+        fireplan_api_key = "key"
+        regex_ort = 'Ort\s*:\s*(.*)'
+        regex_ortsteil = 'Ortsteil\s*:\s*(.*)'
+        regex_objektname = '(?:Objekt\s*:\s*(.*))|Gebäude'
+        http_port = 8080
+        http_host = "localhost"
+        auth_token = "t"
+        rics = [{}]
+        format = {{ regex_stichwort = 'Stichwort\s*:\s*(.*)' }}
+        "#,
+        rics.join(", ")
+    ))
+    .expect("benchmark configuration")
+}
+
+fn payload() -> SubmitPayload {
+    serde_json::from_value(serde_json::json!({
+        "text": depesche(),
+        "title": "B3",
+        "address": "Hauptstraße 12a, 76131 Musterstadt",
+    }))
+    .expect("benchmark payload")
+}
+
+fn parse(c: &mut Criterion) {
+    let configuration = configuration_with_40_rics();
+    let payload = payload();
+    c.bench_function("parse 2 KB Depesche, 40 RICs", |b| {
+        b.iter(|| parser::parse(black_box(payload.clone()), black_box(configuration.clone())).expect("benchmark parse"))
+    });
+}
+
+// In memory and with a new Einsatznummer per alarm, so every RIC is claimed and nothing is written to disk.
+// The table is emptied now and then so it stays the size of a busy TTL window
+fn dedup(c: &mut Criterion) {
+    let parsed = parser::parse(payload(), configuration_with_40_rics()).expect("benchmark parse");
+    assert_eq!(parsed.rics().len(), 17);
+    let mut table = dedup::Table::new();
+    let mut einsatznummer = 0u64;
+    c.bench_function("dedup claim of 17 RICs", |b| {
+        b.iter(|| {
+            einsatznummer += 1;
+            if table.len() > 1 << 16 {
+                table.clear();
+            }
+            let (claimed, _) = dedup::claim_in(&mut table, &einsatznummer.to_string(), "bench", parsed.rics(), 0);
+            assert_eq!(claimed.len(), 17);
+        })
+    });
+}
+
+criterion_group!(benches, parse, dedup);
+criterion_main!(benches);
@@ -0,0 +1,129 @@
+use crate::{ParsedData, Ric};
+use chrono::{Datelike, NaiveTime, Timelike, Weekday};
+use chrono_tz::Tz;
+use log::{error, info};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+// Time-dependent AAO rule: adds RICs when the alarm arrives within the given weekdays and time window
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct AaoRule {
+    name: String,
+    // e.g. ["Mon", "Tue", "Wed", "Thu", "Fri"], all days if omitted
+    weekdays: Option<Vec<String>>,
+    // "HH:MM", window may wrap around midnight (e.g. 18:00 - 06:00), whole day if omitted
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default)]
+    rics: Vec<Ric>,
+    // names of ric_groups whose members are added as well
+    groups: Option<Vec<String>>,
+    // only active while the duty group is staffed as given, see roster
+    duty: Option<crate::roster::DutyCondition>,
+    // RIC numbers or texts removed from the alarm while the rule is active, e.g. an understaffed Abteilung
+    remove: Option<Vec<String>>,
+}
+
+pub fn timezone(name: &Option<String>) -> Tz {
+    match name.as_deref() {
+        Some(n) => n.parse::<Tz>().unwrap_or_else(|e| {
+            error!("Invalid timezone {}: {}, falling back to Europe/Berlin", n, e);
+            chrono_tz::Europe::Berlin
+        }),
+        None => chrono_tz::Europe::Berlin,
+    }
+}
+
+fn parse_time(s: &Option<String>) -> Option<NaiveTime> {
+    let s = s.as_ref()?;
+    match NaiveTime::parse_from_str(s.trim(), "%H:%M") {
+        Ok(t) => Some(t),
+        Err(e) => {
+            error!("Invalid AAO time {}: {}", s, e);
+            None
+        }
+    }
+}
+
+// Rules whose times cannot be read, refused at config load instead of silently matching the whole day,
+// and RICs that alarm nothing
+pub fn check(rules: &[AaoRule]) -> Vec<String> {
+    let mut errors = vec![];
+    for rule in rules {
+        for (field, value) in [("from", &rule.from), ("to", &rule.to)] {
+            if let Some(value) = value {
+                if NaiveTime::parse_from_str(value.trim(), "%H:%M").is_err() {
+                    errors.push(format!("AAO rule {} {} {} is not HH:MM", rule.name, field, value));
+                }
+            }
+        }
+        for ric in &rule.rics {
+            crate::schema::check_ric(&mut errors, &format!("AAO rule {}:", rule.name), ric);
+        }
+    }
+    errors
+}
+
+impl AaoRule {
+    fn is_active(&self, weekday: Weekday, time: NaiveTime) -> bool {
+        if let Some(days) = &self.weekdays {
+            if !days.iter().any(|d| d.parse::<Weekday>().map(|d| d == weekday).unwrap_or(false)) {
+                return false;
+            }
+        }
+
+        match (parse_time(&self.from), parse_time(&self.to)) {
+            (Some(from), Some(to)) if from <= to => time >= from && time < to,
+            (Some(from), Some(to)) => time >= from || time < to,
+            (Some(from), None) => time >= from,
+            (None, Some(to)) => time < to,
+            (None, None) => true,
+        }
+    }
+
+    // checked last, the roster is only consulted for rules active by time
+    fn is_staffed(&self) -> bool {
+        self.duty.as_ref().map(|d| d.holds()).unwrap_or(true)
+    }
+}
+
+// Remove and add RICs of all currently active rules, evaluated in the configured timezone
+pub fn apply(data: &mut ParsedData, rules: &[AaoRule], tz: Tz) {
+    let now = chrono::Utc::now().with_timezone(&tz);
+    let time = NaiveTime::from_hms_opt(now.hour(), now.minute(), 0).unwrap_or_default();
+
+    for rule in rules {
+        if !rule.is_active(now.weekday(), time) || !rule.is_staffed() {
+            continue;
+        }
+        if let Some(remove) = &rule.remove {
+            let before = data.rics.len();
+            // RIC numbers are compared without the leading zeros the parser pads them with
+            data.rics.retain(|r| {
+                !remove
+                    .iter()
+                    .any(|w| w.trim_start_matches('0') == r.ric.trim_start_matches('0') || w.eq_ignore_ascii_case(&r.text))
+            });
+            info!("AAO rule {} active, removing {} RICs", rule.name, before - data.rics.len());
+        }
+        let mut rics: Vec<Ric> = rule.rics.iter().flat_map(crate::ric_groups::expand).collect();
+        for group in rule.groups.iter().flatten() {
+            rics.extend(crate::ric_groups::members(group));
+        }
+        info!("AAO rule {} active, adding {} RICs", rule.name, rics.len());
+        for ric in &rics {
+            let ric = Ric {
+                text: ric.text.clone(),
+                ric: format!("{:0>7}", ric.ric),
+                subric: ric.subric.clone(),
+                umlaut_tolerant: None,
+                standorte: ric.standorte.clone(),
+                info_only: ric.info_only,
+                group: None,
+            };
+            if !data.rics.contains(&ric) {
+                data.rics.push(ric);
+            }
+        }
+    }
+}
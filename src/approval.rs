@@ -0,0 +1,152 @@
+use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+// Top-level keys deciding where alarms go and whom they alarm
+const ROUTING_KEYS: [&str; 10] = [
+    "rics",
+    "ric_groups",
+    "profiles",
+    "aao_rules",
+    "overrides",
+    "sink_routes",
+    "submit_tokens",
+    "default_standort",
+    "priorities",
+    "probe_alarms",
+];
+
+// Keys deciding who may change the config; guarded in addition to the configured keys, so no admin can drop
+// the second admin, swap the admin token or move routing into the secrets alone
+const ALWAYS_GUARDED: [&str; 4] = ["config_approval", "admin_token", "secrets", "secrets_key_file"];
+
+// Four-eyes principle for config changes through the editor: a change touching routing or RIC tables
+// is applied only by a second admin
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ApprovalConfig {
+    // admins with their own token, accepted wherever the admin token is
+    admins: Vec<Admin>,
+    // top-level keys whose changes need approval, defaults to the routing and RIC tables
+    keys: Option<Vec<String>>,
+    // a change waiting for approval is dropped after this, defaults to 24
+    expiry_hours: Option<i64>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Admin {
+    name: String,
+    token: String,
+    // RFC 3339, the token is rejected from then on
+    valid_until: Option<String>,
+}
+
+impl ApprovalConfig {
+    pub fn tokens(&self) -> impl Iterator<Item = &String> {
+        self.admins.iter().map(|a| &a.token)
+    }
+}
+
+static CONFIG: OnceCell<ApprovalConfig> = OnceCell::new();
+
+pub fn init(config: ApprovalConfig) {
+    let _ = CONFIG.set(config);
+}
+
+pub fn enabled() -> bool {
+    CONFIG.get().is_some()
+}
+
+// Whether changes to the top-level key need a second admin
+pub fn guards(key: &str) -> bool {
+    CONFIG.get().is_some_and(|config| guarded_keys(config).iter().any(|k| k == key))
+}
+
+fn guarded_keys(config: &ApprovalConfig) -> Vec<String> {
+    let keys = match &config.keys {
+        Some(keys) => keys.clone(),
+        None => ROUTING_KEYS.iter().map(|k| k.to_string()).collect(),
+    };
+    keys.into_iter().chain(ALWAYS_GUARDED.iter().map(|k| k.to_string())).collect()
+}
+
+// Routing keys of a config table, the keys of its config_approval or the default ones; they must stay in plain
+// text so the approval sees their changes
+pub fn routing_keys(table: &toml::Table) -> Vec<String> {
+    let configured = table.get("config_approval").and_then(|a| a.get("keys")).and_then(|k| k.as_array());
+    match configured {
+        Some(keys) => keys.iter().filter_map(|k| k.as_str()).map(str::to_string).collect(),
+        None => ROUTING_KEYS.iter().map(|k| k.to_string()).collect(),
+    }
+}
+
+// Name of the admin the token belongs to
+pub fn admin(token: &str) -> Option<String> {
+    CONFIG
+        .get()?
+        .admins
+        .iter()
+        .find(|a| a.token == token && crate::tokens::valid_now(&None, &a.valid_until))
+        .map(|a| a.name.clone())
+}
+
+pub fn expiry_secs() -> i64 {
+    CONFIG.get().and_then(|c| c.expiry_hours).unwrap_or(24) * 3600
+}
+
+// Guarded keys whose value differs between the two config texts, empty without approval mode
+pub fn guarded_changes(current: &str, changed: &str) -> Vec<String> {
+    match CONFIG.get() {
+        Some(config) => changes(config, current, changed),
+        None => vec![],
+    }
+}
+
+// A text that is not TOML counts as a change to every guarded key
+fn changes(config: &ApprovalConfig, current: &str, changed: &str) -> Vec<String> {
+    let keys = guarded_keys(config);
+    let (Ok(current), Ok(changed)) = (current.parse::<toml::Table>(), changed.parse::<toml::Table>()) else {
+        return keys;
+    };
+    keys.into_iter().filter(|k| current.get(k) != changed.get(k)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ApprovalConfig {
+        toml::from_str(r#"admins = [ { name = "A", token = "a" }, { name = "B", token = "b" } ]"#).expect("approval config")
+    }
+
+    const CURRENT: &str = r#"auth_token = "t"
+rics = [ { text = "HLF", ric = "123456", subric = "B" } ]
+"#;
+
+    #[test]
+    fn routing_change_is_guarded() {
+        let changed = CURRENT.replace("123456", "654321");
+        assert_eq!(changes(&config(), CURRENT, &changed), vec!["rics"]);
+    }
+
+    #[test]
+    fn other_change_passes() {
+        let changed = CURRENT.replace(r#"auth_token = "t""#, r#"auth_token = "u""#);
+        assert!(changes(&config(), CURRENT, &changed).is_empty());
+    }
+
+    #[test]
+    fn approval_and_admin_token_are_always_guarded() {
+        let mut restricted = config();
+        restricted.keys = Some(vec!["rics".to_string()]);
+        let changed = format!("{}admin_token = \"new\"\nconfig_approval = {{ admins = [] }}\nsecrets = \"fad1:x\"\n", CURRENT);
+        assert_eq!(changes(&restricted, CURRENT, &changed), vec!["config_approval", "admin_token", "secrets"]);
+    }
+
+    #[test]
+    fn unparsable_config_guards_every_key() {
+        let changed = format!("{}rics = [", CURRENT);
+        let guarded = changes(&config(), CURRENT, &changed);
+        assert_eq!(guarded, guarded_keys(&config()));
+        assert!(guarded.contains(&"rics".to_string()) && guarded.contains(&"config_approval".to_string()));
+    }
+}
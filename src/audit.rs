@@ -0,0 +1,64 @@
+use log::{error, warn};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// Separate from the runtime log and never rewritten (retention does not apply), for incident review
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    // "admin_call", "auth_failure", "config_loaded", ...
+    pub action: String,
+    // peer address for web requests, "local" otherwise
+    pub actor: String,
+    pub detail: String,
+}
+
+pub fn record(action: &str, actor: &str, detail: &str) {
+    let entry = AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        action: action.to_string(),
+        actor: actor.to_string(),
+        detail: detail.to_string(),
+    };
+    let line = match serde_json::to_string(&entry) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Could not serialize audit entry: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(crate::paths::audit_file())
+        .and_then(|mut f| f.write_all(format!("{}\n", line).as_bytes()))
+    {
+        error!("Failed to write audit log: {}", e);
+    }
+}
+
+// Entries newest first, optionally filtered by action and minimum timestamp (RFC 3339)
+pub fn query(action: Option<&str>, since: Option<&str>, limit: usize) -> Vec<AuditEntry> {
+    let since = since.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok());
+    let content = std::fs::read_to_string(crate::paths::audit_file()).unwrap_or_default();
+    content
+        .lines()
+        .rev()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str::<AuditEntry>(l) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                warn!("Skipping malformed audit line: {}", e);
+                None
+            }
+        })
+        .filter(|e| action.map(|a| e.action == a).unwrap_or(true))
+        .filter(|e| {
+            since
+                .map(|s| chrono::DateTime::parse_from_rfc3339(&e.timestamp).map(|t| t >= s).unwrap_or(false))
+                .unwrap_or(true)
+        })
+        .take(limit)
+        .collect()
+}
@@ -0,0 +1,187 @@
+use crate::paths;
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_derive::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+const MANIFEST: &str = "manifest.json";
+const CONFIG: &str = "config/fireplan_alarm_divera.conf";
+const CONFIG_ENCRYPTED: &str = "config/fireplan_alarm_divera.conf.enc";
+const STATE: &str = "state/";
+
+// How the config goes into a backup: secrets masked, or the whole file encrypted with the secrets key
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigMode {
+    #[default]
+    Redacted,
+    Encrypted,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    version: String,
+    created: String,
+    files: Vec<String>,
+    config: ConfigMode,
+}
+
+// Files a move to another machine must not lose: audit trail, history, dedup state, outbox and tokens
+fn state_files() -> Vec<String> {
+    vec![
+        paths::history_file(),
+        paths::audit_file(),
+        paths::dedup_file(),
+        paths::mail_seen_file(),
+        paths::tokens_file(),
+        paths::outbox_file(),
+        paths::samples_file(),
+        paths::received_log(),
+        paths::submitted_log(),
+    ]
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn append(archive: &mut tar::Builder<GzEncoder<Vec<u8>>>, name: &str, data: &[u8]) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)
+}
+
+// Key the running config decrypts its secrets with
+fn config_key(config: &str) -> anyhow::Result<crate::secrets::Key> {
+    let table: toml::Table = config.parse().context("config is not valid TOML")?;
+    crate::secrets::key(table.get("secrets_key_file").and_then(|f| f.as_str()))
+}
+
+// tar.gz of the state files and the config; each file is read as it is, the service keeps running.
+// Minted tokens are only kept as hashes, so they go into every backup.
+pub fn create(config: &str, mode: ConfigMode) -> anyhow::Result<Vec<u8>> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mut files = vec![];
+    for path in state_files() {
+        // the history from the configured store, so a backup also moves it out of PostgreSQL
+        let read = if path == paths::history_file() {
+            crate::storage::history()
+                .lines()
+                .map(|lines| lines.iter().map(|l| format!("{}\n", l)).collect::<String>().into_bytes())
+                .map_err(std::io::Error::other)
+        } else {
+            std::fs::read(&path)
+        };
+        match read {
+            Ok(data) => {
+                let name = format!("{}{}", STATE, file_name(&path));
+                append(&mut archive, &name, &data)?;
+                files.push(name);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("could not read {}", path)),
+        }
+    }
+    match mode {
+        ConfigMode::Redacted => append(&mut archive, CONFIG, crate::config_edit::redact(config).as_bytes())?,
+        ConfigMode::Encrypted => {
+            let sealed = crate::secrets::encrypt(config, &config_key(config)?)?;
+            append(&mut archive, CONFIG_ENCRYPTED, sealed.as_bytes())?;
+        }
+    }
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: chrono::Utc::now().to_rfc3339(),
+        files,
+        config: mode,
+    };
+    append(&mut archive, MANIFEST, &serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(archive.into_inner()?.finish()?)
+}
+
+// Unpack a backup on a new machine, with the service stopped. An encrypted config becomes the config file,
+// a redacted one is written next to it as <config>.from-backup to fill in the secrets. Existing files are
+// only replaced with force.
+pub fn restore(archive: &str, config_file: &str, container: bool, key_file: Option<&str>, force: bool) -> anyhow::Result<()> {
+    let reader = std::fs::File::open(archive).with_context(|| format!("could not open {}", archive))?;
+    let mut entries = vec![];
+    for entry in tar::Archive::new(GzDecoder::new(reader)).entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        entries.push((name, data));
+    }
+    let manifest: Manifest = entries
+        .iter()
+        .find(|(name, _)| name == MANIFEST)
+        .map(|(_, data)| serde_json::from_slice(data))
+        .context("no manifest, not a backup of this service")??;
+    println!("Backup of version {} from {}", manifest.version, manifest.created);
+
+    // the config files with their content, decrypted before anything is written
+    let mut configs = vec![];
+    for (name, data) in &entries {
+        let (target, content) = match name.as_str() {
+            CONFIG_ENCRYPTED => (
+                config_file.to_string(),
+                crate::secrets::decrypt(&String::from_utf8_lossy(data), &crate::secrets::key(key_file)?)
+                    .context("could not decrypt the config, wrong passphrase or key file?")?,
+            ),
+            CONFIG => (format!("{}.from-backup", config_file), String::from_utf8_lossy(data).to_string()),
+            _ => continue,
+        };
+        configs.push((name.as_str(), target, content));
+    }
+
+    // the state directory of the restored config, where the service will look for the files: the config in
+    // the backup if it is complete, else the one already in place
+    let restored = configs.iter().find(|(name, _, _)| *name == CONFIG_ENCRYPTED).map(|(_, _, content)| content.clone());
+    let configuration = match restored {
+        Some(content) => crate::load_configuration_from_str(&content, config_file, container),
+        None => crate::try_load_configuration(config_file, container),
+    };
+    match configuration {
+        Ok(configuration) => {
+            if let Some(dir) = &configuration.state_dir {
+                paths::set_state_dir(dir);
+            }
+        }
+        Err(e) => println!("Config not loadable ({}), restoring to {}", e, paths::state_dir().display()),
+    }
+    let known: Vec<String> = state_files().iter().map(|f| file_name(f)).collect();
+    let state: Vec<(String, &Vec<u8>)> = entries
+        .iter()
+        .filter_map(|(name, data)| name.strip_prefix(STATE).map(|n| (n.to_string(), data)))
+        .filter(|(name, _)| known.contains(name))
+        .collect();
+
+    // nothing is written unless every file may be
+    if !force {
+        if let Some((_, target, _)) = configs.iter().find(|(name, target, _)| *name == CONFIG_ENCRYPTED && Path::new(target).exists()) {
+            anyhow::bail!("{} exists, use --force to replace it", target);
+        }
+        if let Some((name, _)) = state.iter().find(|(name, _)| Path::new(&paths::state_file(name)).exists()) {
+            anyhow::bail!("{} exists, use --force to replace the state", paths::state_file(name));
+        }
+    }
+    for (name, target, content) in configs {
+        std::fs::write(&target, content).with_context(|| format!("could not write {}", target))?;
+        match name {
+            CONFIG => println!("Config with masked secrets written to {}, enter the secrets and move it to {}", target, config_file),
+            _ => println!("Config written to {}", target),
+        }
+    }
+    for (name, data) in state {
+        let target = paths::state_file(&name);
+        std::fs::write(&target, data).with_context(|| format!("could not write {}", target))?;
+        println!("Restored {}", target);
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[default]
+    De,
+    En,
+}
+
+// Look of the dashboard and alarm monitor
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct Branding {
+    // shown as page title and heading, defaults to "Fireplan DIVERA Proxy"
+    department: Option<String>,
+    logo_url: Option<String>,
+    // CSS colors, default "#0f172a" and "#ef4444"
+    background_color: Option<String>,
+    accent_color: Option<String>,
+    language: Option<Language>,
+}
+
+// (key, German, English)
+const TEXTS: &[(&str, &str, &str)] = &[
+    ("welcome", "Der Fireplan-DIVERA-Proxy läuft über", "The Fireplan DIVERA proxy service is up and running over"),
+    ("server", "Server", "Server"),
+    ("healthy", "Betriebsbereit", "Healthy"),
+    ("received", "Eingegangen", "Received"),
+    ("submitted", "Übermittelt", "Submitted"),
+    ("statistics", "Statistik", "Statistics"),
+    ("operations", "Einsätze", "operations"),
+    ("dispatch_latency", "Ø Alarmierungsdauer", "avg. dispatch latency"),
+    ("log", "Protokoll", "Log"),
+    ("admin_required", "Das Protokoll erfordert das Admin-Token.", "The log requires the admin token."),
+    ("monitor", "Alarmmonitor", "Alarm monitor"),
+    ("idle", "Kein Einsatz", "No operation"),
+    ("operation", "Einsatz", "Operation"),
+    ("coming", "kommen", "coming"),
+    ("not_coming", "kommen nicht", "not coming"),
+    ("clock", "Uhr", ""),
+    ("manual", "Manuelle Alarmierung", "Manual alarm"),
+    ("stichwort", "Stichwort", "Keyword"),
+    ("address", "Adresse", "Address"),
+    ("coordinates", "Koordinaten (lat,lng)", "Coordinates (lat,lng)"),
+    ("einsatznummer", "Einsatznummer der Leitstelle, falls bekannt", "Leitstelle incident number, if known"),
+    ("text", "Meldung", "Message"),
+    ("rics", "Alarmieren", "Alert"),
+    ("trigger", "Alarm auslösen", "Trigger alarm"),
+    ("triggered", "Alarm ausgelöst", "Alarm triggered"),
+    ("not_triggered", "Alarm NICHT ausgelöst", "Alarm NOT triggered"),
+    ("another", "Weiteren Alarm eingeben", "Enter another alarm"),
+];
+
+// Colors end up in CSS, anything beyond color syntax is dropped
+fn css_color(value: &Option<String>, default: &str) -> String {
+    match value {
+        Some(v) if !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || "#(),.% ".contains(c)) => v.clone(),
+        _ => default.to_string(),
+    }
+}
+
+impl Branding {
+    pub fn department(&self) -> String {
+        self.department.clone().unwrap_or_else(|| "Fireplan DIVERA Proxy".to_string())
+    }
+
+    pub fn logo_url(&self) -> Option<&str> {
+        self.logo_url.as_deref().filter(|u| !u.is_empty())
+    }
+
+    pub fn background_color(&self) -> String {
+        css_color(&self.background_color, "#0f172a")
+    }
+
+    pub fn accent_color(&self) -> String {
+        css_color(&self.accent_color, "#ef4444")
+    }
+
+    pub fn language(&self) -> Language {
+        self.language.unwrap_or_default()
+    }
+
+    // value of the html lang attribute and for date formatting in the browser
+    pub fn lang(&self) -> &'static str {
+        match self.language() {
+            Language::De => "de",
+            Language::En => "en",
+        }
+    }
+
+    pub fn text(&self, key: &str) -> &'static str {
+        TEXTS
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, de, en)| match self.language() {
+                Language::De => *de,
+                Language::En => *en,
+            })
+            .unwrap_or("")
+    }
+}
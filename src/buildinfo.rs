@@ -0,0 +1,64 @@
+use crate::Configuration;
+use serde_derive::Serialize;
+
+#[derive(Clone, Serialize, Debug)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_timestamp: String,
+    pub profile: &'static str,
+    // cargo features of this build
+    pub features: Vec<&'static str>,
+    // optional parts switched on in the configuration
+    pub enabled: Vec<&'static str>,
+    // hash of the active configuration, identifies it without revealing keys or tokens
+    pub config_hash: String,
+}
+
+// FNV-1a, stable across builds and platforms unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn config_hash(configuration: &Configuration) -> String {
+    // through a Value, whose maps are sorted, so HashMap fields don't change the hash between runs
+    let serialized = serde_json::to_value(configuration)
+        .and_then(|v| serde_json::to_vec(&v))
+        .unwrap_or_default();
+    format!("{:016x}", fnv1a(&serialized))
+}
+
+pub fn info(configuration: &Configuration) -> BuildInfo {
+    let timestamp = env!("BUILD_TIMESTAMP").parse::<i64>().unwrap_or(0);
+    let enabled = [
+        ("fireplan", configuration.fireplan_enabled.unwrap_or(true)),
+        ("feuersoftware_connect", configuration.feuersoftware_connect.as_ref().map(|c| c.enabled()).unwrap_or(false)),
+        ("mail_listener", configuration.mail_listener.is_some()),
+        ("pager_input", configuration.pager_input.is_some()),
+        ("divera_poll", configuration.divera_poll.is_some()),
+        ("tls", configuration.tls.unwrap_or(true)),
+        ("notifications", configuration.notifications.as_ref().map(|n| !n.is_empty()).unwrap_or(false)),
+    ];
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("BUILD_GIT_COMMIT"),
+        build_timestamp: chrono::DateTime::from_timestamp(timestamp, 0).map(|t| t.to_rfc3339()).unwrap_or_default(),
+        profile: env!("BUILD_PROFILE"),
+        features: env!("BUILD_FEATURES").split(',').filter(|f| !f.is_empty()).collect(),
+        enabled: enabled.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect(),
+        config_hash: config_hash(configuration),
+    }
+}
+
+pub fn banner(configuration: &Configuration) -> String {
+    let info = info(configuration);
+    format!(
+        "fireplan_alarm_divera {} ({}, built {}, {}), enabled: {}, config {}",
+        info.version,
+        info.git_commit,
+        info.build_timestamp,
+        info.profile,
+        info.enabled.join(", "),
+        info.config_hash
+    )
+}
@@ -0,0 +1,93 @@
+use crate::ParsedData;
+use once_cell::sync::Lazy;
+use serde_derive::Serialize;
+use std::sync::{Arc, RwLock};
+
+// What happens to an alarm on its way through the pipeline, plus service level events.
+// Submission states, history, notifications, metrics and the audit log follow these events.
+#[derive(Clone, Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BusEvent {
+    // a source accepted an alarm, before parsing
+    AlarmReceived { submission_id: String, title: String, standort: String },
+    Parsed { submission_id: String, einsatznrlst: String, standort: String },
+    // all RICs were already alarmed for this Einsatznummer
+    Deduped { alarm: ParsedData },
+    Submitted { alarm: ParsedData },
+    // alarm is None when processing failed before or during parsing; sinks are the ones that did not
+    // accept the alarm, empty when it failed before reaching them
+    Failed { submission_id: String, reason: String, alarm: Option<ParsedData>, sinks: Vec<String> },
+    // refused by a pre-submit hook
+    Vetoed { alarm: ParsedData, hook: String, reason: String },
+    // Leitstelle timestamp older than max_alarm_age_secs
+    Expired { alarm: ParsedData, age_secs: i64 },
+    // Einsatz cancelled by an operator (alarm is the last one recorded for it) or a later alarm for it
+    // suppressed (alarm is that alarm); notify sends a Fehlalarm message to the notification channels
+    Cancelled { einsatznummer: String, reason: String, notify: bool, alarm: Option<ParsedData> },
+    // discarded from a full queue (overflow policy drop_oldest)
+    Dropped { submission_id: String, stage: String, title: String, standort: String },
+    // Einsatzende message; alarm is None when it could not be parsed
+    Closed { submission_id: String, foreign_id: String, title: String, text: String, alarm: Option<ParsedData> },
+    // address banned after repeated authentication failures
+    LockedOut { peer: String, failures: usize, ban_secs: i64 },
+    // configuration read at startup
+    ConfigLoaded { file: String },
+    ComponentDown { component: String, reason: String },
+}
+
+impl BusEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BusEvent::AlarmReceived { .. } => "alarm_received",
+            BusEvent::Parsed { .. } => "parsed",
+            BusEvent::Deduped { .. } => "deduped",
+            BusEvent::Submitted { .. } => "submitted",
+            BusEvent::Failed { .. } => "failed",
+            BusEvent::Vetoed { .. } => "vetoed",
+            BusEvent::Expired { .. } => "expired",
+            BusEvent::Cancelled { .. } => "cancelled",
+            BusEvent::Dropped { .. } => "dropped",
+            BusEvent::Closed { .. } => "closed",
+            BusEvent::LockedOut { .. } => "locked_out",
+            BusEvent::ConfigLoaded { .. } => "config_loaded",
+            BusEvent::ComponentDown { .. } => "component_down",
+        }
+    }
+
+    // Standort of the alarm the event is about, None for events without alarm
+    pub fn standort(&self) -> Option<&str> {
+        match self {
+            BusEvent::AlarmReceived { standort, .. }
+            | BusEvent::Parsed { standort, .. }
+            | BusEvent::Dropped { standort, .. } => Some(standort),
+            BusEvent::Deduped { alarm }
+            | BusEvent::Submitted { alarm }
+            | BusEvent::Vetoed { alarm, .. }
+            | BusEvent::Expired { alarm, .. } => Some(&alarm.standort),
+            BusEvent::Failed { alarm, .. } | BusEvent::Cancelled { alarm, .. } | BusEvent::Closed { alarm, .. } => {
+                alarm.as_ref().map(|a| a.standort.as_str())
+            }
+            BusEvent::LockedOut { .. } | BusEvent::ConfigLoaded { .. } | BusEvent::ComponentDown { .. } => None,
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(&BusEvent) + Send + Sync>;
+
+static SUBSCRIBERS: Lazy<RwLock<Vec<(&'static str, Handler)>>> = Lazy::new(|| RwLock::new(vec![]));
+
+pub fn subscribe(name: &'static str, handler: impl Fn(&BusEvent) + Send + Sync + 'static) {
+    SUBSCRIBERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name, Arc::new(handler)));
+}
+
+// Handlers run in the publishing thread in subscription order, so e.g. the submission state is
+// updated before publish returns; a panicking handler does not keep the others from running
+pub fn publish(event: BusEvent) {
+    let subscribers = SUBSCRIBERS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for (name, handler) in subscribers {
+        crate::supervisor::guard(name, event.name(), || handler(&event));
+    }
+}
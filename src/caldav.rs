@@ -0,0 +1,154 @@
+use crate::bus::BusEvent;
+use crate::export::{ics_escape, ics_fold, ics_time};
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Department calendar the operations are entered in: an event per Einsatz when the alarm is submitted,
+// ended when the Einsatzende message arrives
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct CalDavConfig {
+    // calendar collection, e.g. "https://cloud.example.org/remote.php/dav/calendars/feuerwehr/einsaetze/"
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    // event length until the Einsatzende arrives, defaults to 120
+    open_duration_mins: Option<i64>,
+}
+
+static CONFIG: OnceCell<CalDavConfig> = OnceCell::new();
+
+static DTEND: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^DTEND[^\r\n]*").expect("static regex"));
+
+pub fn init(config: CalDavConfig) {
+    let _ = CONFIG.set(config);
+}
+
+// One event per Einsatznummer, so repeated alarms for the same Einsatz do not add events
+fn resource(config: &CalDavConfig, einsatznummer: &str) -> (String, String) {
+    let id: String = einsatznummer
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let uid = format!("einsatz-{}@fireplan_alarm_divera", id);
+    (format!("{}/einsatz-{}.ics", config.url.trim_end_matches('/'), id), uid)
+}
+
+fn authorized(config: &CalDavConfig, request: RequestBuilder) -> RequestBuilder {
+    match &config.user {
+        Some(user) => request.basic_auth(user, config.password.as_ref()),
+        None => request,
+    }
+}
+
+fn event(config: &CalDavConfig, uid: &str, data: &ParsedData) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let end = now + config.open_duration_mins.unwrap_or(120).max(1) * 60;
+    let summary = format!("Einsatz {} {}", data.einsatznrlst, data.einsatzstichwort);
+    let description = format!(
+        "Einsatzmittel: {}\n\n{}",
+        data.rics.iter().map(|r| r.text.clone()).collect::<Vec<_>>().join(", "),
+        data.zusatzinfo
+    );
+    [
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//fireplan_alarm_divera//Einsaetze//DE".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", ics_time(now)),
+        format!("DTSTART:{}", ics_time(now)),
+        format!("DTEND:{}", ics_time(end)),
+        format!("SUMMARY:{}", ics_escape(summary.trim())),
+        format!("LOCATION:{}", ics_escape(&crate::export::address(data))),
+        format!("DESCRIPTION:{}", ics_escape(&description)),
+        "END:VEVENT".to_string(),
+        "END:VCALENDAR".to_string(),
+    ]
+    .iter()
+    .map(|line| ics_fold(line))
+    .collect()
+}
+
+fn client() -> anyhow::Result<Client> {
+    Ok(Client::builder().timeout(Duration::from_secs(20)).build()?)
+}
+
+pub fn create(config: &CalDavConfig, data: &ParsedData) -> anyhow::Result<()> {
+    let einsatznummer = if data.einsatznrlst.is_empty() { &data.submission_id } else { &data.einsatznrlst };
+    let (url, uid) = resource(config, einsatznummer);
+    let r = authorized(config, client()?.put(&url))
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        // keep the event of an earlier alarm for the same Einsatz
+        .header("If-None-Match", "*")
+        .body(event(config, &uid, data))
+        .send()?;
+    match r.status() {
+        s if s.is_success() => info!("CalDAV: created event for Einsatz {}", einsatznummer),
+        StatusCode::PRECONDITION_FAILED => info!("CalDAV: event for Einsatz {} already exists", einsatznummer),
+        s => return Err(anyhow::anyhow!("CalDAV server answered {}", s)),
+    }
+    Ok(())
+}
+
+// End the event of the Einsatz now
+pub fn close(config: &CalDavConfig, einsatznummer: &str) -> anyhow::Result<()> {
+    let (url, _) = resource(config, einsatznummer);
+    let client = client()?;
+    let r = authorized(config, client.get(&url)).send()?;
+    if r.status() == StatusCode::NOT_FOUND {
+        info!("CalDAV: no event for Einsatz {}", einsatznummer);
+        return Ok(());
+    }
+    if !r.status().is_success() {
+        return Err(anyhow::anyhow!("CalDAV server answered {}", r.status()));
+    }
+    let etag = r.headers().get("ETag").and_then(|e| e.to_str().ok()).map(str::to_string);
+    let calendar = r.text()?;
+    let ended = DTEND.replace(&calendar, format!("DTEND:{}", ics_time(chrono::Utc::now().timestamp())).as_str());
+
+    let mut request = authorized(config, client.put(&url))
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .body(ended.to_string());
+    if let Some(etag) = etag {
+        request = request.header("If-Match", etag);
+    }
+    let status = request.send()?.status();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("CalDAV server answered {}", status));
+    }
+    info!("CalDAV: ended event for Einsatz {}", einsatznummer);
+    Ok(())
+}
+
+pub fn on_event(event: &BusEvent) {
+    let Some(config) = CONFIG.get() else {
+        return;
+    };
+    // blocking HTTP in its own thread, the calendar must not hold up the pipeline
+    match event {
+        BusEvent::Submitted { alarm } => {
+            let alarm = alarm.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = create(config, &alarm) {
+                    error!("CalDAV: could not create event for Einsatz {}: {}", alarm.einsatznrlst, e);
+                }
+            });
+        }
+        BusEvent::Closed { foreign_id, .. } if !foreign_id.is_empty() => {
+            let einsatznummer = foreign_id.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = close(config, &einsatznummer) {
+                    error!("CalDAV: could not end event for Einsatz {}: {}", einsatznummer, e);
+                }
+            });
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,150 @@
+use crate::bus::BusEvent;
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Final outcome of each alarm posted back to the system that sent it (DIVERA automation, FE2, Node-RED),
+// to the configured url and/or the one given with /submit?callback=<url>
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct CallbackConfig {
+    // called for every alarm
+    url: Option<String>,
+    // hosts a callback given with the request may point to, none are allowed if empty
+    allowed_hosts: Option<Vec<String>>,
+    // sent as bearer token, so the receiver can check where the callback comes from
+    token: Option<String>,
+    // defaults to 10
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct RicOutcome {
+    text: String,
+    ric: String,
+    subric: String,
+    outcome: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct Outcome {
+    submission_id: String,
+    einsatznummer: String,
+    standort: String,
+    // submitted, failed, duplicate, vetoed, expired or cancelled
+    outcome: &'static str,
+    reason: String,
+    rics: Vec<RicOutcome>,
+    timestamp: String,
+}
+
+// a submission may end in several events (one per Standort), so callbacks are kept for a while
+const KEEP_SECS: i64 = 3600;
+
+static CONFIG: OnceCell<CallbackConfig> = OnceCell::new();
+// submission id -> (callback url, registered)
+static REQUESTED: Lazy<Mutex<HashMap<String, (String, i64)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn init(config: CallbackConfig) {
+    let _ = CONFIG.set(config);
+}
+
+// Check a callback url given with a request against allowed_hosts
+pub fn check(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid callback url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("callback url must be http or https".to_string());
+    }
+    let host = parsed.host_str().unwrap_or_default();
+    let allowed = CONFIG.get().and_then(|c| c.allowed_hosts.as_ref()).is_some_and(|hosts| hosts.iter().any(|h| h == host));
+    if !allowed {
+        return Err(format!("callback host {} is not in allowed_hosts", host));
+    }
+    Ok(())
+}
+
+// Remember the callback of a submission, before it enters the pipeline
+pub fn register(submission_id: &str, url: &str) {
+    let now = chrono::Utc::now().timestamp();
+    let mut requested = REQUESTED.lock().unwrap_or_else(|e| e.into_inner());
+    requested.retain(|_, (_, registered)| now - *registered < KEEP_SECS);
+    requested.insert(submission_id.to_string(), (url.to_string(), now));
+}
+
+fn outcome(event: &BusEvent) -> Option<Outcome> {
+    let (submission_id, alarm, outcome, reason): (&str, Option<&ParsedData>, &'static str, String) = match event {
+        BusEvent::Submitted { alarm } => (&alarm.submission_id, Some(alarm), "submitted", String::new()),
+        BusEvent::Failed { submission_id, reason, alarm, .. } => (submission_id, alarm.as_ref(), "failed", reason.clone()),
+        BusEvent::Deduped { alarm } => (&alarm.submission_id, Some(alarm), "duplicate", "all RICs already alarmed".to_string()),
+        BusEvent::Vetoed { alarm, hook, reason } => (&alarm.submission_id, Some(alarm), "vetoed", format!("{}: {}", hook, reason)),
+        BusEvent::Expired { alarm, age_secs } => (&alarm.submission_id, Some(alarm), "expired", format!("{} s old", age_secs)),
+        BusEvent::Cancelled { alarm: Some(alarm), reason, .. } => (&alarm.submission_id, Some(alarm), "cancelled", reason.clone()),
+        _ => return None,
+    };
+    let rics = alarm
+        .map(|a| {
+            a.rics
+                .iter()
+                .map(|r| RicOutcome {
+                    text: r.text.clone(),
+                    ric: r.ric.clone(),
+                    subric: r.subric.clone(),
+                    // info RICs are never sent to Fireplan
+                    outcome: if outcome == "submitted" && r.info_only() { "info_only" } else { outcome },
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(Outcome {
+        submission_id: submission_id.to_string(),
+        einsatznummer: alarm.map(|a| a.einsatznrlst.clone()).unwrap_or_default(),
+        standort: alarm.map(|a| a.standort.clone()).unwrap_or_default(),
+        outcome,
+        reason,
+        rics,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+fn post(config: &CallbackConfig, url: &str, outcome: &Outcome) -> anyhow::Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(config.timeout_secs.unwrap_or(10))).build()?;
+    let mut request = client.post(url).json(outcome);
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+    let status = request.send()?.status();
+    anyhow::ensure!(status.is_success(), "answered {}", status);
+    Ok(())
+}
+
+pub fn on_event(event: &BusEvent) {
+    let Some(config) = CONFIG.get() else {
+        return;
+    };
+    let Some(outcome) = outcome(event) else {
+        return;
+    };
+    let requested = REQUESTED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&outcome.submission_id)
+        .map(|(url, _)| url.clone());
+    let urls: Vec<String> = config.url.iter().cloned().chain(requested).collect();
+    if urls.is_empty() {
+        return;
+    }
+    // blocking HTTP in its own thread, a slow receiver must not hold up the pipeline
+    std::thread::spawn(move || {
+        for url in urls {
+            match post(config, &url, &outcome) {
+                Ok(()) => info!("Callback: {} of submission {} reported to {}", outcome.outcome, outcome.submission_id, url),
+                Err(e) => error!("Callback: could not report {} of submission {} to {}: {}", outcome.outcome, outcome.submission_id, url, e),
+            }
+        }
+    });
+}
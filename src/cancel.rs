@@ -0,0 +1,35 @@
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Cancelled Einsatznummern are remembered for one day, later alarms for them are not submitted
+const RETENTION_SECS: i64 = 24 * 60 * 60;
+
+// einsatznummer -> cancelled at
+static CANCELLED: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// False if the Einsatz was already cancelled
+pub fn cancel(einsatznummer: &str) -> bool {
+    let now = chrono::Utc::now().timestamp();
+    let mut cancelled = CANCELLED.lock().unwrap_or_else(|e| e.into_inner());
+    cancelled.retain(|_, at| now - *at < RETENTION_SECS);
+    if cancelled.contains_key(einsatznummer) {
+        return false;
+    }
+    cancelled.insert(einsatznummer.to_string(), now);
+    info!("Einsatz {} cancelled", einsatznummer);
+    true
+}
+
+pub fn is_cancelled(einsatznummer: &str) -> bool {
+    if einsatznummer.is_empty() {
+        return false;
+    }
+    let now = chrono::Utc::now().timestamp();
+    CANCELLED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(einsatznummer)
+        .is_some_and(|at| now - at < RETENTION_SECS)
+}
@@ -0,0 +1,38 @@
+use crate::ParsedData;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Category of an alarm for the yearly report (Brand, THL, Medizinisch, Fehlalarm-Probe, ...):
+// the first rule with a keyword contained (case-insensitive) in one of its fields wins
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct CategoryRule {
+    name: String,
+    keywords: Vec<String>,
+    // text fields of the alarm searched for the keywords, defaults to einsatzstichwort
+    fields: Option<Vec<String>>,
+}
+
+// alarms no rule matches
+pub const DEFAULT_CATEGORY: &str = "Sonstige";
+
+fn matches(rule: &CategoryRule, alarm: &serde_json::Map<String, Value>) -> bool {
+    let default_fields = ["einsatzstichwort".to_string()];
+    let fields = rule.fields.as_deref().unwrap_or(&default_fields);
+    fields.iter().filter_map(|f| alarm.get(f).and_then(Value::as_str)).any(|value| {
+        let value = value.to_lowercase();
+        rule.keywords.iter().any(|k| !k.is_empty() && value.contains(&k.to_lowercase()))
+    })
+}
+
+// Tag the alarm after parsing and overrides, so the category ends up in history, stats and export
+pub fn classify(data: &mut ParsedData, rules: &[CategoryRule]) {
+    let Ok(Value::Object(alarm)) = serde_json::to_value(&*data) else {
+        return;
+    };
+    data.category = rules
+        .iter()
+        .find(|rule| matches(rule, &alarm))
+        .map(|rule| rule.name.clone())
+        .unwrap_or_else(|| DEFAULT_CATEGORY.to_string());
+}
@@ -0,0 +1,55 @@
+use crate::bus::BusEvent;
+use crate::{parser, Configuration, Incoming};
+use log::{error, info};
+use regex::Regex;
+
+// True if title or text of the payload match the configured end-of-operation regex
+pub fn is_closing(configuration: &Configuration, incoming: &Incoming) -> bool {
+    let Some(regex) = &configuration.regex_einsatzende else {
+        return false;
+    };
+    match Regex::new(regex) {
+        Ok(re) => re.is_match(&incoming.payload.title) || re.is_match(&incoming.payload.text),
+        Err(_) => {
+            error!("regex_einsatzende is not a proper regular expression");
+            false
+        }
+    }
+}
+
+// Einsatzende: no alarm, but a clear-notification to the configured channels and a line in the submission log
+pub fn handle(configuration: &Configuration, incoming: &Incoming) {
+    let payload = &incoming.payload;
+    info!("Einsatzende received for {} - {}", payload.foreign_id, payload.title);
+
+    let line = format!(
+        "END - {}\t{} - {}\n",
+        crate::localtime::now(),
+        payload.foreign_id,
+        crate::privacy::redact(&payload.title)
+    );
+    if let Err(e) = crate::privacy::append_log(&crate::paths::submitted_log(), &line) {
+        error!("Failed to write submission log: {}", e);
+    }
+
+    // notification channels, history and submission state follow the Closed event
+    let alarm = match parser::parse(payload.clone(), configuration.clone()) {
+        Ok(mut data) => {
+            data.submission_id = incoming.id.clone();
+            data.received = incoming.received;
+            data.standort = incoming.route.standort.clone();
+            Some(data)
+        }
+        Err(e) => {
+            error!("Could not parse Einsatzende message: {}", e);
+            None
+        }
+    };
+    crate::bus::publish(BusEvent::Closed {
+        submission_id: incoming.id.clone(),
+        foreign_id: payload.foreign_id.clone(),
+        title: payload.title.clone(),
+        text: payload.text.clone(),
+        alarm,
+    });
+}
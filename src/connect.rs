@@ -0,0 +1,74 @@
+use crate::ParsedData;
+use log::{error, info};
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+const DEFAULT_URL: &str = "https://connectapi.feuersoftware.com/interfaces/public/operation";
+
+// FeuerSoftware Connect public API, alarms are posted there in addition to Fireplan
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ConnectConfig {
+    // token of the Connect "public API" interface
+    token: String,
+    enabled: Option<bool>,
+    url: Option<String>,
+}
+
+impl ConnectConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+}
+
+fn operation(data: &ParsedData) -> serde_json::Value {
+    let position = match crate::coordinates::parse(&data.koordinaten) {
+        Some((lat, lng)) => json!({ "Latitude": lat, "Longitude": lng }),
+        None => serde_json::Value::Null,
+    };
+    json!({
+        "Start": chrono::Utc::now().to_rfc3339(),
+        "Status": "new",
+        "AlarmEnabled": true,
+        "Number": data.einsatznrlst,
+        "Keyword": data.einsatzstichwort,
+        "Facts": data.zusatzinfo,
+        "Ric": data.rics.iter().map(|r| r.text.clone()).collect::<Vec<_>>().join(","),
+        "Address": {
+            "Street": data.strasse,
+            "HouseNumber": data.hausnummer,
+            "City": data.ort,
+            "District": data.ortsteil,
+        },
+        "Position": position,
+        "Properties": [ { "Key": "Objekt", "Value": data.objektname } ],
+    })
+}
+
+// Post the alarm as Connect operation; repeated alarms with the same Einsatznummer update the operation
+pub fn submit(config: &ConnectConfig, data: &ParsedData) -> anyhow::Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(20)).build()?;
+    let url = config.url.as_deref().unwrap_or(DEFAULT_URL);
+    info!("[{}] - FeuerSoftware Connect submit triggered", data.standort);
+
+    let r = client
+        .post(format!("{}?updateStrategy=byNumber", url))
+        .header("Authorization", format!("bearer {}", config.token))
+        .json(&operation(data))
+        .send()?;
+    let status = r.status();
+    if status.is_success() {
+        info!("[{}] - Posted alarm {} to FeuerSoftware Connect", data.standort, data.einsatznrlst);
+        Ok(())
+    } else {
+        error!(
+            "[{}] - FeuerSoftware Connect answered {}: {}",
+            data.standort,
+            status,
+            r.text().unwrap_or_default()
+        );
+        Err(anyhow::anyhow!("FeuerSoftware Connect answered {}", status))
+    }
+}
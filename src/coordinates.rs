@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    LatLng,
+    LngLat,
+}
+
+// How the Fireplan tenant wants the koordinaten string; alarms keep "lat,lng" internally
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct CoordinateFormat {
+    // decimal places, unchanged if omitted
+    precision: Option<usize>,
+    // defaults to ","
+    separator: Option<String>,
+    order: Option<Order>,
+    // overrides separator and order, e.g. "N {lat} E {lng}"
+    template: Option<String>,
+}
+
+// "lat,lng" -> (lat, lng), None unless both are numbers
+pub fn parse(koordinaten: &str) -> Option<(f64, f64)> {
+    let (lat, lng) = koordinaten.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lng.trim().parse().ok()?))
+}
+
+impl CoordinateFormat {
+    // Empty when the alarm has no usable coordinates
+    pub fn format(&self, koordinaten: &str) -> String {
+        let Some((lat, lng)) = parse(koordinaten) else {
+            return String::new();
+        };
+        let number = |v: f64| match self.precision {
+            Some(p) => format!("{:.*}", p, v),
+            None => v.to_string(),
+        };
+        let (lat, lng) = (number(lat), number(lng));
+        if let Some(template) = &self.template {
+            return template.replace("{lat}", &lat).replace("{lng}", &lng);
+        }
+        let separator = self.separator.as_deref().unwrap_or(",");
+        match self.order.unwrap_or_default() {
+            Order::LatLng => format!("{}{}{}", lat, separator, lng),
+            Order::LngLat => format!("{}{}{}", lng, separator, lat),
+        }
+    }
+}
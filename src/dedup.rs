@@ -0,0 +1,171 @@
+use crate::Ric;
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+type Key = (String, String, String);
+
+// (einsatznummer, ric, standort) -> alarmed
+pub type Table = HashMap<Key, i64>;
+
+// How long an alarmed RIC is remembered (dedup_ttl_hours), longer than any operation lasts
+const DEFAULT_TTL_HOURS: i64 = 72;
+static TTL_SECS: OnceCell<i64> = OnceCell::new();
+
+pub fn set_ttl(hours: Option<i64>) {
+    let _ = TTL_SECS.set(hours.unwrap_or(DEFAULT_TTL_HOURS).max(1) * 3600);
+}
+
+fn ttl_secs() -> i64 {
+    TTL_SECS.get().copied().unwrap_or(DEFAULT_TTL_HOURS * 3600)
+}
+
+// RICs already alarmed per Einsatznummer, kept across restarts so a restart
+// during an operation does not alarm the same units again
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DedupEntry {
+    pub einsatznummer: String,
+    pub ric: String,
+    // a RIC alarmed at several Standorte is remembered per Standort
+    #[serde(default)]
+    pub standort: String,
+    // unix time of the first alarm
+    pub alarmed: i64,
+}
+
+static TABLE: Lazy<Mutex<Table>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn entry(((einsatznummer, ric, standort), alarmed): (&Key, &i64)) -> DedupEntry {
+    DedupEntry {
+        einsatznummer: einsatznummer.clone(),
+        ric: ric.clone(),
+        standort: standort.clone(),
+        alarmed: *alarmed,
+    }
+}
+
+fn line(entry: &DedupEntry) -> String {
+    format!("{}\n", serde_json::to_string(entry).unwrap_or_default())
+}
+
+// The whole table as JSON lines, after entries expired or were removed
+fn save(table: &Table) {
+    let file = crate::paths::dedup_file();
+    let tmp = format!("{}.tmp", file);
+    let content: String = table.iter().map(|e| line(&entry(e))).collect();
+    if let Err(e) = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, &file)) {
+        error!("Could not save dedup table {}: {}", file, e);
+    }
+}
+
+// Newly claimed entries are appended, the file is only rewritten when entries go
+fn append(entries: &[DedupEntry]) {
+    let file = crate::paths::dedup_file();
+    let content: String = entries.iter().map(line).collect();
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut f| f.write_all(content.as_bytes()));
+    if let Err(e) = result {
+        error!("Could not save dedup table {}: {}", file, e);
+    }
+}
+
+// Forget entries older than the TTL, true if there were any
+fn expire(table: &mut Table, now: i64) -> bool {
+    let before = table.len();
+    table.retain(|_, alarmed| now - *alarmed < ttl_secs());
+    table.len() != before
+}
+
+pub fn load() {
+    let file = crate::paths::dedup_file();
+    // the JSON array written by earlier versions
+    let legacy = crate::paths::state_file("fireplan_alarm_divera_dedup.json");
+    let entries: Vec<DedupEntry> = match std::fs::read_to_string(&file) {
+        Ok(content) => content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|l| match serde_json::from_str(l) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!("Skipping unreadable dedup entry in {}: {}", file, e);
+                    None
+                }
+            })
+            .collect(),
+        Err(_) => match std::fs::read(&legacy) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                warn!("Could not read dedup table {}: {}, starting empty", legacy, e);
+                vec![]
+            }),
+            Err(_) => vec![],
+        },
+    };
+    let mut table = TABLE.lock().unwrap_or_else(|e| e.into_inner());
+    for e in entries {
+        table.entry((e.einsatznummer, e.ric, e.standort)).or_insert(e.alarmed);
+    }
+    expire(&mut table, chrono::Utc::now().timestamp());
+    info!("Loaded {} dedup entries", table.len());
+    // compacted once at startup, appended to from here on
+    save(&table);
+    if std::path::Path::new(&legacy).exists() {
+        let _ = std::fs::remove_file(&legacy);
+    }
+}
+
+// The RICs not alarmed yet for this Einsatznummer; they count as alarmed from now on.
+// A panic in another worker must not disable deduplication, so a poisoned lock is recovered.
+pub fn claim(einsatznummer: &str, standort: &str, rics: &[Ric]) -> Vec<Ric> {
+    let mut table = TABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let now = chrono::Utc::now().timestamp();
+    let expired = expire(&mut table, now);
+    let (new_rics, claimed) = claim_in(&mut table, einsatznummer, standort, rics, now);
+    if expired {
+        save(&table);
+    } else if !claimed.is_empty() {
+        append(&claimed);
+    }
+    new_rics
+}
+
+// The in-memory part of claim: the RICs not in the table yet, entered as alarmed at `now`, with their new entries
+pub fn claim_in(table: &mut Table, einsatznummer: &str, standort: &str, rics: &[Ric], now: i64) -> (Vec<Ric>, Vec<DedupEntry>) {
+    let mut new_rics = vec![];
+    let mut claimed = vec![];
+    for ric in rics {
+        let key = (einsatznummer.to_string(), ric.ric.clone(), standort.to_string());
+        if let Entry::Vacant(vacant) = table.entry(key.clone()) {
+            vacant.insert(now);
+            new_rics.push(ric.clone());
+            claimed.push(entry((&key, &now)));
+        }
+    }
+    (new_rics, claimed)
+}
+
+pub fn list() -> Vec<DedupEntry> {
+    let table = TABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries: Vec<DedupEntry> = table.iter().map(entry).collect();
+    entries.sort_by(|a, b| b.alarmed.cmp(&a.alarmed).then(a.ric.cmp(&b.ric)));
+    entries
+}
+
+// Forget entries so the next alarm for the Einsatz is sent again; all RICs of the Einsatz if ric is None
+pub fn remove(einsatznummer: &str, ric: Option<&str>) -> usize {
+    let mut table = TABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let before = table.len();
+    table.retain(|(nr, r, _), _| !(nr == einsatznummer && ric.map(|ric| ric == r).unwrap_or(true)));
+    let removed = before - table.len();
+    if removed > 0 {
+        save(&table);
+    }
+    removed
+}
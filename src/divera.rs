@@ -0,0 +1,114 @@
+use crate::{Event, Incoming, Route, SubmitPayload};
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const DEFAULT_URL: &str = "https://app.divera247.com/api/last-alarm";
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct DiveraPollConfig {
+    accesskey: String,
+    interval_secs: Option<u64>,
+    url: Option<String>,
+}
+
+fn as_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn as_string_list(v: &Value) -> Vec<String> {
+    v.as_array()
+        .map(|a| a.iter().map(as_string).collect())
+        .unwrap_or_default()
+}
+
+// Convert a DIVERA alarm object into the webhook payload format
+fn to_payload(alarm: &Value) -> SubmitPayload {
+    SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        id: alarm["id"].as_u64().unwrap_or(0),
+        foreign_id: as_string(&alarm["foreign_id"]),
+        title: as_string(&alarm["title"]),
+        text: as_string(&alarm["text"]),
+        address: as_string(&alarm["address"]),
+        lat: as_string(&alarm["lat"]),
+        lng: as_string(&alarm["lng"]),
+        priority: match &alarm["priority"] {
+            Value::Bool(b) => *b as u8,
+            v => v.as_u64().unwrap_or(0) as u8,
+        },
+        cluster: as_string_list(&alarm["cluster"]),
+        group: as_string_list(&alarm["group"]),
+        vehicle: as_string_list(&alarm["vehicle"]),
+        ts_create: alarm["ts_create"].as_i64().unwrap_or(0),
+        ts_update: alarm["ts_update"].as_i64().unwrap_or(0),
+    }
+}
+
+fn fetch_last_alarm(client: &Client, url: &str, accesskey: &str) -> anyhow::Result<Option<SubmitPayload>> {
+    let request_url = format!("{}?accesskey={}", url, accesskey);
+    let r = client.get(&request_url).send()?;
+    let status = r.status();
+    let body = r.text()?;
+    crate::trace::exchange("DIVERA", "GET", &request_url, "", status.as_str(), &body);
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("DIVERA answered {}", status));
+    }
+    let response: Value = serde_json::from_str(&body)?;
+
+    if !response["success"].as_bool().unwrap_or(false) {
+        // DIVERA answers success=false when there is no active alarm
+        return Ok(None);
+    }
+    Ok(Some(to_payload(&response["data"])))
+}
+
+pub fn start_poller(config: DiveraPollConfig) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let url = config.url.clone().unwrap_or_else(|| DEFAULT_URL.to_string());
+        let interval = Duration::from_secs(config.interval_secs.unwrap_or(30).max(5));
+        let client = match Client::builder().timeout(Duration::from_secs(20)).build() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("DIVERA poller: could not build HTTP client: {}", e);
+                return;
+            }
+        };
+
+        info!("DIVERA poller started, polling {} every {:?}", url, interval);
+
+        // (id, ts_update) of the last seen alarm; the alarm present at startup is only remembered, not submitted
+        let mut last_seen: Option<(u64, i64)> = None;
+        let mut primed = false;
+
+        loop {
+            match fetch_last_alarm(&client, &url, &config.accesskey) {
+                Ok(Some(payload)) => {
+                    let key = (payload.id, payload.ts_update);
+                    if !primed {
+                        info!("DIVERA poller: current alarm {} at startup, not submitting", payload.id);
+                    } else if last_seen != Some(key) {
+                        info!("DIVERA poller: new alarm {} - {}", payload.id, payload.title);
+                        crate::log_received(&payload);
+                        if let Err(e) = crate::send_event(Event::Submit(Incoming::new(payload, Route::default()))) {
+                            error!("DIVERA poller: could not send event: {}", e);
+                        }
+                    }
+                    last_seen = Some(key);
+                    primed = true;
+                }
+                Ok(None) => primed = true,
+                Err(e) => warn!("DIVERA poller: request failed: {}", e),
+            }
+            std::thread::sleep(interval);
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+// Typed errors of the pipeline stages, so callers can tell retryable from permanent failures and
+// metrics can be labeled by kind; anyhow stays at the edges (subcommands, scripts, hooks)
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("config file {file} missing: {source}")]
+    Missing { file: String, source: std::io::Error },
+    #[error("config file {file} is not valid TOML: {source}")]
+    Syntax { file: String, source: toml::de::Error },
+    #[error("could not decrypt secrets in {file}: {reason}")]
+    Secrets { file: String, reason: String },
+    #[error("invalid configuration in {file}: {source}")]
+    Invalid { file: String, source: toml::de::Error },
+    #[error("invalid configuration in {file}: {}", .reasons.join("; "))]
+    Rejected { file: String, reasons: Vec<String> },
+}
+
+// Alarm input that cannot be turned into a payload
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("JSON parse error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("form parse error: {0}")]
+    Form(#[from] serde_urlencoded::de::Error),
+    #[error("multipart body without boundary")]
+    MultipartBoundary,
+    #[error("multipart body without form fields")]
+    MultipartEmpty,
+    #[error("unsupported FE2 message type {0}")]
+    Fe2Type(String),
+    #[error("FE2 message without data object")]
+    Fe2Data,
+    #[error("FE2 alarm without keyword and message")]
+    Fe2Empty,
+    #[error("manual alarm needs a Stichwort and at least one RIC")]
+    ManualIncomplete,
+}
+
+#[derive(Debug, Error)]
+pub enum FireplanError {
+    // no HTTP answer: DNS, connect, TLS or timeout
+    #[error("Fireplan not reachable: {0}")]
+    Unreachable(#[from] reqwest::Error),
+    #[error("Register answered {0}")]
+    Register(StatusCode),
+    #[error("could not deserialize token key: {0}")]
+    Token(#[from] serde_json::Error),
+    #[error("Fireplan answered {0}")]
+    Rejected(StatusCode),
+}
+
+impl FireplanError {
+    // Worth trying again later: network trouble, overload and server errors. A rejected API key or
+    // alarm stays rejected until the configuration changes.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FireplanError::Unreachable(_) => true,
+            FireplanError::Register(status) | FireplanError::Rejected(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS || *status == StatusCode::REQUEST_TIMEOUT
+            }
+            FireplanError::Token(_) => false,
+        }
+    }
+
+    // metric label
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FireplanError::Unreachable(_) => "unreachable",
+            FireplanError::Register(_) => "register",
+            FireplanError::Token(_) => "token",
+            FireplanError::Rejected(_) => "rejected",
+        }
+    }
+}
+
+// Mail intake
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("unexpected answer '{0}'")]
+    UnexpectedAnswer(String),
+}
@@ -0,0 +1,113 @@
+use crate::bus::BusEvent;
+use crate::summaries::SummaryCache;
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    #[default]
+    Osrm,
+    Valhalla,
+}
+
+// Driving distance and time from the station to the alarm, added to the alarm notification and the monitor page
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct EtaConfig {
+    // self-hosted routing server, e.g. "http://osrm.local:5000" or "http://valhalla.local:8002"
+    url: String,
+    engine: Option<Engine>,
+    // "lat,lng" of the Gerätehaus
+    station: String,
+    // defaults to 5
+    timeout_secs: Option<u64>,
+}
+
+static CONFIG: OnceCell<(EtaConfig, (f64, f64))> = OnceCell::new();
+static SUMMARIES: SummaryCache = SummaryCache::new("ETA");
+
+pub fn init(config: EtaConfig) {
+    match crate::coordinates::parse(&config.station) {
+        Some(station) => {
+            let _ = CONFIG.set((config, station));
+        }
+        None => error!("ETA: station {} is not \"lat,lng\", routing disabled", config.station),
+    }
+}
+
+fn send(request: reqwest::blocking::RequestBuilder) -> anyhow::Result<Value> {
+    let r = request.send()?;
+    anyhow::ensure!(r.status().is_success(), "answered {}", r.status());
+    Ok(r.json()?)
+}
+
+// (meters, seconds) of the fastest car route
+fn route(config: &EtaConfig, from: (f64, f64), to: (f64, f64)) -> anyhow::Result<(f64, f64)> {
+    let client = Client::builder().timeout(Duration::from_secs(config.timeout_secs.unwrap_or(5))).build()?;
+    let base = config.url.trim_end_matches('/');
+    match config.engine.unwrap_or_default() {
+        Engine::Osrm => {
+            // OSRM wants lng,lat
+            let url = format!("{}/route/v1/driving/{},{};{},{}?overview=false", base, from.1, from.0, to.1, to.0);
+            let answer = send(client.get(url))?;
+            let route = &answer["routes"][0];
+            match (route["distance"].as_f64(), route["duration"].as_f64()) {
+                (Some(m), Some(s)) => Ok((m, s)),
+                _ => anyhow::bail!("no route ({})", answer["code"].as_str().unwrap_or("no code")),
+            }
+        }
+        Engine::Valhalla => {
+            let body = json!({
+                "locations": [{ "lat": from.0, "lon": from.1 }, { "lat": to.0, "lon": to.1 }],
+                "costing": "auto",
+                "units": "kilometers",
+            });
+            let answer = send(client.post(format!("{}/route", base)).json(&body))?;
+            let summary = &answer["trip"]["summary"];
+            match (summary["length"].as_f64(), summary["time"].as_f64()) {
+                (Some(km), Some(s)) => Ok((km * 1000.0, s)),
+                _ => anyhow::bail!("no route"),
+            }
+        }
+    }
+}
+
+// "Anfahrt: 12.4 km, ca. 14 min"
+fn describe(meters: f64, seconds: f64) -> String {
+    format!("Anfahrt: {:.1} km, ca. {} min", meters / 1000.0, (seconds / 60.0).ceil().max(1.0) as u64)
+}
+
+// Route already computed for the Einsatz, for the alarm notification and the monitor page
+pub fn cached(einsatznrlst: &str) -> Option<String> {
+    SUMMARIES.get(einsatznrlst)
+}
+
+// Compute distance and driving time from the station on a thread, once per Einsatz with coordinates
+pub fn prefetch(alarm: &ParsedData) {
+    let Some((config, station)) = CONFIG.get() else {
+        return;
+    };
+    let Some(target) = crate::coordinates::parse(&alarm.koordinaten) else {
+        return;
+    };
+    let einsatznrlst = alarm.einsatznrlst.clone();
+    SUMMARIES.fetch(&alarm.einsatznrlst, move || {
+        let (meters, seconds) = route(config, *station, target)?;
+        let summary = describe(meters, seconds);
+        info!("ETA for {}: {}", einsatznrlst, summary);
+        Ok(summary)
+    });
+}
+
+// Also for alarms sent again from the outbox, for the monitor page
+pub fn on_event(event: &BusEvent) {
+    if let BusEvent::Submitted { alarm } = event {
+        prefetch(alarm);
+    }
+}
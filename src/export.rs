@@ -0,0 +1,185 @@
+use crate::history::HistoryEntry;
+use crate::ParsedData;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+// One operation, combined from all history entries with the same Einsatznummer
+pub struct Operation {
+    pub alarm: ParsedData,
+    pub start: i64,
+    // time of the Einsatzende message, if one was received
+    pub end: Option<i64>,
+    pub results: Vec<String>,
+}
+
+// Group history entries into operations, oldest first
+pub fn operations(entries: &[HistoryEntry]) -> Vec<Operation> {
+    let mut operations: Vec<Operation> = vec![];
+    for entry in entries {
+        let nr = &entry.alarm.einsatznrlst;
+        let existing = operations
+            .iter_mut()
+            .find(|o| !nr.is_empty() && &o.alarm.einsatznrlst == nr);
+
+        match (existing, entry.result.as_str()) {
+            (Some(op), "closed") => op.end = Some(entry.received),
+            (None, "closed") => {}
+            (Some(op), result) => {
+                for ric in &entry.alarm.rics {
+                    if !op.alarm.rics.contains(ric) {
+                        op.alarm.rics.push(ric.clone());
+                    }
+                }
+                op.results.push(result.to_string());
+            }
+            (None, result) => operations.push(Operation {
+                alarm: entry.alarm.clone(),
+                start: entry.received,
+                end: None,
+                results: vec![result.to_string()],
+            }),
+        }
+    }
+    operations
+}
+
+pub fn address(alarm: &ParsedData) -> String {
+    [
+        format!("{} {}", alarm.strasse, alarm.hausnummer).trim().to_string(),
+        alarm.ortsteil.clone(),
+        alarm.ort.clone(),
+    ]
+    .into_iter()
+    .filter(|s| !s.trim().is_empty())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+pub fn ics_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// RFC 5545 line folding at 75 octets
+pub fn ics_fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+pub fn ics_time(ts: i64) -> String {
+    DateTime::<Utc>::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+pub fn ics(operations: &[Operation]) -> String {
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//fireplan_alarm_divera//Einsaetze//DE",
+        "X-WR-CALNAME:Einsätze",
+    ] {
+        out.push_str(&ics_fold(line));
+    }
+
+    for op in operations {
+        let a = &op.alarm;
+        // without Einsatzende the event is shown with a nominal duration of one hour
+        let end = op.end.filter(|e| *e > op.start).unwrap_or(op.start + 3600);
+        let description = format!(
+            "Einsatzmittel: {}\nErgebnis: {}\n\n{}",
+            a.rics.iter().map(|r| r.text.clone()).collect::<Vec<_>>().join(", "),
+            op.results.join(", "),
+            a.zusatzinfo
+        );
+        for line in [
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@fireplan_alarm_divera", ics_escape(&a.submission_id)),
+            format!("DTSTAMP:{}", ics_time(op.start)),
+            format!("DTSTART:{}", ics_time(op.start)),
+            format!("DTEND:{}", ics_time(end)),
+            format!("SUMMARY:{}", ics_escape(format!("{} {}", a.einsatznrlst, a.einsatzstichwort).trim())),
+            format!("LOCATION:{}", ics_escape(&address(a))),
+            format!("DESCRIPTION:{}", ics_escape(&description)),
+            "END:VEVENT".to_string(),
+        ] {
+            out.push_str(&ics_fold(&line));
+        }
+    }
+
+    out.push_str(&ics_fold("END:VCALENDAR"));
+    out
+}
+
+// Operations starting within [from, to] (local dates, inclusive), open ends if omitted
+pub fn filter_range(operations: Vec<Operation>, from: Option<NaiveDate>, to: Option<NaiveDate>, tz: Tz) -> Vec<Operation> {
+    operations
+        .into_iter()
+        .filter(|op| {
+            let day = Utc
+                .timestamp_opt(op.start, 0)
+                .single()
+                .map(|d| d.with_timezone(&tz).date_naive());
+            match day {
+                Some(day) => from.map(|f| day >= f).unwrap_or(true) && to.map(|t| day <= t).unwrap_or(true),
+                None => false,
+            }
+        })
+        .collect()
+}
+
+fn local_time(ts: i64, tz: Tz) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|d| d.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+// Semicolon separated with UTF-8 BOM, so German Excel opens it directly
+pub fn csv(operations: &[Operation], tz: Tz) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(vec![0xEF, 0xBB, 0xBF]);
+    writer.write_record([
+        "Alarmzeit",
+        "Einsatzende",
+        "Einsatznummer",
+        "Stichwort",
+        "Kategorie",
+        "Adresse",
+        "Objekt",
+        "Standort",
+        "Einsatzmittel",
+        "RICs",
+        "Ergebnis",
+    ])?;
+    for op in operations {
+        let a = &op.alarm;
+        writer.write_record([
+            local_time(op.start, tz),
+            op.end.map(|e| local_time(e, tz)).unwrap_or_default(),
+            a.einsatznrlst.clone(),
+            a.einsatzstichwort.clone(),
+            crate::stats::category(a),
+            address(a),
+            a.objektname.clone(),
+            a.standort.clone(),
+            a.rics.iter().map(|r| r.text.clone()).collect::<Vec<_>>().join(", "),
+            a.rics.iter().map(|r| format!("{}{}", r.ric, r.subric)).collect::<Vec<_>>().join(", "),
+            op.results.join(", "),
+        ])?;
+    }
+    Ok(writer.into_inner()?)
+}
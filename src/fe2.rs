@@ -0,0 +1,107 @@
+use crate::error::ParseError;
+use crate::SubmitPayload;
+use serde_json::Value;
+
+// Alamos FE2 external interface (aPager / "Externe Schnittstelle"):
+// { "type": "ALARM", "timestamp": "...", "sender": "...", "authorization": "<token>",
+//   "data": { "externalId", "keyword", "keyword_description", "message": [...],
+//             "location": { "street", "house", "zip", "city", "building", "coordinate": [lng, lat] },
+//             "units": [ { "address": "..." } ] } }
+
+fn as_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.trim().to_string(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// Token sent in the body, FE2 can't add query parameters to some outputs
+pub fn authorization(body: &Value) -> Option<String> {
+    body["authorization"].as_str().map(|s| s.to_string())
+}
+
+// (lat, lng) from a GeoJSON style [lng, lat] array or a {lat, lon|lng} object
+fn coordinate(location: &Value) -> (String, String) {
+    let c = &location["coordinate"];
+    if let Some([lng, lat]) = c.as_array().map(|a| a.as_slice()) {
+        return (as_string(lat), as_string(lng));
+    }
+    let lng = if c["lon"].is_null() { &c["lng"] } else { &c["lon"] };
+    (as_string(&c["lat"]), as_string(lng))
+}
+
+// Convert an FE2 alarm into the webhook payload; the units go into the text after the
+// Einsatzmittel marker so the RIC matching of the parser applies as for Leitstelle texts
+pub fn to_payload(body: &Value, einsatzmittel_marker: &str) -> Result<SubmitPayload, ParseError> {
+    let kind = body["type"].as_str().unwrap_or("ALARM");
+    if !kind.eq_ignore_ascii_case("ALARM") {
+        return Err(ParseError::Fe2Type(kind.to_string()));
+    }
+    let data = &body["data"];
+    if !data.is_object() {
+        return Err(ParseError::Fe2Data);
+    }
+
+    let keyword = as_string(&data["keyword"]);
+    let description = as_string(&data["keyword_description"]);
+    let title = match (keyword.is_empty(), description.is_empty()) {
+        (false, false) => format!("{} {}", keyword, description),
+        (false, true) => keyword,
+        _ => description,
+    };
+
+    let message = match &data["message"] {
+        Value::Array(lines) => lines.iter().map(as_string).collect::<Vec<_>>().join("\n"),
+        other => as_string(other),
+    };
+    let units: Vec<String> = data["units"]
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .map(|u| if u.is_object() { as_string(&u["address"]) } else { as_string(u) })
+                .filter(|u| !u.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut text = message;
+    if !units.is_empty() {
+        text = format!("{}\n{} {}", text, einsatzmittel_marker, units.join(", "));
+    }
+
+    if title.is_empty() && text.trim().is_empty() {
+        return Err(ParseError::Fe2Empty);
+    }
+
+    let location = &data["location"];
+    let street = format!("{} {}", as_string(&location["street"]), as_string(&location["house"]));
+    let city = format!("{} {}", as_string(&location["zip"]), as_string(&location["city"]));
+    let address = [street.trim(), city.trim()]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (lat, lng) = coordinate(location);
+
+    let timestamp = body["timestamp"]
+        .as_str()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.timestamp())
+        .or_else(|| body["timestamp"].as_i64().map(|t| if t > 1_000_000_000_000 { t / 1000 } else { t }))
+        .unwrap_or_else(|| chrono::Utc::now().timestamp());
+
+    Ok(SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        foreign_id: as_string(&data["externalId"]),
+        title,
+        text,
+        address,
+        lat,
+        lng,
+        vehicle: units,
+        ts_create: timestamp,
+        ts_update: timestamp,
+        ..Default::default()
+    })
+}
@@ -1,18 +1,20 @@
-use crate::ParsedData;
-use log::{error, info};
+use crate::coordinates::CoordinateFormat;
+use crate::error::FireplanError;
+use crate::{ParsedData, Ric};
+use log::{error, info, warn};
 use reqwest::blocking::Client;
 use serde_derive::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use once_cell::sync::{Lazy, OnceCell};
+use std::sync::{Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::JoinHandle;
 
-#[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq, Debug)]
+#[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq, Debug, Default)]
 struct FireplanAlarm {
     ric: String,
-    subRIC: String,
+    #[serde(rename = "subRIC")]
+    sub_ric: String,
     einsatznrlst: String,
     strasse: String,
     hausnummer: String,
@@ -29,184 +31,431 @@ struct FireplanAlarm {
 #[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq, Debug)]
 struct ApiKey {
     utoken: String,
+    // lifetime in seconds, if Fireplan says so; 0 or less means the token must not be cached
+    #[serde(default, alias = "expiresIn")]
+    expires_in: Option<i64>,
 }
 
-// Token cache: standort -> (token, stored_at)
-static TOKEN_CACHE: Lazy<Mutex<HashMap<String, (String, Instant)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-const TOKEN_TTL: Duration = Duration::from_secs(30 * 60);
+#[derive(Clone, Debug)]
+struct CachedToken {
+    token: String,
+    fetched: Instant,
+    expires: Instant,
+}
 
-fn get_api_token(client: &Client, standort: &str, api_key: &str) -> Option<String> {
-    // Try cached value
-    if let Ok(cache) = TOKEN_CACHE.lock() {
-        if let Some((tok, ts)) = cache.get(standort) {
-            if ts.elapsed() < TOKEN_TTL {
-                info!("Returning token from cache, stored {:?}", ts);
-                return Some(tok.clone());
-            }
+impl CachedToken {
+    fn new(token: String, ttl: Duration) -> CachedToken {
+        let fetched = Instant::now();
+        CachedToken { token, fetched, expires: fetched + ttl }
+    }
+
+    fn valid(&self) -> bool {
+        Instant::now() < self.expires
+    }
+
+    // Renewed in the background this long before expiry: five minutes, a fifth of shorter lifetimes
+    fn due(&self) -> bool {
+        let margin = TOKEN_REFRESH_MARGIN.min((self.expires - self.fetched) / 5);
+        Instant::now() + margin >= self.expires
+    }
+}
+
+// Token cache: standort -> token
+type TokenCache = HashMap<String, CachedToken>;
+static TOKEN_CACHE: Lazy<RwLock<TokenCache>> = Lazy::new(|| RwLock::new(HashMap::new()));
+// Lifetime of tokens without expiry information (fireplan_token_ttl_secs)
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(30 * 60);
+static TOKEN_TTL: OnceCell<Duration> = OnceCell::new();
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+pub fn set_token_ttl(secs: Option<u64>) {
+    let _ = TOKEN_TTL.set(secs.map(Duration::from_secs).unwrap_or(DEFAULT_TOKEN_TTL));
+}
+
+// Seconds until the `exp` claim if the token is a JWT, 0 for an expired one so it is not cached
+fn jwt_lifetime(token: &str) -> Option<u64> {
+    use base64::Engine;
+    let claims = token.split('.').nth(1)?;
+    let claims = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(claims.trim_end_matches('=')).ok()?;
+    let exp = serde_json::from_slice::<serde_json::Value>(&claims).ok()?["exp"].as_i64()?;
+    Some((exp - chrono::Utc::now().timestamp()).max(0) as u64)
+}
+
+// Lifetime from the Register answer (expires_in or the exp claim of a JWT), else the configured one
+fn token_ttl(key: &ApiKey) -> Duration {
+    match key.expires_in.map(|secs| secs.max(0) as u64).or_else(|| jwt_lifetime(&key.utoken)) {
+        Some(secs) => Duration::from_secs(secs),
+        None => TOKEN_TTL.get().copied().unwrap_or(DEFAULT_TOKEN_TTL),
+    }
+}
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Timeout for Fireplan calls (fireplan_timeout_secs), a hanging Register call must not block a worker forever
+static TIMEOUT: OnceCell<Duration> = OnceCell::new();
+
+pub fn set_timeout(secs: Option<u64>) {
+    let _ = TIMEOUT.set(secs.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT));
+}
+
+// Fireplan API location (fireplan_url), e.g. a mock server in the integration tests
+const DEFAULT_URL: &str = "https://data.fireplan.de";
+static BASE_URL: OnceCell<String> = OnceCell::new();
+
+pub fn set_url(url: Option<String>) {
+    let _ = BASE_URL.set(url.unwrap_or_else(|| DEFAULT_URL.to_string()).trim_end_matches('/').to_string());
+}
+
+fn url(path: &str) -> String {
+    format!("{}{}", BASE_URL.get().map(String::as_str).unwrap_or(DEFAULT_URL), path)
+}
+
+// Concurrent Fireplan submissions per Standort (fireplan_max_concurrency), so a burst of alarms
+// does not hit the API with dozens of simultaneous requests sharing one token
+const DEFAULT_MAX_CONCURRENCY: usize = 2;
+static MAX_CONCURRENCY: OnceCell<usize> = OnceCell::new();
+static IN_FLIGHT: Lazy<(Mutex<HashMap<String, usize>>, Condvar)> = Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+
+pub fn set_max_concurrency(max: Option<usize>) {
+    let _ = MAX_CONCURRENCY.set(max.unwrap_or(DEFAULT_MAX_CONCURRENCY).max(1));
+}
+
+// koordinaten as the tenant expects them (fireplan_coordinates)
+static COORDINATE_FORMAT: OnceCell<CoordinateFormat> = OnceCell::new();
+
+pub fn set_coordinate_format(format: Option<CoordinateFormat>) {
+    let _ = COORDINATE_FORMAT.set(format.unwrap_or_default());
+}
+
+// FireplanAlarm field -> template (fireplan_fields), see map_fields
+static FIELD_TEMPLATES: OnceCell<HashMap<String, String>> = OnceCell::new();
+
+pub fn set_field_templates(templates: Option<HashMap<String, String>>) {
+    let templates = templates.unwrap_or_default();
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(FireplanAlarm::default()) {
+        for field in templates.keys().filter(|f| !fields.contains_key(*f)) {
+            error!("fireplan_fields: {} is not a Fireplan alarm field, ignored", field);
         }
     }
+    let _ = FIELD_TEMPLATES.set(templates);
+}
 
-    // Fetch fresh token
-    let token_string = match client
-        .get(format!(
-            "https://data.fireplan.de/api/Register/{}",
-            standort
-        ))
-        .header("API-Key", api_key.to_string())
-        .header("accept", "*/*")
-        .send()
-    {
-        Ok(r) => {
-            if r.status().is_success() {
-                match r.text() {
-                    Ok(t) => t,
-                    Err(e) => {
-                        error!("[{}] - Could not get API Key body: {}", standort, e);
-                        return None;
-                    }
-                }
-            } else {
-                error!(
-                    "[{}] - Could not get API Key: {:?}",
-                    standort,
-                    r.status()
-                );
-                return None;
+// Assemble fields from templates with JSON pointers into the alarm, e.g. zusatzinfo = "{/objektname}: {/zusatzinfo}".
+// Pointers see the parsed alarm (standort, rics, priority, ...) with the Fireplan fields as they would be sent.
+fn map_fields(alarm: FireplanAlarm, data: &ParsedData) -> FireplanAlarm {
+    let Some(templates) = FIELD_TEMPLATES.get().filter(|t| !t.is_empty()) else {
+        return alarm;
+    };
+    let (Ok(serde_json::Value::Object(mut source)), Ok(serde_json::Value::Object(mut fields))) =
+        (serde_json::to_value(data), serde_json::to_value(&alarm))
+    else {
+        return alarm;
+    };
+    source.extend(fields.clone());
+    let source = serde_json::Value::Object(source);
+    for (field, template) in templates {
+        if let Some(value) = fields.get_mut(field) {
+            *value = serde_json::Value::String(crate::mapping::render(template, &source));
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(fields)).unwrap_or_else(|e| {
+        error!("fireplan_fields could not be applied: {}", e);
+        alarm
+    })
+}
+
+// Slot for one submission to the Standort, released on drop
+struct Permit(String);
+
+impl Permit {
+    fn acquire(standort: &str) -> Permit {
+        let max = MAX_CONCURRENCY.get().copied().unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        let (lock, released) = &*IN_FLIGHT;
+        let mut in_flight = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut waited = false;
+        while in_flight.get(standort).copied().unwrap_or(0) >= max {
+            if !waited {
+                info!("[{}] - {} submissions in flight, waiting", standort, max);
+                waited = true;
             }
+            in_flight = released.wait(in_flight).unwrap_or_else(|e| e.into_inner());
         }
-        Err(e) => {
-            error!("[{}] - Could not get API Key: {}", standort, e);
-            return None;
+        *in_flight.entry(standort.to_string()).or_insert(0) += 1;
+        Permit(standort.to_string())
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (lock, released) = &*IN_FLIGHT;
+        let mut in_flight = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(n) = in_flight.get_mut(&self.0) {
+            *n = n.saturating_sub(1);
         }
-    };
+        drop(in_flight);
+        released.notify_all();
+    }
+}
 
+fn client() -> Client {
+    let timeout = TIMEOUT.get().copied().unwrap_or(DEFAULT_TIMEOUT);
+    Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout.min(Duration::from_secs(10)))
+        .build()
+        .unwrap_or_else(|e| {
+            error!("Could not build Fireplan HTTP client with timeouts: {}", e);
+            Client::new()
+        })
+}
 
-    info!("Retrieved token from fireplan API");
-    let token: ApiKey = match serde_json::from_str(&token_string) {
-        Ok(apikey) => apikey,
-        Err(e) => {
-            error!("could not deserialize token key: {}", e);
-            return None;
+// A panic while the cache was held must not turn every later alarm into a Register call: the
+// entries stay usable (a token is inserted as a whole), so the poison is logged and cleared
+fn cache_poisoned() {
+    error!("Fireplan token cache was poisoned by a panic, continuing with its entries");
+    crate::prometheus::inc("fireplan_token_cache_poisoned_total", "Recoveries of the Fireplan token cache after a panic", &[]);
+    TOKEN_CACHE.clear_poison();
+}
+
+fn read_cache() -> RwLockReadGuard<'static, TokenCache> {
+    TOKEN_CACHE.read().unwrap_or_else(|e| {
+        cache_poisoned();
+        e.into_inner()
+    })
+}
+
+fn write_cache() -> RwLockWriteGuard<'static, TokenCache> {
+    TOKEN_CACHE.write().unwrap_or_else(|e| {
+        cache_poisoned();
+        e.into_inner()
+    })
+}
+
+fn count_cache(standort: &str, result: &str) {
+    crate::prometheus::inc(
+        "fireplan_token_cache_total",
+        "Fireplan token lookups by Standort and result (hit, miss, expired)",
+        &[("standort", standort), ("result", result)],
+    );
+}
+
+fn get_api_token(client: &Client, standort: &str, api_key: &str) -> Result<String, FireplanError> {
+    // Try cached value
+    let cached = read_cache().get(standort).cloned();
+    match cached {
+        Some(cached) if cached.valid() => {
+            info!("Returning token from cache, stored {:?}", cached.fetched);
+            count_cache(standort, "hit");
+            return Ok(cached.token);
         }
-    };
+        Some(_) => count_cache(standort, "expired"),
+        None => count_cache(standort, "miss"),
+    }
+
+    let token = fetch_api_token(client, standort, api_key).inspect_err(|e| error!("[{}] - Could not get API Key: {}", standort, e))?;
 
     // Store in cache
-    if let Ok(mut cache) = TOKEN_CACHE.lock() {
-        cache.insert(standort.to_string(), (token.utoken.clone(), Instant::now()));
-        info!("Stored token in cache for standort {}", standort);
+    write_cache().insert(standort.to_string(), token.clone());
+    info!("Stored token in cache for standort {}, valid for {:?}", standort, token.expires - token.fetched);
+
+    Ok(token.token)
+}
+
+// Failed submissions by Standort and error kind, with whether a retry can help
+pub fn count_error(standort: &str, e: &FireplanError) {
+    crate::prometheus::inc(
+        "fireplan_errors_total",
+        "Failed Fireplan submissions by Standort, error kind and retryability",
+        &[("standort", standort), ("kind", e.kind()), ("retryable", if e.is_retryable() { "true" } else { "false" })],
+    );
+}
+
+fn prefetch(standort: &str, api_key: &str) -> Result<(), FireplanError> {
+    let token = fetch_api_token(&client(), standort, api_key)?;
+    write_cache().insert(standort.to_string(), token);
+    Ok(())
+}
+
+// Fetch tokens for all configured Standorte at startup, so the first alarm does not pay for the
+// Register call and an invalid API key shows up in the log right away. Afterwards tokens are renewed
+// shortly before they expire and Standorte without token are retried every minute.
+pub fn start_token_refresh(api_key: String, standorte: Vec<String>) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for standort in &standorte {
+            match prefetch(standort, &api_key) {
+                Ok(()) => info!("[{}] - API token prefetched", standort),
+                Err(e) => error!("[{}] - Could not prefetch API token, check fireplan_api_key and Standort: {}", standort, e),
+            }
+        }
+        loop {
+            std::thread::sleep(Duration::from_secs(60));
+            let due: Vec<String> = {
+                let cache = read_cache();
+                let expiring = cache.iter().filter(|(_, token)| token.due()).map(|(standort, _)| standort.clone());
+                let missing = standorte.iter().filter(|s| !cache.contains_key(*s)).cloned();
+                expiring.chain(missing).collect()
+            };
+            for standort in due {
+                match prefetch(&standort, &api_key) {
+                    Ok(()) => info!("[{}] - Renewed API token in the background", standort),
+                    // a cached token stays valid until its TTL, the next round or the next alarm tries again
+                    Err(e) => warn!("[{}] - Background token renewal failed: {}", standort, e),
+                }
+            }
+        }
+    })
+}
+
+// Fetch a fresh token from the Register endpoint, bypassing the cache
+fn fetch_api_token(client: &Client, standort: &str, api_key: &str) -> Result<CachedToken, FireplanError> {
+    let url = url(&format!("/api/Register/{}", standort));
+    let r = client
+        .get(&url)
+        .header("API-Key", api_key.to_string())
+        .header("accept", "*/*")
+        .send()
+        .inspect_err(|e| crate::health::fireplan_unreachable(&e.to_string()))?;
+
+    let status = r.status();
+    if !status.is_success() {
+        crate::trace::exchange("Fireplan", "GET", &url, "", status.as_str(), &r.text().unwrap_or_default());
+        crate::health::fireplan_unreachable(&format!("Register answered {}", status));
+        return Err(FireplanError::Register(status));
     }
+    crate::health::fireplan_reachable();
+
+    let token_string = r.text()?;
+    crate::trace::exchange("Fireplan", "GET", &url, "", status.as_str(), &token_string);
+    info!("Retrieved token from fireplan API");
+    let token: ApiKey = serde_json::from_str(&token_string)?;
+    let ttl = token_ttl(&token);
+    Ok(CachedToken::new(token.utoken, ttl))
+}
 
-    Some(token.utoken)
+// Check that a token can be fetched for the Standort with the configured API key
+pub fn verify_token(standort: &str, api_key: &str) -> Result<(), FireplanError> {
+    fetch_api_token(&client(), standort, api_key).map(|_| ())
 }
 
-pub fn submit(standort: String, api_key: String, data: ParsedData) {
+pub fn submit(standort: String, api_key: String, data: ParsedData) -> Result<(), FireplanError> {
     info!("[{}] - Fireplan submit triggered", standort);
+    let rics: Vec<Ric> = data.rics.iter().filter(|r| !r.info_only()).cloned().collect();
+    if rics.is_empty() {
+        info!("[{}] - only info RICs, nothing to alarm in Fireplan", standort);
+        return Ok(());
+    }
+    let _permit = Permit::acquire(&standort);
 
-    let client = Client::new();
+    let client = client();
 
     let mut alarms: Vec<FireplanAlarm> = Vec::new();
 
     // Use cached or freshly fetched token
-    let api_token = match get_api_token(&client, &standort, &api_key) {
-        Some(t) => t,
-        None => return,
-    };
+    let api_token = get_api_token(&client, &standort, &api_key)?;
 
     info!("[{}] - using cached/fetched API Token", standort);
 
-    for ric in data.rics.clone() {
+    for ric in rics {
         let alarm = FireplanAlarm {
             ric: ric.ric,
-            subRIC: ric.subric,
+            sub_ric: ric.subric,
             einsatznrlst: data.einsatznrlst.clone(),
             strasse: data.strasse.clone(),
             hausnummer: data.hausnummer.clone(),
             ort: data.ort.clone(),
             ortsteil: data.ortsteil.clone(),
             objektname: data.objektname.clone(),
-            koordinaten: data.koordinaten.clone(),
+            koordinaten: COORDINATE_FORMAT.get().cloned().unwrap_or_default().format(&data.koordinaten),
             einsatzstichwort: data.einsatzstichwort.clone(),
             zusatzinfo: data.zusatzinfo.clone(),
         };
+        let alarm = map_fields(alarm, &data);
 
         alarms.push(alarm);
 
     }
 
-    info!("[{}] - submitting Alarm: {:?}", standort, alarms);
+    info!("[{}] - submitting Alarm: {}", standort, crate::privacy::redact(&format!("{:?}", alarms)));
+    let alarm_url = url("/api/Alarmierung");
+    let trace = |status: &str, response: &str| {
+        let request = serde_json::to_string(&alarms).unwrap_or_default();
+        crate::trace::exchange("Fireplan", "PUT", &alarm_url, &request, status, response);
+    };
 
         match client
-            .put("https://data.fireplan.de/api/Alarmierung")
+            .put(&alarm_url)
             .header("API-Token", api_token.clone())
             .header("accept", "*/*")
             .json(&alarms)
             .send()
         {
             Ok(r) => {
+                // any HTTP answer shows Fireplan is reachable, a rejected alarm is reported as failed submission
+                crate::health::fireplan_reachable();
                 if r.status().is_success() {
                     // On success, append timestamp and "einsatznrlst - einsatzstichwort" to the submitted log file
-                    let ts = chrono::Utc::now().to_rfc3339();
+                    let ts = crate::localtime::now();
                     let rics_str = data.rics.iter().map(|r| format!("{}:{}", r.text, r.subric)).collect::<Vec<_>>().join(",");
                     let line = format!(
                         "OK - {}\t{} - {} - {}\n",
                         ts,
                         data.einsatznrlst.as_str(),
                         rics_str,
-                        data.einsatzstichwort.as_str()
+                        crate::privacy::redact(&data.einsatzstichwort)
                     );
-                    if let Err(e) = OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("/root/fireplan_alarm_divera_submitted")
-                        .and_then(|mut f| f.write_all(line.as_bytes()))
-                    {
+                    if let Err(e) = crate::privacy::append_log(&crate::paths::submitted_log(), &line) {
                         error!("[{}] - Failed to write submission log: {}", standort, e);
                     }
 
+                    let status = r.status();
                     match r.text() {
                         Ok(t) => {
+                            trace(status.as_str(), &t);
                             info!("[{}] - Posted alarm, server says: {}", standort, t)
                         }
                         Err(e) => {
                             error!("[{}] - Could not get result text: {}", standort, e);
                         }
                     }
+                    Ok(())
                 } else {
+                    let status = r.status();
                     error!(
                         "[{}] - Could not post alarm: {:?}",
                         standort,
-                        r.status()
+                        status
                     );
                     match r.text() {
-                        Ok(t) => info!("[{}] - server says: {}", standort, t),
+                        Ok(t) => {
+                            trace(status.as_str(), &t);
+                            info!("[{}] - server says: {}", standort, t)
+                        }
                         Err(e) => {
                             error!("[{}] - Could not get result text: {}", standort, e);
                         }
                     }
+                    Err(FireplanError::Rejected(status))
                 }
             }
             Err(e) => {
                 error!("[{}] - Could not post alarm: {}", standort, e);
+                crate::health::fireplan_unreachable(&e.to_string());
+                trace("no response", &e.to_string());
 
                 // On failure, append timestamp and "einsatznrlst - einsatzstichwort" to the submitted log file
-                let ts = chrono::Utc::now().to_rfc3339();
+                let ts = crate::localtime::now();
                 let rics_str = data.rics.iter().map(|r| format!("{}:{}", r.text, r.subric)).collect::<Vec<_>>().join(",");
                 let line = format!(
                     "FAIL - {}\t{} - {} - {}\n",
                     ts,
                     data.einsatznrlst.as_str(),
                     rics_str,
-                    data.einsatzstichwort.as_str()
+                    crate::privacy::redact(&data.einsatzstichwort)
                 );
-                if let Err(e) = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("/root/fireplan_alarm_divera_submitted")
-                    .and_then(|mut f| f.write_all(line.as_bytes()))
-                {
+                if let Err(e) = crate::privacy::append_log(&crate::paths::submitted_log(), &line) {
                     error!("[{}] - Failed to write submission log: {}", standort, e);
                 }
 
+                Err(FireplanError::Unreachable(e))
             }
         }
 }
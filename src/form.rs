@@ -0,0 +1,90 @@
+use crate::error::ParseError;
+use serde_json::{Map, Value};
+
+// Fields that are numbers or lists in the payload schema, everything else stays a string
+const NUMBER_FIELDS: [&str; 5] = ["schema_version", "id", "priority", "ts_create", "ts_update"];
+const LIST_FIELDS: [&str; 3] = ["cluster", "group", "vehicle"];
+
+// Typed payload value of a text field value; list fields are comma separated,
+// unparsable numbers stay strings so validation reports them per field
+pub fn typed(name: &str, value: String) -> Value {
+    if LIST_FIELDS.contains(&name) {
+        Value::Array(split_list(&value))
+    } else if NUMBER_FIELDS.contains(&name) {
+        value.trim().parse::<i64>().map(Value::from).unwrap_or(Value::String(value))
+    } else {
+        Value::String(value)
+    }
+}
+
+fn split_list(value: &str) -> Vec<Value> {
+    value
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| Value::String(v.to_string()))
+        .collect()
+}
+
+// Build the JSON object payload::validate expects from form fields; list fields may be repeated or comma separated
+fn to_object(fields: Vec<(String, String)>) -> Value {
+    let mut object = Map::new();
+    for (name, value) in fields {
+        let name = name.trim_end_matches("[]").to_string();
+        if LIST_FIELDS.contains(&name.as_str()) {
+            if let Value::Array(list) = object.entry(name).or_insert_with(|| Value::Array(vec![])) {
+                list.extend(split_list(&value));
+            }
+        } else {
+            let value = typed(&name, value);
+            object.insert(name, value);
+        }
+    }
+    Value::Object(object)
+}
+
+pub fn from_urlencoded(body: &[u8]) -> Result<Value, ParseError> {
+    Ok(serde_urlencoded::from_bytes::<Vec<(String, String)>>(body).map(to_object)?)
+}
+
+// multipart/form-data with text fields only, file parts are ignored
+pub fn from_multipart(content_type: &str, body: &[u8]) -> Result<Value, ParseError> {
+    let boundary = content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        .ok_or(ParseError::MultipartBoundary)?;
+
+    let body = String::from_utf8_lossy(body).replace("\r\n", "\n");
+    let delimiter = format!("--{}", boundary);
+    let mut fields = vec![];
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+        let Some((head, value)) = part.trim_start_matches('\n').split_once("\n\n") else {
+            continue;
+        };
+        let disposition = head
+            .lines()
+            .find(|l| l.to_lowercase().starts_with("content-disposition:"))
+            .unwrap_or_default();
+        if disposition.contains("filename=") {
+            continue;
+        }
+        let name = disposition
+            .split(';')
+            .filter_map(|p| p.split_once('='))
+            .find(|(k, _)| k.trim() == "name")
+            .map(|(_, v)| v.trim().trim_matches('"').to_string());
+        if let Some(name) = name {
+            fields.push((name, value.strip_suffix('\n').unwrap_or(value).to_string()));
+        }
+    }
+    if fields.is_empty() {
+        return Err(ParseError::MultipartEmpty);
+    }
+    Ok(to_object(fields))
+}
@@ -0,0 +1,150 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+// "12", "12a", "12 a", "12-14", "12 - 14a", "12a-c", each optionally followed by "/ 1"
+static HOUSE_NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b(?P<number>\d+)(?:\s?(?P<letter>[a-zA-Z])\b)?(?:\s*-\s*(?P<to>\d+(?:\s?[a-zA-Z]\b)?|[a-zA-Z]\b))?(?:\s*/\s*(?P<slash>\w+))?")
+        .expect("static regex")
+});
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HouseNumberPosition {
+    // "Hauptstraße 12"
+    #[default]
+    Last,
+    // "12 Rue du Lac", "12 Main Street"
+    First,
+}
+
+// Where the part of the address line that is not street or house number goes
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RemainderField {
+    #[default]
+    Zusatzinfo,
+    Objektname,
+    // dropped
+    None,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Address {
+    pub strasse: String,
+    pub hausnummer: String,
+    // slash annotation and text after the house number, e.g. "Hausnummer 12/1, Hinterhaus"
+    pub zusatz: String,
+}
+
+// Leitstelle text conventions; the defaults describe the German (ILS Karlsruhe) format,
+// Austrian, Swiss or bilingual Leitstellen override what differs
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct InputFormat {
+    // the RIC texts are searched after this marker, e.g. "Alarmierte Einheiten:" or "Moyens engagés:"
+    einsatzmittel_marker: Option<String>,
+    ric_separator: Option<String>,
+    // Einsatzstichwort from the text (first capture group) instead of the title, e.g. 'Schlagwort\s*:\s*(.*)'
+    regex_stichwort: Option<String>,
+    // everything before the first separator is street and house number
+    address_separator: Option<String>,
+    house_number: Option<HouseNumberPosition>,
+    house_number_remainder: Option<RemainderField>,
+    // postal code in the address line (first capture group), defaults to five digits
+    regex_plz: Option<String>,
+}
+
+impl InputFormat {
+    pub fn einsatzmittel_marker(&self) -> &str {
+        self.einsatzmittel_marker.as_deref().unwrap_or("Einsatzmittel:")
+    }
+
+    pub fn ric_separator(&self) -> &str {
+        self.ric_separator.as_deref().unwrap_or(",")
+    }
+
+    pub fn regex_stichwort(&self) -> Option<&str> {
+        self.regex_stichwort.as_deref()
+    }
+
+    pub fn address_separator(&self) -> &str {
+        self.address_separator.as_deref().unwrap_or(",")
+    }
+
+    // (PLZ, Ort following it up to the next separator) of an address line like "Hauptstraße 12, 76131 Karlsruhe"
+    pub fn postal_code(&self, address: &str) -> Option<(String, String)> {
+        let regex = self.regex_plz.as_deref().unwrap_or(r"\b(\d{5})\b");
+        let re = match crate::parser::regex(regex) {
+            Some(re) => re,
+            None => {
+                log::error!("regex_plz is not a proper regular expression");
+                return None;
+            }
+        };
+        let caps = re.captures(address)?;
+        let plz = caps.get(1)?;
+        let ort = address[caps.get(0)?.end()..]
+            .split(self.address_separator())
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        Some((plz.as_str().to_string(), ort))
+    }
+
+    pub fn house_number_remainder(&self) -> RemainderField {
+        self.house_number_remainder.unwrap_or_default()
+    }
+
+    // Street and house number of an address line. House numbers may carry letters ("12a", "12 a"),
+    // ranges ("12-14", "12a-c") and slash annotations ("12 / 1"); the slash annotation and any text
+    // after the house number ("Hinterhaus") end up in the remainder
+    pub fn split_address(&self, address: &str) -> Address {
+        let address_part = address.split(self.address_separator()).next().unwrap_or("").trim();
+        let mut candidates = HOUSE_NUMBER.captures_iter(address_part).filter(|c| {
+            let m = c.get(0).expect("whole match");
+            match self.house_number.unwrap_or_default() {
+                // street before it, and not an ordinal as in "Straße des 17. Juni"
+                HouseNumberPosition::Last => m.start() > 0 && !address_part[m.end()..].starts_with('.'),
+                HouseNumberPosition::First => m.start() == 0,
+            }
+        });
+        // the last number, streets may carry numbers themselves ("L 560 12")
+        let found = match self.house_number.unwrap_or_default() {
+            HouseNumberPosition::Last => candidates.last(),
+            HouseNumberPosition::First => candidates.next(),
+        };
+        let Some(caps) = found else {
+            return Address {
+                strasse: address_part.split_whitespace().collect::<Vec<_>>().join(" "),
+                ..Default::default()
+            };
+        };
+
+        let m = caps.get(0).expect("whole match");
+        let mut hausnummer = format!("{}{}", &caps["number"], caps.name("letter").map(|l| l.as_str()).unwrap_or(""));
+        if let Some(to) = caps.name("to") {
+            hausnummer.push('-');
+            hausnummer.push_str(&to.as_str().replace(' ', ""));
+        }
+        let (strasse, after) = match self.house_number.unwrap_or_default() {
+            HouseNumberPosition::Last => (&address_part[..m.start()], &address_part[m.end()..]),
+            HouseNumberPosition::First => (&address_part[m.end()..], ""),
+        };
+
+        let mut zusatz: Vec<String> = vec![];
+        if let Some(slash) = caps.name("slash") {
+            zusatz.push(format!("Hausnummer {}/{}", hausnummer, slash.as_str()));
+        }
+        let after = after.trim();
+        if !after.is_empty() {
+            zusatz.push(after.to_string());
+        }
+        Address {
+            strasse: strasse.split_whitespace().collect::<Vec<_>>().join(" "),
+            hausnummer,
+            zusatz: zusatz.join(", "),
+        }
+    }
+}
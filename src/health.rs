@@ -0,0 +1,140 @@
+use once_cell::sync::{Lazy, OnceCell};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct ReadinessConfig {
+    // degraded when the last Fireplan call failed and none succeeded for this long, defaults to 30
+    fireplan_max_failure_mins: Option<i64>,
+    // degraded when a pipeline queue is filled above this percentage of its capacity, defaults to 80
+    queue_percent: Option<usize>,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Readiness {
+    // configuration loaded, pipeline not running yet
+    Starting,
+    Ready,
+    // running, but a component needs attention
+    Degraded,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct Report {
+    pub status: Readiness,
+    pub reasons: Vec<String>,
+    pub components: BTreeMap<String, String>,
+}
+
+type QueueLevel = Arc<dyn Fn() -> (usize, usize) + Send + Sync>;
+
+static CONFIG: OnceCell<ReadinessConfig> = OnceCell::new();
+static RUNNING: AtomicBool = AtomicBool::new(false);
+// component name -> error of its last start or run, None when fine
+static COMPONENTS: Lazy<Mutex<BTreeMap<String, Option<String>>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+static QUEUES: Lazy<Mutex<Vec<(&'static str, QueueLevel)>>> = Lazy::new(|| Mutex::new(vec![]));
+// (timestamp of the last successful Fireplan call, error of the last call if it failed)
+static FIREPLAN: Lazy<Mutex<(Option<i64>, Option<String>)>> = Lazy::new(|| Mutex::new((None, None)));
+
+pub fn init(config: ReadinessConfig) {
+    let _ = CONFIG.set(config);
+}
+
+// Starting -> Ready once the pipeline workers run
+pub fn set_running() {
+    RUNNING.store(true, Ordering::SeqCst);
+}
+
+pub fn component(name: &str, result: Result<(), String>) {
+    COMPONENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), result.err());
+}
+
+pub fn on_event(event: &crate::bus::BusEvent) {
+    if let crate::bus::BusEvent::ComponentDown { component: name, reason } = event {
+        component(name, Err(reason.clone()));
+    }
+}
+
+pub fn register_queue(name: &'static str, level: impl Fn() -> (usize, usize) + Send + Sync + 'static) {
+    QUEUES.lock().unwrap_or_else(|e| e.into_inner()).push((name, Arc::new(level)));
+}
+
+// (queue, entries, capacity) of the pipeline queues, for the metrics export
+pub fn queue_levels() -> Vec<(&'static str, usize, usize)> {
+    QUEUES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(name, level)| {
+            let (len, capacity) = level();
+            (*name, len, capacity)
+        })
+        .collect()
+}
+
+pub fn fireplan_reachable() {
+    let mut fireplan = FIREPLAN.lock().unwrap_or_else(|e| e.into_inner());
+    fireplan.0 = Some(chrono::Utc::now().timestamp());
+    fireplan.1 = None;
+}
+
+pub fn fireplan_unreachable(error: &str) {
+    FIREPLAN.lock().unwrap_or_else(|e| e.into_inner()).1 = Some(error.to_string());
+}
+
+pub fn report() -> Report {
+    let config = CONFIG.get().cloned().unwrap_or_default();
+    let mut reasons = vec![];
+    let mut components = BTreeMap::new();
+
+    for (name, error) in COMPONENTS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        match error {
+            Some(e) => {
+                reasons.push(format!("{}: {}", name, e));
+                components.insert(name.clone(), "failed".to_string());
+            }
+            None => {
+                components.insert(name.clone(), "ok".to_string());
+            }
+        }
+    }
+
+    let (last_ok, last_error) = FIREPLAN.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let max_failure = config.fireplan_max_failure_mins.unwrap_or(30) * 60;
+    let now = chrono::Utc::now().timestamp();
+    let fireplan = match (last_ok, last_error) {
+        (None, None) => "not contacted yet".to_string(),
+        (Some(_), None) => "ok".to_string(),
+        (ok, Some(error)) if ok.map(|t| now - t > max_failure).unwrap_or(true) => {
+            reasons.push(format!("fireplan: unreachable, {}", error));
+            "unreachable".to_string()
+        }
+        (_, Some(_)) => "last call failed".to_string(),
+    };
+    components.insert("fireplan".to_string(), fireplan);
+
+    let limit = config.queue_percent.unwrap_or(80);
+    for (name, level) in QUEUES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let (len, capacity) = level();
+        if len * 100 > capacity * limit {
+            reasons.push(format!("queue {}: {} of {} entries", name, len, capacity));
+        }
+        components.insert(format!("queue_{}", name), format!("{}/{}", len, capacity));
+    }
+
+    let status = if !RUNNING.load(Ordering::SeqCst) {
+        Readiness::Starting
+    } else if reasons.is_empty() {
+        Readiness::Ready
+    } else {
+        Readiness::Degraded
+    };
+    Report { status, reasons, components }
+}
@@ -0,0 +1,189 @@
+use crate::ParsedData;
+use log::{error, info, warn};
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// serializes appends and retention rewrites of the history, holds the hash of the last entry once read
+static WRITE_LOCK: Mutex<Option<String>> = Mutex::new(None);
+
+// One processed alarm, appended as JSON line to the history store (file or PostgreSQL, see history_storage)
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HistoryEntry {
+    pub alarm: ParsedData,
+    // unix timestamps
+    pub received: i64,
+    pub processed: i64,
+    // "submitted", "failed", "deduped", "closed"
+    pub result: String,
+    pub reason: Option<String>,
+    // hash chain for tamper evidence: hash of the previous entry and SHA-256 of this line without the hash,
+    // empty in entries written before the chain was introduced
+    #[serde(default)]
+    pub prev_hash: String,
+    // must stay the last field, the hash covers the line up to it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl HistoryEntry {
+    pub fn new(alarm: &ParsedData, result: &str, reason: Option<String>) -> HistoryEntry {
+        HistoryEntry {
+            alarm: alarm.clone(),
+            received: alarm.received,
+            processed: chrono::Utc::now().timestamp(),
+            result: result.to_string(),
+            reason,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+}
+
+// Outcomes of alarms are recorded as they are published
+pub fn on_event(event: &crate::bus::BusEvent) {
+    use crate::bus::BusEvent;
+    match event {
+        BusEvent::Deduped { alarm } => record(&HistoryEntry::new(alarm, "deduped", None)),
+        BusEvent::Submitted { alarm } => record(&HistoryEntry::new(alarm, "submitted", None)),
+        BusEvent::Failed { alarm: Some(alarm), reason, .. } => {
+            record(&HistoryEntry::new(alarm, "failed", Some(reason.clone())))
+        }
+        BusEvent::Vetoed { alarm, hook, reason } => {
+            record(&HistoryEntry::new(alarm, "vetoed", Some(format!("{}: {}", hook, reason))))
+        }
+        BusEvent::Expired { alarm, age_secs } => {
+            record(&HistoryEntry::new(alarm, "expired", Some(format!("{} s old", age_secs))))
+        }
+        BusEvent::Cancelled { alarm: Some(alarm), reason, .. } => {
+            record(&HistoryEntry::new(alarm, "cancelled", Some(reason.clone())))
+        }
+        // Einsatzende, so exports can compute the duration of the operation
+        BusEvent::Closed { alarm: Some(alarm), .. } => record(&HistoryEntry::new(alarm, "closed", None)),
+        _ => {}
+    }
+}
+
+// Hex SHA-256 of a text
+pub fn sha256(content: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, content.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// The line without its hash, as it was hashed; None for lines without hash
+fn unhashed(line: &str) -> Option<String> {
+    let start = line.rfind(",\"hash\":\"")?;
+    line.ends_with("\"}").then(|| format!("{}}}", &line[..start]))
+}
+
+// Hash of the last entry, empty for an empty history
+fn last_hash() -> anyhow::Result<String> {
+    let line = crate::storage::history().last_line()?;
+    Ok(line.and_then(|l| serde_json::from_str::<HistoryEntry>(&l).ok()).map(|e| e.hash).unwrap_or_default())
+}
+
+// The hash of the last entry is kept between writes; it is read again after a failed read or write, so an
+// unreachable store never makes an entry point to a wrong predecessor
+pub fn record(entry: &HistoryEntry) {
+    let mut last = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let prev_hash = match last.clone().map(Ok).unwrap_or_else(last_hash) {
+        Ok(hash) => hash,
+        Err(e) => {
+            error!("History entry not written, the last entry could not be read: {:#}", e);
+            return;
+        }
+    };
+    let mut entry = HistoryEntry {
+        alarm: crate::privacy::redact_alarm(&entry.alarm),
+        prev_hash,
+        hash: String::new(),
+        ..entry.clone()
+    };
+    entry.hash = match serde_json::to_string(&entry) {
+        Ok(l) => sha256(&l),
+        Err(e) => {
+            error!("Could not serialize history entry: {}", e);
+            return;
+        }
+    };
+    let line = match serde_json::to_string(&entry) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Could not serialize history entry: {}", e);
+            return;
+        }
+    };
+    match crate::storage::history().append(entry.received, &line) {
+        Ok(()) => *last = Some(entry.hash),
+        Err(e) => {
+            *last = None;
+            error!("Failed to write history: {:#}", e);
+        }
+    }
+}
+
+// Check the hash chain of the history: every line must hash to its hash and point to the hash of the
+// line before. The first remaining line may point to an entry removed by retention; lines from before the
+// chain was introduced are only allowed at the start. Ok(number of chained entries) or Err(line number, reason)
+pub fn verify_chain() -> Result<usize, (usize, String)> {
+    let lines = crate::storage::history().lines().map_err(|e| (0, format!("{:#}", e)))?;
+    let mut prev: Option<String> = None;
+    let mut chained = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        let entry: HistoryEntry = serde_json::from_str(line).map_err(|e| (number, format!("malformed: {}", e)))?;
+        if entry.hash.is_empty() {
+            if prev.is_some() {
+                return Err((number, "entry without hash inside the chain".to_string()));
+            }
+            continue;
+        }
+        let hashed = unhashed(line).ok_or_else(|| (number, "hash is not the last field".to_string()))?;
+        if sha256(&hashed) != entry.hash {
+            return Err((number, "content does not match its hash".to_string()));
+        }
+        if let Some(prev) = &prev {
+            if &entry.prev_hash != prev {
+                return Err((number, "previous hash does not match, entries removed or reordered".to_string()));
+            }
+        }
+        prev = Some(entry.hash);
+        chained += 1;
+    }
+    Ok(chained)
+}
+
+// All history entries, oldest first
+pub fn load() -> Vec<HistoryEntry> {
+    let lines = crate::storage::history().lines().unwrap_or_else(|e| {
+        error!("Could not read the history: {:#}", e);
+        vec![]
+    });
+    lines
+        .iter()
+        .filter_map(|l| match serde_json::from_str(l) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                warn!("Skipping malformed history line: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn last() -> Option<HistoryEntry> {
+    load().pop()
+}
+
+// Remove entries received before the cutoff (unix timestamp); kept entries stay unchanged, so the
+// rest of the hash chain stays verifiable
+pub fn prune(cutoff: i64) {
+    let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match crate::storage::history().prune(cutoff) {
+        Ok(0) => {}
+        Ok(removed) => info!("Retention: removed {} history entries", removed),
+        Err(e) => error!("Retention: could not prune history: {:#}", e),
+    }
+}
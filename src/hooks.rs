@@ -0,0 +1,112 @@
+use crate::ParsedData;
+use log::{error, info, warn};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// exit code of a hook refusing the alarm
+const VETO_EXIT_CODE: i32 = 2;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnError {
+    // submit the alarm as it was before the hook
+    #[default]
+    Continue,
+    Veto,
+}
+
+// External program run before submission: gets the parsed alarm as JSON on stdin and may print a modified
+// version on stdout (nothing printed keeps it unchanged) or exit with code 2 to veto the alarm
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct PreSubmitHook {
+    name: String,
+    command: String,
+    args: Option<Vec<String>>,
+    // defaults to 10
+    timeout_secs: Option<u64>,
+    // crash, timeout or invalid output, defaults to continue
+    on_error: Option<OnError>,
+}
+
+pub enum Outcome {
+    Continue(Box<ParsedData>),
+    // (hook name, reason)
+    Veto(String, String),
+}
+
+fn run(hook: &PreSubmitHook, data: &ParsedData) -> anyhow::Result<Outcome> {
+    let input = serde_json::to_vec(data)?;
+    let mut child = Command::new(&hook.command)
+        .args(hook.args.iter().flatten())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // separate threads, so a hook writing a lot before reading its input cannot block on full pipes
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("no stdin"))?;
+    std::thread::spawn(move || stdin.write_all(&input));
+    let mut stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("no stdout"))?;
+    let stdout = std::thread::spawn(move || {
+        let mut out = String::new();
+        stdout.read_to_string(&mut out).map(|_| out)
+    });
+    let mut stderr = child.stderr.take().ok_or_else(|| anyhow::anyhow!("no stderr"))?;
+    let stderr = std::thread::spawn(move || {
+        let mut out = String::new();
+        let _ = stderr.read_to_string(&mut out);
+        out
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(hook.timeout_secs.unwrap_or(10));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("timed out");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    let out = stdout.join().map_err(|_| anyhow::anyhow!("reading stdout panicked"))??;
+    let err = stderr.join().unwrap_or_default();
+
+    if status.code() == Some(VETO_EXIT_CODE) {
+        let reason = [err.trim(), out.trim()].into_iter().find(|s| !s.is_empty()).unwrap_or("no reason given");
+        return Ok(Outcome::Veto(hook.name.clone(), reason.to_string()));
+    }
+    if !status.success() {
+        anyhow::bail!("exited with {}: {}", status, err.trim());
+    }
+    if out.trim().is_empty() {
+        return Ok(Outcome::Continue(Box::new(data.clone())));
+    }
+    let mut modified: ParsedData = serde_json::from_str(&out).map_err(|e| anyhow::anyhow!("invalid output: {}", e))?;
+    // the alarm stays the same submission
+    modified.submission_id = data.submission_id.clone();
+    Ok(Outcome::Continue(Box::new(modified)))
+}
+
+// Run all hooks in order, each gets the output of the previous one
+pub fn apply(hooks: &[PreSubmitHook], mut data: ParsedData) -> Outcome {
+    for hook in hooks {
+        match run(hook, &data) {
+            Ok(Outcome::Continue(modified)) => data = *modified,
+            Ok(veto) => return veto,
+            Err(e) if hook.on_error.unwrap_or_default() == OnError::Veto => {
+                error!("Pre-submit hook {} failed: {}, not submitting", hook.name, e);
+                return Outcome::Veto(hook.name.clone(), format!("hook failed: {}", e));
+            }
+            Err(e) => warn!("Pre-submit hook {} failed: {}, continuing with the unmodified alarm", hook.name, e),
+        }
+    }
+    if !hooks.is_empty() {
+        info!("Pre-submit hooks done for alarm {}", data.einsatznrlst);
+    }
+    Outcome::Continue(Box::new(data))
+}
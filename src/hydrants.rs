@@ -0,0 +1,127 @@
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Water sources near the alarm address, from a local GeoJSON extract (e.g. an Overpass export of
+// emergency=fire_hydrant / water_tank / suction_point / fire_water_pond), for rural departments
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HydrantConfig {
+    // a relative path is taken from the state directory
+    file: String,
+    // defaults to 3
+    count: Option<usize>,
+    // sources further away are not listed, defaults to 1000
+    max_distance_m: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaterSource {
+    pub lat: f64,
+    pub lng: f64,
+    // e.g. "Hydrant (Unterflur)"
+    pub kind: String,
+}
+
+static SOURCES: OnceCell<(HydrantConfig, Vec<WaterSource>)> = OnceCell::new();
+
+// German label from the OSM tags, falls back to "typ"/"type" of hand-made files
+fn kind(properties: &Value) -> String {
+    let tags = properties.get("tags").unwrap_or(properties);
+    let tag = |k: &str| tags[k].as_str().unwrap_or_default();
+    let base = match tag("emergency") {
+        "fire_hydrant" => "Hydrant",
+        "water_tank" => "Löschwasserbehälter",
+        "suction_point" => "Saugstelle",
+        "fire_water_pond" => "Löschteich",
+        "" => {
+            let own = [tag("typ"), tag("type"), tag("name")].into_iter().find(|t| !t.is_empty());
+            return own.unwrap_or("Wasserentnahme").to_string();
+        }
+        other => other,
+    };
+    let detail = match tag("fire_hydrant:type") {
+        "underground" => "Unterflur",
+        "pillar" => "Überflur",
+        "wall" => "Wand",
+        "pipe" => "Rohr",
+        other => other,
+    };
+    if detail.is_empty() {
+        base.to_string()
+    } else {
+        format!("{} ({})", base, detail)
+    }
+}
+
+// Point features of a FeatureCollection, GeoJSON positions are [lng, lat]
+fn load(path: &str) -> anyhow::Result<Vec<WaterSource>> {
+    let collection: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let features = collection["features"].as_array().ok_or_else(|| anyhow::anyhow!("no features array"))?;
+    Ok(features
+        .iter()
+        .filter(|f| f["geometry"]["type"] == "Point")
+        .filter_map(|f| {
+            let position = f["geometry"]["coordinates"].as_array()?;
+            Some(WaterSource {
+                lat: position.get(1)?.as_f64()?,
+                lng: position.first()?.as_f64()?,
+                kind: kind(&f["properties"]),
+            })
+        })
+        .collect())
+}
+
+pub fn init(config: HydrantConfig) {
+    let file = crate::paths::state_file(&config.file);
+    match load(&file) {
+        Ok(sources) => {
+            info!("Loaded {} water sources from {}", sources.len(), file);
+            let _ = SOURCES.set((config, sources));
+        }
+        Err(e) => error!("Could not load water sources {}: {}", file, e),
+    }
+}
+
+pub fn enabled() -> bool {
+    SOURCES.get().is_some()
+}
+
+// Great-circle distance in meters
+fn distance_m(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (dlat, dlng) = ((lat2 - lat1).to_radians(), (lng2 - lng1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2) + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlng / 2.0).sin().powi(2);
+    6_371_000.0 * 2.0 * a.sqrt().asin()
+}
+
+// Nearest sources within max_distance_m, closest first
+pub fn nearest(lat: f64, lng: f64) -> Vec<(WaterSource, f64)> {
+    let Some((config, sources)) = SOURCES.get() else {
+        return vec![];
+    };
+    let max_distance = config.max_distance_m.unwrap_or(1000.0);
+    let mut near: Vec<(WaterSource, f64)> = sources
+        .iter()
+        .map(|s| (s.clone(), distance_m(lat, lng, s.lat, s.lng)))
+        .filter(|(_, d)| *d <= max_distance)
+        .collect();
+    near.sort_by(|a, b| a.1.total_cmp(&b.1));
+    near.truncate(config.count.unwrap_or(3));
+    near
+}
+
+// Append the nearest water sources to zusatzinfo, e.g. "Hydrant (Unterflur) 85 m"
+pub fn enrich(data: &mut ParsedData) {
+    let Some((lat, lng)) = crate::coordinates::parse(&data.koordinaten) else {
+        return;
+    };
+    let near = nearest(lat, lng);
+    if near.is_empty() {
+        return;
+    }
+    let lines: Vec<String> = near.iter().map(|(s, d)| format!("{} {:.0} m", s.kind, d)).collect();
+    info!("Adding {} water sources to alarm {}", lines.len(), data.einsatznrlst);
+    data.zusatzinfo = format!("{}\n\nWasserentnahme:\n{}", data.zusatzinfo, lines.join("\n"));
+}
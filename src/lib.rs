@@ -0,0 +1,1367 @@
+use std::collections::HashMap;
+use log::{error, info, LevelFilter, warn};
+use schemars::JsonSchema;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use simplelog::{ColorChoice, CombinedLogger, Config, TermLogger, TerminalMode, SimpleLogger};
+use std::fs;
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use cmd_lib::run_cmd;
+use once_cell::sync::OnceCell;
+use error::ConfigError;
+
+mod aao;
+mod approval;
+mod audit;
+mod backup;
+mod branding;
+mod buildinfo;
+mod bus;
+mod caldav;
+mod callback;
+mod categories;
+mod cancel;
+mod closing;
+mod config_edit;
+mod connect;
+mod coordinates;
+pub mod dedup;
+mod divera;
+mod error;
+mod eta;
+mod export;
+mod fe2;
+mod fireplan;
+mod form;
+mod format;
+mod health;
+mod history;
+mod hydrants;
+mod hooks;
+mod localtime;
+mod lockout;
+mod logtail;
+mod mail;
+mod manual;
+mod map;
+mod mapping;
+mod merge;
+mod normalize;
+mod notify;
+mod objects;
+mod outbox;
+mod overrides;
+mod pager;
+pub mod parser;
+mod payload;
+pub mod paths;
+mod plz;
+mod priority;
+mod privacy;
+mod privileges;
+mod probe;
+mod prometheus;
+mod problem;
+mod queue;
+mod responses;
+mod roster;
+mod ric_groups;
+mod routing;
+mod schema;
+mod samples;
+mod scripting;
+mod secrets;
+mod selftest;
+#[cfg(windows)]
+mod service;
+mod smtp;
+mod sound;
+mod stats;
+mod storage;
+mod submissions;
+mod summaries;
+mod supervisor;
+mod tenants;
+mod ticker;
+mod tokens;
+mod trace;
+mod verify;
+mod weather;
+mod web_server;
+
+// Global event queue feeding the main loop
+static EVENTS: OnceCell<Arc<queue::BoundedQueue<Event>>> = OnceCell::new();
+
+// Public helper to allow any thread to send an Event to main loop
+#[allow(clippy::result_large_err)]
+pub fn send_event(event: Event) -> Result<(), mpsc::SendError<Event>> {
+    match EVENTS.get() {
+        // shutdown jumps the queue and is never dropped
+        Some(events) if matches!(event, Event::Shutdown) => events.push_front(event),
+        Some(events) => events.push(event),
+        None => return Err(mpsc::SendError(event)),
+    }
+    Ok(())
+}
+
+// Append a line with timestamp and title to the receive log file
+pub fn log_received(data: &SubmitPayload) {
+    let ts = localtime::now();
+    let line = format!("{}\t{}\n", ts, privacy::redact(&data.title));
+    if let Err(e) = privacy::append_log(&paths::received_log(), &line) {
+        error!("Failed to write receive log: {}", e);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq, Debug)]
+pub struct Standort {
+    standort: String,
+    additional_rics: Option<Vec<Ric>>
+}
+
+#[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq, Debug, JsonSchema)]
+pub struct Ric {
+    text: String,
+    // ric and subric are empty for group references
+    #[serde(default)]
+    ric: String,
+    #[serde(default)]
+    subric: String,
+    // match the text with "ae" == "ä", "oe" == "ö", "ue" == "ü", "ss" == "ß"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    umlaut_tolerant: Option<bool>,
+    // Fireplan Standorte this RIC is alarmed at, instead of the Standort of the alarm source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    standorte: Option<Vec<String>>,
+    // kept in history and shown on the monitor, but never alarmed in Fireplan, e.g. test RICs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info_only: Option<bool>,
+    // name of a ric_groups entry, the text alarms its members instead of a single RIC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+}
+
+impl Ric {
+    pub fn info_only(&self) -> bool {
+        self.info_only.unwrap_or(false)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Configuration {
+    fireplan_api_key: String,
+    regex_ort: String,
+    regex_ortsteil: String,
+    regex_objektname: String,
+    simple_trigger: Option<String>,
+    rics: Vec<Ric>,
+    http_port: u16,
+    http_host: String,
+    auth_token: String,
+    // plausibility checks on /submit (keywords, operating area, text length), may be omitted
+    payload_guard: Option<payload::PayloadGuard>,
+    // ban addresses after repeated authentication failures, active with defaults if omitted
+    lockout: Option<lockout::LockoutConfig>,
+    // RFC 3339, auth_token is rejected from then on, once the sources use a minted or configured submit token
+    auth_token_valid_until: Option<String>,
+    object_db: Option<String>,
+    // nearest hydrants and other water sources, appended to zusatzinfo and shown on the map
+    hydrants: Option<hydrants::HydrantConfig>,
+    object_db_fields: Option<Vec<String>>,
+    timezone: Option<String>,
+    // timezone for timestamps shown to people (web UI, receive/submission logs, notifications), defaults to UTC
+    display_timezone: Option<String>,
+    // test RIC alarmed at every Standort by verify-fireplan, only the tokens are checked if omitted
+    verify_ric: Option<Ric>,
+    // internal listener for the Prometheus export, replaces the /metrics page on the public port
+    metrics_listener: Option<prometheus::MetricsListenerConfig>,
+    // department name, logo, colors and language of the dashboard and alarm monitor
+    branding: Option<branding::Branding>,
+    aao_rules: Option<Vec<aao::AaoRule>>,
+    // members available per duty group, for AAO rules with a duty condition
+    duty_roster: Option<roster::RosterConfig>,
+    // named RIC sets referenced by parser RICs (group = "...") and AAO rules (groups = [...])
+    ric_groups: Option<Vec<ric_groups::RicGroup>>,
+    // conditional changes of alarm fields after parsing, e.g. another Standort for an Ortsteil
+    overrides: Option<Vec<overrides::OverrideRule>>,
+    // keyword rules tagging each alarm with a category for stats and export
+    categories: Option<Vec<categories::CategoryRule>>,
+    // token for admin and protected operational endpoints, must differ from auth_token; without it they are disabled
+    admin_token: Option<String>,
+    // RFC 3339, admin_token is rejected from then on
+    admin_token_valid_until: Option<String>,
+    // routing and RIC changes in the config editor need a second admin
+    config_approval: Option<approval::ApprovalConfig>,
+    protect_operational_endpoints: Option<bool>,
+    enable_echo: Option<bool>,
+    divera_poll: Option<divera::DiveraPollConfig>,
+    // DIVERA Rückmeldungen of the active alarm, headcount on the monitor page
+    divera_responses: Option<responses::ResponsesConfig>,
+    submit_tokens: Option<Vec<SubmitToken>>,
+    profiles: Option<Vec<Profile>>,
+    idempotency_window_secs: Option<u64>,
+    max_body_bytes: Option<usize>,
+    max_json_depth: Option<usize>,
+    mail_listener: Option<smtp::MailListenerConfig>,
+    // Standort -> charset of mails that declare none
+    mail_charsets: Option<HashMap<String, String>>,
+    pager_input: Option<pager::PagerInputConfig>,
+    merge_window_secs: Option<u64>,
+    // how long RICs alarmed for an Einsatznummer are not alarmed again, defaults to 72
+    dedup_ttl_hours: Option<i64>,
+    // alarms whose Leitstelle timestamp is older are archived but not submitted
+    max_alarm_age_secs: Option<i64>,
+    regex_einsatzende: Option<String>,
+    notifications: Option<Vec<notify::Channel>>,
+    map_tile_url: Option<String>,
+    map_tile_cache: Option<String>,
+    offline_map: Option<bool>,
+    queue_capacity: Option<usize>,
+    queue_overflow: Option<queue::OverflowPolicy>,
+    retention_days: Option<u32>,
+    redactions: Option<Vec<privacy::Redaction>>,
+    // plain HTTP when false, e.g. behind a TLS terminating ingress, defaults to true
+    tls: Option<bool>,
+    // directory for logs, history and caches, defaults to /root
+    state_dir: Option<String>,
+    // alarm history in the state directory or on a PostgreSQL server, defaults to the file
+    history_storage: Option<storage::StorageConfig>,
+    // switch to this user after binding the listening ports (Unix only), state_dir must be writable for it
+    run_as_user: Option<String>,
+    // reject /submit bodies with fields not in the payload schema, defaults to false
+    strict_payload: Option<bool>,
+    // Leitstelle text conventions, German defaults
+    format: Option<format::InputFormat>,
+    // field mappings for /submit sources that don't send the DIVERA payload
+    input_mappings: Option<Vec<mapping::InputMapping>>,
+    // submit to Fireplan, may be switched off when only other sinks are used, defaults to true
+    fireplan_enabled: Option<bool>,
+    feuersoftware_connect: Option<connect::ConnectConfig>,
+    // keyword/RIC/Standort rules restricting which alarms go to which sink
+    sink_routes: Option<Vec<routing::SinkRoute>>,
+    // thresholds for reporting DEGRADED on /ready
+    readiness: Option<health::ReadinessConfig>,
+    // timeout for Fireplan API calls, defaults to 15
+    fireplan_timeout_secs: Option<u64>,
+    // Fireplan API base URL, defaults to https://data.fireplan.de
+    fireplan_url: Option<String>,
+    // lifetime of Fireplan tokens whose Register answer carries no expiry, defaults to 1800
+    fireplan_token_ttl_secs: Option<u64>,
+    // parallel Fireplan submissions per Standort, further alarms wait, defaults to 2
+    fireplan_max_concurrency: Option<usize>,
+    // Standort for /submit, DIVERA and other sources without their own, defaults to "Verwaltung"
+    default_standort: Option<String>,
+    // PLZ -> Ort for alarms whose Ort regex finds nothing, "PLZ;Ort" CSV file and/or inline entries
+    plz_file: Option<String>,
+    plz_orte: Option<HashMap<String, String>>,
+    // subRIC, notification channels and monitor styling per Einsatz priority
+    priorities: Option<Vec<priority::PriorityLevel>>,
+    // station LED ticker / e-paper displays
+    tickers: Option<Vec<ticker::TickerConfig>>,
+    // scheduled test alarms, e.g. the weekly siren probe, recorded as drill
+    probe_alarms: Option<Vec<probe::ProbeAlarm>>,
+    // gong on the host's audio output or a network speaker
+    sound: Option<sound::SoundConfig>,
+    // department calendar getting an event per Einsatz
+    caldav: Option<caldav::CalDavConfig>,
+    // submission outcome per RIC posted back to the alarm source
+    callbacks: Option<callback::CallbackConfig>,
+    // weather summary for storm and flood keywords in notifications and on the monitor page
+    weather: Option<weather::WeatherConfig>,
+    // driving distance and time from the station via a self-hosted OSRM or Valhalla
+    eta: Option<eta::EtaConfig>,
+    // external programs that may modify or veto an alarm before submission
+    pre_submit_hooks: Option<Vec<hooks::PreSubmitHook>>,
+    // Rhai scripts post-processing alarms and choosing sinks
+    scripts: Option<Vec<scripting::ScriptConfig>>,
+    // precision, order and format of the koordinaten sent to Fireplan
+    fireplan_coordinates: Option<coordinates::CoordinateFormat>,
+    // Fireplan alarm field -> template with JSON pointers into the alarm, e.g. ort = "{/ortsteil}"
+    fireplan_fields: Option<HashMap<String, String>>,
+    // further departments run by this process, each with its own config file
+    tenants: Option<Vec<tenants::Tenant>>,
+}
+
+// Additional /submit token routed to its own Standort and parser profile
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct SubmitToken {
+    token: String,
+    standort: String,
+    profile: Option<String>,
+    // name of an input mapping applied to the body
+    mapping: Option<String>,
+    // RFC 3339, the token is accepted from/until then, e.g. while rotating the token configured in DIVERA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_until: Option<String>,
+}
+
+// Parser profile overriding the global regexes and RIC list
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Profile {
+    name: String,
+    regex_ort: Option<String>,
+    regex_ortsteil: Option<String>,
+    regex_objektname: Option<String>,
+    rics: Option<Vec<Ric>>,
+    format: Option<format::InputFormat>,
+}
+
+impl Configuration {
+    // TCP/UDP ports the service listens on
+    pub fn listening_ports(&self) -> Vec<u16> {
+        let mut ports = vec![self.http_port];
+        ports.extend(self.mail_listener.iter().map(|m| m.port));
+        ports.extend(self.pager_input.iter().map(|p| p.port));
+        ports.extend(self.metrics_listener.iter().map(|m| m.port));
+        ports
+    }
+
+    // All Fireplan Standorte alarms may be routed to
+    pub fn standorte(&self) -> Vec<String> {
+        let mut standorte = vec![Route::default().standort];
+        let rics = self
+            .rics
+            .iter()
+            .chain(self.profiles.iter().flatten().flat_map(|p| p.rics.iter().flatten()))
+            .chain(self.ric_groups.iter().flatten().flat_map(|g| g.rics()));
+        let candidates = self
+            .submit_tokens
+            .iter()
+            .flatten()
+            .map(|t| t.standort.clone())
+            .chain(self.mail_listener.iter().filter_map(|m| m.standort.clone()))
+            .chain(self.pager_input.iter().filter_map(|p| p.standort.clone()))
+            .chain(rics.flat_map(|r| r.standorte.clone().unwrap_or_default()));
+        for standort in candidates {
+            if !standorte.contains(&standort) {
+                standorte.push(standort);
+            }
+        }
+        standorte
+    }
+
+    // Configuration with the named parser profile applied, unchanged if the profile is unknown
+    pub fn with_profile(&self, name: &Option<String>) -> Configuration {
+        let mut configuration = self.clone();
+        let Some(name) = name else {
+            return configuration;
+        };
+        let Some(profile) = self.profiles.iter().flatten().find(|p| &p.name == name) else {
+            warn!("Unknown parser profile {}, using defaults", name);
+            return configuration;
+        };
+        if let Some(r) = &profile.regex_ort {
+            configuration.regex_ort = r.clone();
+        }
+        if let Some(r) = &profile.regex_ortsteil {
+            configuration.regex_ortsteil = r.clone();
+        }
+        if let Some(r) = &profile.regex_objektname {
+            configuration.regex_objektname = r.clone();
+        }
+        if let Some(rics) = &profile.rics {
+            configuration.rics = rics.clone();
+        }
+        if let Some(format) = &profile.format {
+            configuration.format = Some(format.clone());
+        }
+        configuration
+    }
+}
+
+// Where an incoming alarm goes: Fireplan Standort and parser profile
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Route {
+    standort: String,
+    profile: Option<String>,
+}
+
+// Standort for alarms without a Standort of their own (default_standort), set once at startup
+static DEFAULT_STANDORT: OnceCell<String> = OnceCell::new();
+
+impl Default for Route {
+    fn default() -> Self {
+        Route {
+            standort: DEFAULT_STANDORT.get().cloned().unwrap_or_else(|| "Verwaltung".to_string()),
+            profile: None,
+        }
+    }
+}
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ParsedData {
+    submission_id: String,
+    received: i64,
+    // Leitstelle timestamp (mail Date header / DIVERA ts_create), 0 if unknown
+    #[serde(default)]
+    created: i64,
+    standort: String,
+    rics: Vec<Ric>,
+    einsatznrlst: String,
+    strasse: String,
+    hausnummer: String,
+    ort: String,
+    ortsteil: String,
+    objektname: String,
+    koordinaten: String,
+    einsatzstichwort: String,
+    zusatzinfo: String,
+    // priority of the payload, see priorities
+    #[serde(default)]
+    priority: u8,
+    // position in the mailbox the alarm came from, None for other sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sequence: Option<Sequence>,
+    // report category, see categories
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    category: String,
+    // scheduled probe alarm, see probe_alarms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    drill: Option<probe::Drill>,
+}
+
+impl ParsedData {
+    pub fn einsatznrlst(&self) -> &str {
+        &self.einsatznrlst
+    }
+
+    pub fn rics(&self) -> &[Ric] {
+        &self.rics
+    }
+}
+
+// Arrival order of a mail within its mailbox, mails of one mailbox run through the pipeline one after another
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Sequence {
+    pub mailbox: String,
+    pub number: u64,
+}
+
+impl std::fmt::Display for Sequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} #{}", self.mailbox, self.number)
+    }
+}
+
+// Incoming JSON payload structure for submit, missing fields are defaulted (see payload::validate)
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct SubmitPayload {
+    schema_version: u32,
+    id: u64,
+    foreign_id: String,
+    title: String,
+    text: String,
+    address: String,
+    lat: String,
+    lng: String,
+    priority: u8,
+    cluster: Vec<String>,
+    group: Vec<String>,
+    vehicle: Vec<String>,
+    ts_create: i64,
+    ts_update: i64,
+}
+
+// Incoming alarm together with its processing id and route
+#[derive(Clone, Debug)]
+pub struct Incoming {
+    id: String,
+    received: i64,
+    payload: SubmitPayload,
+    route: Route,
+    sequence: Option<Sequence>,
+    drill: Option<probe::Drill>,
+}
+
+impl Incoming {
+    pub fn new(payload: SubmitPayload, route: Route) -> Incoming {
+        Incoming {
+            id: submissions::create(),
+            received: chrono::Utc::now().timestamp(),
+            payload,
+            route,
+            sequence: None,
+            drill: None,
+        }
+    }
+
+    pub fn with_sequence(self, sequence: Sequence) -> Incoming {
+        Incoming { sequence: Some(sequence), ..self }
+    }
+
+    pub fn with_drill(self, drill: probe::Drill) -> Incoming {
+        Incoming { drill: Some(drill), ..self }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+// New event enum to transport richer context
+#[derive(Clone, Debug)]
+pub enum Event {
+    Data(ParsedData),
+    Submit(Incoming),
+    Shutdown,
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let container = args.iter().any(|a| a == "--container");
+    // set when another process hosts this one as tenant
+    let tenant = args.windows(2).find(|w| w[0] == "--tenant").map(|w| w[1].clone());
+    let state_dir = args.windows(2).find(|w| w[0] == "--state-dir").map(|w| w[1].clone());
+    if let (Some(tenant), Some(state_dir)) = (tenant, state_dir) {
+        tenants::set_current(tenant, state_dir);
+    }
+    let file = args
+        .windows(2)
+        .find(|w| w[0] == "--config")
+        .map(|w| w[1].clone())
+        .or_else(|| std::env::var(format!("{}CONFIG", ENV_PREFIX)).ok())
+        .unwrap_or_else(|| if container { CONTAINER_CONFIG_FILE.to_string() } else { paths::default_config_file() });
+
+    match args.get(1).map(|a| a.as_str()) {
+        #[cfg(windows)]
+        Some("install") => match service::install(&file) {
+            Ok(()) => println!("Service installed, using configuration {}", file),
+            Err(e) => eprintln!("Could not install service: {e}"),
+        },
+        #[cfg(windows)]
+        Some("uninstall") => match service::uninstall() {
+            Ok(()) => println!("Service removed"),
+            Err(e) => eprintln!("Could not remove service: {e}"),
+        },
+        #[cfg(windows)]
+        Some("service") => {
+            if let Err(e) = service::run_dispatcher() {
+                eprintln!("Could not start as service: {e}");
+            }
+        }
+        Some("verify-fireplan") => {
+            let configuration = load_configuration(&file, container);
+            if let Some(dir) = &configuration.state_dir {
+                paths::set_state_dir(dir);
+            }
+            fireplan::set_timeout(configuration.fireplan_timeout_secs);
+            fireplan::set_url(configuration.fireplan_url.clone());
+            fireplan::set_coordinate_format(configuration.fireplan_coordinates.clone());
+            fireplan::set_field_templates(configuration.fireplan_fields.clone());
+            if !verify::fireplan(&configuration) {
+                std::process::exit(1);
+            }
+        }
+        // verify-imap is kept as the name operators look for
+        Some("verify-mail" | "verify-imap") => {
+            let configuration = load_configuration(&file, container);
+            if let Some(dir) = &configuration.state_dir {
+                paths::set_state_dir(dir);
+            }
+            if !verify::mail(&configuration) {
+                std::process::exit(1);
+            }
+        }
+        // plain TOML with the secret keys on stdin, prints the encrypted `secrets` entry for the config file;
+        // to the --recipient age keys if given, otherwise to the key file or passphrase
+        Some("encrypt-secrets") => {
+            let key_file = args.windows(2).find(|w| w[0] == "--key-file").map(|w| w[1].clone());
+            let recipients: Vec<String> = args.windows(2).filter(|w| w[0] == "--recipient").map(|w| w[1].clone()).collect();
+            let mut plain = String::new();
+            let result = std::io::Read::read_to_string(&mut std::io::stdin(), &mut plain)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| toml::from_str::<toml::Table>(&plain).map_err(anyhow::Error::from))
+                .and_then(|_| match recipients.is_empty() {
+                    true => secrets::key(key_file.as_deref()),
+                    false => Ok(secrets::Key::Recipients(recipients)),
+                })
+                .and_then(|key| secrets::encrypt(&plain, &key));
+            match result {
+                Ok(sealed) => println!("secrets = '''\n{}'''", sealed),
+                Err(e) => {
+                    eprintln!("Could not encrypt secrets: {:#}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        // restore <archive> [--force] [--key-file <file>]: state files and config from GET /admin/backup
+        Some("restore") => {
+            let Some(archive) = args.get(2).filter(|a| !a.starts_with("--")) else {
+                eprintln!("Usage: restore <backup.tar.gz> [--config <file>] [--key-file <file>] [--force]");
+                std::process::exit(2);
+            };
+            let key_file = args.windows(2).find(|w| w[0] == "--key-file").map(|w| w[1].clone());
+            let force = args.iter().any(|a| a == "--force");
+            if let Err(e) = backup::restore(archive, &file, container, key_file.as_deref(), force) {
+                eprintln!("Could not restore: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("verify-history") => {
+            let configuration = load_configuration(&file, container);
+            if let Some(dir) = &configuration.state_dir {
+                paths::set_state_dir(dir);
+            }
+            if let Err(e) = storage::init(&configuration.history_storage.clone().unwrap_or_default()) {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+            if !verify::history() {
+                std::process::exit(1);
+            }
+        }
+        _ => run(&file, container),
+    }
+}
+
+const ENV_PREFIX: &str = "FIREPLAN_ALARM_DIVERA_";
+const CONTAINER_CONFIG_FILE: &str = "/config/fireplan_alarm_divera.conf";
+const CONTAINER_STATE_DIR: &str = "/data";
+
+// Container mode: the config file is optional and every top-level key can be set as FIREPLAN_ALARM_DIVERA_<KEY>.
+// Values are read as TOML (numbers, booleans, arrays, inline tables, quoted strings), anything else as plain string.
+fn load_configuration(file: &str, container: bool) -> Configuration {
+    try_load_configuration(file, container).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn try_load_configuration(file: &str, container: bool) -> Result<Configuration, ConfigError> {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(_) if container => String::new(),
+        Err(source) => return Err(ConfigError::Missing { file: file.to_string(), source }),
+    };
+    load_configuration_from_str(&content, file, container)
+}
+
+// Config text as if it were the file, e.g. the decrypted config of a backup before it is written
+fn load_configuration_from_str(content: &str, file: &str, container: bool) -> Result<Configuration, ConfigError> {
+    let mut table: toml::Table =
+        toml::from_str(content).map_err(|source| ConfigError::Syntax { file: file.to_string(), source })?;
+    if container {
+        for (name, value) in std::env::vars() {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == "CONFIG" || name == secrets::PASSPHRASE_ENV {
+                continue;
+            }
+            let value = toml::from_str::<toml::Table>(&format!("v = {}", value))
+                .ok()
+                .and_then(|mut t| t.remove("v"))
+                .unwrap_or(toml::Value::String(value));
+            table.insert(key.to_lowercase(), value);
+        }
+        table
+            .entry("state_dir")
+            .or_insert_with(|| toml::Value::String(CONTAINER_STATE_DIR.to_string()));
+    }
+    into_configuration(table, file)
+}
+
+// Decrypt the secrets and deserialize, shared with the dry validation of uploaded configs
+fn into_configuration(mut table: toml::Table, file: &str) -> Result<Configuration, ConfigError> {
+    secrets::merge(&mut table).map_err(|e| ConfigError::Secrets { file: file.to_string(), reason: format!("{:#}", e) })?;
+    let configuration: Configuration =
+        table.try_into().map_err(|source| ConfigError::Invalid { file: file.to_string(), source })?;
+    let reasons = schema::rejected(&configuration);
+    if !reasons.is_empty() {
+        return Err(ConfigError::Rejected { file: file.to_string(), reasons });
+    }
+    Ok(configuration)
+}
+
+pub fn run(file: &str, container: bool) {
+    let mut configuration = load_configuration(file, container);
+    tenants::apply(&mut configuration.state_dir);
+    if let Some(dir) = &configuration.state_dir {
+        paths::set_state_dir(dir);
+    }
+    if let Some(standort) = &configuration.default_standort {
+        let _ = DEFAULT_STANDORT.set(standort.clone());
+    }
+
+    // Robust logger init: use TermLogger when TTY is available, otherwise fallback to SimpleLogger
+    let term = TermLogger::new(
+        LevelFilter::Info,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    );
+    // the last lines also stay in memory for /admin/logs
+    let tail = simplelog::WriteLogger::new(LevelFilter::Info, Config::default(), logtail::Writer::default());
+    #[allow(unused_mut)]
+    let mut loggers: Vec<Box<dyn simplelog::SharedLogger>> = vec![term, tail];
+    // the Windows service has no console, log to a file in the state directory as well
+    #[cfg(windows)]
+    match fs::OpenOptions::new().create(true).append(true).open(paths::service_log()) {
+        Ok(f) => loggers.push(simplelog::WriteLogger::new(LevelFilter::Info, Config::default(), f)),
+        Err(e) => eprintln!("Could not open log file {}: {e}", paths::service_log()),
+    }
+    CombinedLogger::init(loggers).unwrap_or_else(|_| {
+        CombinedLogger::init(vec![SimpleLogger::new(LevelFilter::Info, Config::default())]).unwrap();
+    });
+
+    info!("{}", buildinfo::banner(&configuration));
+    // with the decrypted secrets merged in, so only masked
+    match config_edit::redacted(&configuration) {
+        Ok(content) => info!("Configuration:\n{}", content),
+        Err(e) => warn!("Configuration not logged: {:#}", e),
+    }
+
+    // history file or PostgreSQL table, before anything records alarms
+    if let Err(e) = storage::init(&configuration.history_storage.clone().unwrap_or_default()) {
+        panic!("{:#}", e);
+    }
+    // Redaction applies to everything stored or sent to notification channels from here on
+    privacy::init(&configuration.redactions);
+    health::init(configuration.readiness.clone().unwrap_or_default());
+    fireplan::set_timeout(configuration.fireplan_timeout_secs);
+    fireplan::set_url(configuration.fireplan_url.clone());
+    fireplan::set_token_ttl(configuration.fireplan_token_ttl_secs);
+    fireplan::set_max_concurrency(configuration.fireplan_max_concurrency);
+    fireplan::set_coordinate_format(configuration.fireplan_coordinates.clone());
+    fireplan::set_field_templates(configuration.fireplan_fields.clone());
+    localtime::init(&configuration.display_timezone);
+    priority::init(configuration.priorities.clone());
+    scripting::init(configuration.scripts.clone().unwrap_or_default());
+    ric_groups::init(configuration.ric_groups.as_deref().unwrap_or_default());
+    lockout::init(configuration.lockout.clone().unwrap_or_default());
+
+    // Subscribers of the pipeline events
+    bus::subscribe("submissions", submissions::on_event);
+    bus::subscribe("history", history::on_event);
+    bus::subscribe("stats", stats::on_event);
+    bus::subscribe("health", health::on_event);
+    bus::subscribe("audit", |event| {
+        if let bus::BusEvent::ConfigLoaded { file } = event {
+            audit::record("config_loaded", "local", file);
+        }
+    });
+    if let Some(channels) = configuration.notifications.clone() {
+        notify::start_digests(channels.clone());
+        bus::subscribe("notifications", move |event| notify_event(&channels, event));
+    }
+    if let Some(tickers) = configuration.tickers.clone() {
+        ticker::init(
+            tickers,
+            configuration.sink_routes.clone().unwrap_or_default(),
+            aao::timezone(&configuration.timezone),
+        );
+        bus::subscribe("ticker", ticker::on_event);
+    }
+    if let Some(sound_config) = configuration.sound.clone() {
+        sound::init(
+            sound_config,
+            configuration.sink_routes.clone().unwrap_or_default(),
+            aao::timezone(&configuration.timezone),
+        );
+        bus::subscribe("sound", sound::on_event);
+    }
+    if let Some(caldav_config) = configuration.caldav.clone() {
+        caldav::init(caldav_config);
+        bus::subscribe("caldav", caldav::on_event);
+    }
+    if let Some(callback_config) = configuration.callbacks.clone() {
+        callback::init(callback_config);
+        bus::subscribe("callbacks", callback::on_event);
+    }
+    if let Some(weather_config) = configuration.weather.clone() {
+        weather::init(weather_config);
+        bus::subscribe("weather", weather::on_event);
+    }
+    if let Some(eta_config) = configuration.eta.clone() {
+        eta::init(eta_config);
+        bus::subscribe("eta", eta::on_event);
+    }
+
+    // Optional internal listener for the Prometheus export
+    if let Some(metrics_config) = &configuration.metrics_listener {
+        let result = prometheus::start_listener(metrics_config);
+        if let Err(e) = &result {
+            error!("Failed to start metrics listener: {e}");
+        }
+        component_started("metrics_listener", result.map(|_| ()));
+    }
+
+    // Start HTTPS web server (actix) before receiving from channel
+    if let Err(e) = web_server::start_https_server(&configuration) {
+        error!("Failed to start HTTPS server: {e}");
+    }
+
+    // Initialize global event queue, bounded so a stuck Fireplan API cannot grow memory without limit
+    let capacity = configuration.queue_capacity.unwrap_or(queue::DEFAULT_CAPACITY);
+    let policy = configuration.queue_overflow.unwrap_or_default();
+    let events = Arc::new(queue::BoundedQueue::new("events", capacity, policy, |event: Event| match event {
+        Event::Submit(incoming) => dropped_alarm("events", &incoming.id, &incoming.payload.title, &incoming.route.standort),
+        Event::Data(data) => dropped_alarm("events", &data.submission_id, &data.einsatzstichwort, &data.standort),
+        Event::Shutdown => {}
+    }));
+    let _ = EVENTS.set(Arc::clone(&events));
+
+    // Optional SMTP/LMTP listener for alarm mails pushed by the MTA
+    if let Some(listener_config) = configuration.mail_listener.clone() {
+        let result = smtp::start_listener(listener_config, configuration.mail_charsets.clone().unwrap_or_default());
+        if let Err(e) = &result {
+            error!("Failed to start mail listener: {e}");
+        }
+        component_started("mail_listener", result.map(|_| ()));
+    }
+
+    // Optional POCSAG/ZVEI decoder input as redundant trigger path
+    if let Some(pager_config) = configuration.pager_input.clone() {
+        let result = pager::start_input(pager_config, &configuration);
+        if let Err(e) = &result {
+            error!("Failed to start pager input: {e}");
+        }
+        component_started("pager_input", result);
+    }
+
+    // All listening sockets are bound, privileged ports are no longer needed
+    if let Some(user) = &configuration.run_as_user {
+        match privileges::drop_to(user) {
+            Ok(()) => {
+                audit::record("privileges_dropped", "local", user);
+                tenants::rebind_to_host();
+            }
+            Err(e) => {
+                error!("Could not switch to user {}: {}, refusing to run with full privileges", user, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    bus::publish(bus::BusEvent::ConfigLoaded { file: file.to_string() });
+
+    if let Some(days) = configuration.retention_days {
+        privacy::start_retention(days);
+    }
+
+    if configuration.fireplan_enabled.unwrap_or(true) {
+        fireplan::start_token_refresh(configuration.fireplan_api_key.clone(), configuration.standorte());
+    }
+
+    // Optional DIVERA pull mode for plans without outbound webhooks
+    if let Some(poll_config) = configuration.divera_poll.clone() {
+        divera::start_poller(poll_config);
+    }
+    if let Some(responses_config) = configuration.divera_responses.clone() {
+        responses::start_poller(responses_config);
+    }
+    if let Some(roster_config) = configuration.duty_roster.clone() {
+        roster::start(roster_config, aao::timezone(&configuration.timezone));
+    }
+    if let Some(probes) = configuration.probe_alarms.clone() {
+        let marker = configuration.format.clone().unwrap_or_default().einsatzmittel_marker().to_string();
+        probe::start(probes, marker, aao::timezone(&configuration.timezone));
+    }
+
+    if let Some(hosted) = configuration.tenants.clone() {
+        match tenants::current() {
+            Some(name) => warn!("Tenant {} cannot host tenants itself, ignoring its tenants", name),
+            None => tenants::start(hosted, &configuration.listening_ports()),
+        }
+    }
+
+    // Spawn a thread to listen for OS signals and send Shutdown (the Windows service stops via its control handler)
+    #[cfg(unix)]
+    {
+        std::thread::spawn(|| {
+            use signal_hook::consts::signal::*;
+            use signal_hook::iterator::Signals;
+
+            let mut signals = match Signals::new([SIGINT, SIGTERM, SIGHUP, SIGQUIT, SIGUSR1]) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to register signal handlers: {e}");
+                    return;
+                }
+            };
+
+            for sig in signals.forever() {
+                match sig {
+                    SIGINT | SIGTERM | SIGHUP | SIGQUIT => {
+                        let _ = send_event(Event::Shutdown);
+                        break;
+                    }
+                    // SIGUSR1 cycles the HTTP exchange tracing for Fireplan and DIVERA calls
+                    SIGUSR1 if trace::enabled() => trace::disable(),
+                    SIGUSR1 => trace::enable(30),
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    // RICs already alarmed per Einsatznummer, persisted in the state directory
+    dedup::set_ttl(configuration.dedup_ttl_hours);
+    dedup::load();
+    // alarms Fireplan did not accept before the restart
+    outbox::load();
+    // submit tokens minted through /api/tokens
+    tokens::load();
+    // recent alarms for trying config changes in the editor
+    samples::load();
+    config_edit::init(file, container);
+    if let Some(approval) = configuration.config_approval.clone() {
+        approval::init(approval);
+    }
+
+    // PLZ -> Ort table, optional
+    plz::init(&configuration.plz_file, &configuration.plz_orte);
+
+    // Local object database for enrichment, optional
+    let object_db = Arc::new(objects::load_from_config(&configuration.object_db));
+    if let Some(hydrant_config) = configuration.hydrants.clone() {
+        hydrants::init(hydrant_config);
+    }
+
+    // Optional merge window for the same incident arriving via several sources
+    let merge_window = Arc::new(
+        configuration
+            .merge_window_secs
+            .map(|secs| merge::MergeWindow::new(std::time::Duration::from_secs(secs))),
+    );
+
+    // Parser and submitter stages, 10 workers each, fed by bounded queues
+    let parse_queue = Arc::new(queue::BoundedQueue::new("parser", capacity, policy, |incoming: Incoming| {
+        dropped_alarm("parser", &incoming.id, &incoming.payload.title, &incoming.route.standort)
+    }));
+    let submit_queue = Arc::new(queue::BoundedQueue::new("submitter", capacity, policy, |data: ParsedData| {
+        dropped_alarm("submitter", &data.submission_id, &data.einsatzstichwort, &data.standort)
+    }));
+
+    // queue fill levels for /ready
+    {
+        let (events, parse_queue, submit_queue) = (Arc::clone(&events), Arc::clone(&parse_queue), Arc::clone(&submit_queue));
+        health::register_queue("events", move || events.level());
+        health::register_queue("parser", move || parse_queue.level());
+        health::register_queue("submitter", move || submit_queue.level());
+    }
+
+    for _ in 0..10 {
+        let configuration = configuration.clone();
+        let object_db = Arc::clone(&object_db);
+        let parse_queue = Arc::clone(&parse_queue);
+        let submit_queue = Arc::clone(&submit_queue);
+        std::thread::spawn(move || loop {
+            let incoming = parse_queue.pop();
+            let (id, context) = (incoming.id.clone(), format!("{:?}", incoming.payload));
+            let standort = incoming.route.standort.clone();
+            let started = Instant::now();
+            let parsed = supervisor::guard("parser", &context, || process_incoming(&configuration, &object_db, incoming));
+            // the Standort after overrides when the alarm was parsed
+            let standort = match &parsed {
+                Some(Some(data)) => data.standort.clone(),
+                _ => standort,
+            };
+            observe_stage("parse", &standort, started);
+            match parsed {
+                Some(Some(data)) => {
+                    for part in fan_out(data) {
+                        submit_queue.push(part);
+                    }
+                }
+                Some(None) => {}
+                None => bus::publish(bus::BusEvent::Failed {
+                    submission_id: id,
+                    reason: "internal error while parsing".to_string(),
+                    alarm: None,
+                    sinks: vec![],
+                }),
+            }
+        });
+    }
+
+    for _ in 0..10 {
+        let configuration = configuration.clone();
+        let merge_window = Arc::clone(&merge_window);
+        let submit_queue = Arc::clone(&submit_queue);
+        std::thread::spawn(move || loop {
+            let data = submit_queue.pop();
+            let (id, context, standort) = (data.submission_id.clone(), privacy::redact(&format!("{:?}", data)), data.standort.clone());
+            let started = Instant::now();
+            let submitted = supervisor::guard("submitter", &context, || process_data(&configuration, &merge_window, data));
+            observe_stage("submit", &standort, started);
+            if submitted.is_none() {
+                bus::publish(bus::BusEvent::Failed {
+                    submission_id: id,
+                    reason: "internal error while submitting".to_string(),
+                    alarm: None,
+                    sinks: vec![],
+                });
+            }
+        });
+    }
+
+    health::set_running();
+
+    // Route events from the sources to the pipeline stages
+    loop {
+        let event = events.pop();
+        if matches!(event, Event::Shutdown) {
+            info!("Shutdown event received, exiting main loop");
+            tenants::stop();
+            break;
+        }
+        let context = privacy::redact(&format!("{:?}", event));
+        supervisor::guard("router", &context, || match event {
+            Event::Data(data) => submit_queue.push(data),
+            Event::Submit(incoming) => {
+                samples::record(&incoming);
+                bus::publish(bus::BusEvent::AlarmReceived {
+                    submission_id: incoming.id.clone(),
+                    title: incoming.payload.title.clone(),
+                    standort: incoming.route.standort.clone(),
+                });
+                parse_queue.push(incoming)
+            }
+            Event::Shutdown => {}
+        });
+    }
+}
+
+// Alarm discarded from a full queue (overflow policy drop_oldest)
+fn dropped_alarm(stage: &str, submission_id: &str, title: &str, standort: &str) {
+    error!("Alarm {} ({}) dropped from the full {} queue", submission_id, title, stage);
+    bus::publish(bus::BusEvent::Dropped {
+        submission_id: submission_id.to_string(),
+        stage: stage.to_string(),
+        title: title.to_string(),
+        standort: standort.to_string(),
+    });
+}
+
+fn component_started<T: std::fmt::Display>(component: &str, result: Result<(), T>) {
+    match result {
+        Ok(()) => health::component(component, Ok(())),
+        Err(e) => bus::publish(bus::BusEvent::ComponentDown {
+            component: component.to_string(),
+            reason: e.to_string(),
+        }),
+    }
+}
+
+// Notification channels get queue overflows, lockouts, cancellations and Einsatzende messages, and alarms
+// when their priority says so
+fn notify_event(channels: &[notify::Channel], event: &bus::BusEvent) {
+    match event {
+        bus::BusEvent::Submitted { alarm } => {
+            let selected = notify::named(channels, &priority::channels(alarm.priority));
+            if !selected.is_empty() {
+                let address = format!("{} {}, {}", alarm.strasse, alarm.hausnummer, alarm.ort);
+                let head: Vec<String> =
+                    [address, eta::cached(&alarm.einsatznrlst).unwrap_or_default()].into_iter().filter(|l| !l.trim().is_empty()).collect();
+                let weather = weather::cached(&alarm.einsatznrlst).unwrap_or_default();
+                let text = format!("{}\n{}\n\n{}", head.join("\n"), alarm.zusatzinfo, weather);
+                notify::send(
+                    &selected,
+                    &notify::Notification::new("alarm", &alarm.einsatznrlst, &alarm.einsatzstichwort, text.trim()),
+                );
+            }
+        }
+        bus::BusEvent::Failed { reason, alarm: Some(alarm), sinks, .. } => {
+            let selected = notify::escalation(channels);
+            if !selected.is_empty() {
+                let rics = alarm.rics.iter().map(|r| r.text.as_str()).collect::<Vec<_>>().join(", ");
+                // people were paged when only another sink failed
+                let headline = if sinks.is_empty() || sinks.iter().any(|s| s == "Fireplan") {
+                    "NICHT ALARMIERT".to_string()
+                } else {
+                    format!("NICHT AN {} ÜBERMITTELT", sinks.join(", ").to_uppercase())
+                };
+                let text = format!(
+                    "{}: {}\n\n{} {}, {} {}\n{}\nRICs: {}\n{}",
+                    headline,
+                    reason,
+                    alarm.strasse,
+                    alarm.hausnummer,
+                    alarm.ort,
+                    alarm.ortsteil,
+                    alarm.objektname,
+                    rics,
+                    alarm.zusatzinfo
+                );
+                notify::send(
+                    &selected,
+                    &notify::Notification::new("escalation", &alarm.einsatznrlst, &alarm.einsatzstichwort, &text),
+                );
+            }
+        }
+        bus::BusEvent::Dropped { stage, title, .. } => {
+            let notification = notify::Notification::new(
+                "queue-overflow",
+                "",
+                title,
+                &format!("Alarm was dropped from the full {} queue and NOT sent to Fireplan", stage),
+            );
+            // may be published from the web server runtime, where blocking HTTP is not allowed
+            let channels = channels.to_vec();
+            std::thread::spawn(move || notify::send(&channels, &notification));
+        }
+        bus::BusEvent::Closed { foreign_id, title, text, .. } => {
+            notify::send(channels, &notify::Notification::new("einsatzende", foreign_id, title, text));
+        }
+        bus::BusEvent::LockedOut { peer, failures, ban_secs } => {
+            let notification = notify::Notification::new(
+                "auth-lockout",
+                "",
+                &format!("{} banned", peer),
+                &format!("{} failed authentications, banned for {} s; the submit token may be under attack", failures, ban_secs),
+            );
+            // published from the web server runtime
+            let channels = channels.to_vec();
+            std::thread::spawn(move || notify::send(&channels, &notification));
+        }
+        bus::BusEvent::Cancelled { einsatznummer, reason, notify: true, .. } => {
+            let notification =
+                notify::Notification::new("cancelled", einsatznummer, "Fehlalarm / Einsatzabbruch", reason);
+            // published from the web server runtime
+            let channels = channels.to_vec();
+            std::thread::spawn(move || notify::send(&channels, &notification));
+        }
+        _ => {}
+    }
+}
+
+// Time an alarm spent in a pipeline stage, queue waits not included
+fn observe_stage(stage: &str, standort: &str, started: Instant) {
+    prometheus::observe(
+        "alarm_processing_seconds",
+        "Processing time per alarm by pipeline stage (parse: parsing and enrichment, submit: deduplication and sinks) and Standort",
+        &[("stage", stage), ("standort", standort)],
+        started.elapsed().as_secs_f64(),
+    );
+}
+
+// Parser stage: Einsatzende handling, parsing and enrichment
+fn process_incoming(configuration: &Configuration, object_db: &Option<objects::ObjectDb>, incoming: Incoming) -> Option<ParsedData> {
+    let configuration = configuration.with_profile(&incoming.route.profile);
+    if closing::is_closing(&configuration, &incoming) {
+        closing::handle(&configuration, &incoming);
+        return None;
+    }
+
+    match parser::parse(incoming.payload, configuration.clone()) {
+        Ok(mut parsed_data) => {
+            parsed_data.submission_id = incoming.id.clone();
+            parsed_data.received = incoming.received;
+            parsed_data.standort = incoming.route.standort.clone();
+            parsed_data.sequence = incoming.sequence.clone();
+            parsed_data.drill = incoming.drill.clone();
+            if let Some(age) = stale(&configuration, &parsed_data) {
+                warn!("Alarm {} is {} s old, archived but not submitted", parsed_data.einsatznrlst, age);
+                bus::publish(bus::BusEvent::Expired { alarm: parsed_data, age_secs: age });
+                return None;
+            }
+            bus::publish(bus::BusEvent::Parsed {
+                submission_id: incoming.id.clone(),
+                einsatznrlst: parsed_data.einsatznrlst.clone(),
+                standort: parsed_data.standort.clone(),
+            });
+            if let Some(db) = object_db.as_ref() {
+                db.enrich(&mut parsed_data, &configuration.object_db_fields.clone().unwrap_or_default());
+            }
+            hydrants::enrich(&mut parsed_data);
+            if let Some(rules) = &configuration.aao_rules {
+                aao::apply(&mut parsed_data, rules, aao::timezone(&configuration.timezone));
+            }
+            if let Some(rules) = &configuration.overrides {
+                overrides::apply(&mut parsed_data, rules);
+            }
+            if let Some(rules) = &configuration.categories {
+                categories::classify(&mut parsed_data, rules);
+            }
+            info!("Parsed data handed to submitter");
+            Some(parsed_data)
+        }
+        Err(e) => {
+            error!("Failed to parse payload text: {}", e);
+            bus::publish(bus::BusEvent::Failed {
+                submission_id: incoming.id.clone(),
+                reason: format!("parse error: {}", e),
+                alarm: None,
+                sinks: vec![],
+            });
+            None
+        }
+    }
+}
+
+// Age of the alarm if it exceeds max_alarm_age_secs, e.g. an old unseen mail found after a mailbox migration.
+// Alarms without Leitstelle timestamp are never stale.
+fn stale(configuration: &Configuration, data: &ParsedData) -> Option<i64> {
+    let max = configuration.max_alarm_age_secs?;
+    if data.created <= 0 {
+        return None;
+    }
+    let age = chrono::Utc::now().timestamp() - data.created;
+    (age > max).then_some(age)
+}
+
+// One alarm per Standort: RICs with their own Standorte are alarmed there, the others at the Standort of the source
+fn fan_out(data: ParsedData) -> Vec<ParsedData> {
+    let mut parts: Vec<ParsedData> = vec![];
+    for ric in &data.rics {
+        let targets = match &ric.standorte {
+            Some(standorte) if !standorte.is_empty() => standorte.clone(),
+            _ => vec![data.standort.clone()],
+        };
+        for standort in targets {
+            match parts.iter_mut().find(|p| p.standort == standort) {
+                Some(part) => part.rics.push(ric.clone()),
+                None => parts.push(ParsedData {
+                    standort,
+                    rics: vec![ric.clone()],
+                    ..data.clone()
+                }),
+            }
+        }
+    }
+    if parts.len() > 1 {
+        info!(
+            "Alarm {} goes to Standorte {}",
+            data.einsatznrlst,
+            parts.iter().map(|p| p.standort.as_str()).collect::<Vec<_>>().join(", ")
+        );
+    }
+    if parts.is_empty() {
+        parts.push(data);
+    }
+    parts
+}
+
+// Submitter stage: merge, RIC deduplication and submission to Fireplan
+fn process_data(
+    configuration: &Configuration,
+    merge_window: &Option<merge::MergeWindow>,
+    mut data: ParsedData,
+) {
+    if let Some(window) = merge_window {
+        window.merge(&mut data);
+    }
+
+    if cancel::is_cancelled(&data.einsatznrlst) {
+        warn!("Einsatz {} was cancelled, not submitting alarm {}", data.einsatznrlst, data.submission_id);
+        bus::publish(bus::BusEvent::Cancelled {
+            einsatznummer: data.einsatznrlst.clone(),
+            reason: "Einsatz cancelled before submission".to_string(),
+            notify: false,
+            alarm: Some(data),
+        });
+        return;
+    }
+
+    if let Some(pre_submit_hooks) = &configuration.pre_submit_hooks {
+        data = match hooks::apply(pre_submit_hooks, data.clone()) {
+            hooks::Outcome::Continue(modified) => *modified,
+            hooks::Outcome::Veto(hook, reason) => {
+                warn!("Alarm vetoed by pre-submit hook {}: {}", hook, reason);
+                bus::publish(bus::BusEvent::Vetoed { alarm: data, hook, reason });
+                return;
+            }
+        };
+    }
+
+    // sinks the scripts left for the alarm, all when no scripts are configured
+    let mut sinks = vec![routing::Sink::Fireplan.name().to_string(), routing::Sink::FeuerSoftwareConnect.name().to_string()];
+    if scripting::enabled() {
+        (data, sinks) = scripting::apply(data, sinks);
+    }
+
+    if let Some(sequence) = &data.sequence {
+        info!("Alarm {} is mail {}", data.einsatznrlst, sequence);
+    }
+
+    // Deduplicate RICs based on (einsatznrlst, ric)
+    let alarmier_rics = dedup::claim(&data.einsatznrlst, &data.standort, &data.rics);
+
+    if alarmier_rics.is_empty() {
+        warn!("All contained RICs already submitted for this EinsatzNrLeitstelle, do not submit this alarm");
+        bus::publish(bus::BusEvent::Deduped { alarm: data });
+    } else {
+        data.rics = alarmier_rics;
+        // fetched while the sinks run, the alarm notification takes what is there by then
+        weather::prefetch(&data);
+        eta::prefetch(&data);
+        let submission_id = data.submission_id.clone();
+        let routes = configuration.sink_routes.as_deref().unwrap_or_default();
+        // the alarm with the RICs routed to the sink, None if the sink does not get it
+        let routed = |sink: routing::Sink, enabled: bool| {
+            if !enabled {
+                return None;
+            }
+            if !sinks.iter().any(|s| s == sink.name()) {
+                info!("Alarm {} is not sent to {:?}, removed by script", data.einsatznrlst, sink);
+                return None;
+            }
+            let rics = routing::rics_for(sink, routes, &data);
+            if rics.is_none() {
+                info!("Alarm {} is not routed to {:?}", data.einsatznrlst, sink);
+            }
+            rics.map(|rics| ParsedData { rics, ..data.clone() })
+        };
+        let fireplan_data = routed(routing::Sink::Fireplan, configuration.fireplan_enabled.unwrap_or(true));
+        let connect = configuration.feuersoftware_connect.as_ref().filter(|c| c.enabled());
+        let connect_data = routed(routing::Sink::FeuerSoftwareConnect, connect.is_some());
+        // the sinks are called in parallel, the alarm counts as submitted when every sink it was routed to accepted it
+        let (fireplan_result, connect_result) = std::thread::scope(|scope| {
+            let connect_handle = connect
+                .zip(connect_data.as_ref())
+                .map(|(c, d)| scope.spawn(move || connect::submit(c, d)));
+            let fireplan_result = fireplan_data.as_ref().map(|d| {
+                info!("Submitting to Fireplan Standort {}", d.standort);
+                fireplan::submit(d.standort.clone(), configuration.fireplan_api_key.clone(), d.clone())
+            });
+            let connect_result = connect_handle.map(|h| {
+                h.join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("FeuerSoftware Connect submit panicked")))
+            });
+            (fireplan_result, connect_result)
+        });
+        if let Some(result) = &fireplan_result {
+            prometheus::inc(
+                "fireplan_submissions_total",
+                "Alarm submissions to Fireplan by Standort and result",
+                &[("standort", &data.standort), ("result", if result.is_ok() { "ok" } else { "failed" })],
+            );
+        }
+        if let (Some(d), Some(Err(e))) = (&fireplan_data, &fireplan_result) {
+            fireplan::count_error(&d.standort, e);
+            outbox::add(d, e);
+        }
+        let fireplan_result = fireplan_result.map(|r| r.map_err(anyhow::Error::from));
+        let (failed_sinks, errors): (Vec<String>, Vec<String>) =
+            [("Fireplan", fireplan_result), ("FeuerSoftware Connect", connect_result)]
+                .into_iter()
+                .filter_map(|(sink, result)| result?.err().map(|e| (sink.to_string(), format!("{}: {}", sink, e))))
+                .unzip();
+        let result = if errors.is_empty() { Ok(()) } else { Err(anyhow::anyhow!(errors.join("; "))) };
+        match result {
+            Ok(()) => {
+                if data.created > 0 {
+                    info!(
+                        "Alarm {} reached Fireplan {} s after the Leitstelle timestamp",
+                        data.einsatznrlst,
+                        chrono::Utc::now().timestamp() - data.created
+                    );
+                }
+                bus::publish(bus::BusEvent::Submitted { alarm: data.clone() });
+            }
+            Err(e) => bus::publish(bus::BusEvent::Failed {
+                submission_id,
+                reason: e.to_string(),
+                alarm: Some(data.clone()),
+                sinks: failed_sinks,
+            }),
+        }
+        if let Some(script_path) = configuration.simple_trigger.clone() {
+            info!("Executing simple trigger");
+            match run_cmd!($script_path) {
+                Ok(()) => info!("Execute ok"),
+                Err(e) => error!("Failure: {e}")
+            }
+        }
+    }
+}
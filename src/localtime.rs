@@ -0,0 +1,35 @@
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use log::error;
+use once_cell::sync::OnceCell;
+
+// Timezone timestamps are shown in (display_timezone); history, dedup table and APIs keep UTC
+static DISPLAY: OnceCell<Tz> = OnceCell::new();
+
+pub fn init(name: &Option<String>) {
+    let tz = match name.as_deref() {
+        Some(n) => n.parse::<Tz>().unwrap_or_else(|e| {
+            error!("Invalid display_timezone {}: {}, showing UTC", n, e);
+            Tz::UTC
+        }),
+        None => Tz::UTC,
+    };
+    let _ = DISPLAY.set(tz);
+}
+
+pub fn timezone() -> Tz {
+    DISPLAY.get().copied().unwrap_or(Tz::UTC)
+}
+
+// RFC 3339 with the offset of the display timezone, so log lines stay unambiguous
+pub fn now() -> String {
+    Utc::now().with_timezone(&timezone()).to_rfc3339()
+}
+
+// "15.10.2026 08:12:03" for texts read by people
+pub fn format(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|t| t.with_timezone(&timezone()).format("%d.%m.%Y %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
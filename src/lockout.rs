@@ -0,0 +1,129 @@
+use crate::problem::problem;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use log::warn;
+use once_cell::sync::{Lazy, OnceCell};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use actix_web::http::header::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+// Temporary ban of addresses guessing tokens; active with the defaults when omitted
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct LockoutConfig {
+    // defaults to true
+    enabled: Option<bool>,
+    // failed attempts within window_secs that ban the address, defaults to 10 within 600
+    max_failures: Option<usize>,
+    window_secs: Option<i64>,
+    // defaults to 900
+    ban_secs: Option<i64>,
+    // reverse proxies whose X-Forwarded-For names the client; from other peers the header is ignored
+    trusted_proxies: Option<Vec<String>>,
+}
+
+#[derive(Default)]
+struct Attempts {
+    failures: VecDeque<i64>,
+    banned_until: i64,
+}
+
+static CONFIG: OnceCell<LockoutConfig> = OnceCell::new();
+
+// peer address -> recent failures and ban
+static ATTEMPTS: Lazy<Mutex<HashMap<String, Attempts>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn init(config: LockoutConfig) {
+    let _ = CONFIG.set(config);
+}
+
+fn config() -> LockoutConfig {
+    CONFIG.get().cloned().unwrap_or_default()
+}
+
+fn trusted(ip: &IpAddr) -> bool {
+    CONFIG
+        .get()
+        .and_then(|c| c.trusted_proxies.as_ref())
+        .is_some_and(|proxies| proxies.iter().any(|p| p.trim().parse::<IpAddr>().is_ok_and(|p| &p == ip)))
+}
+
+// Client address of a request: the peer, or behind a trusted proxy the last forwarded address that is not
+// a trusted proxy itself. A client can put anything into X-Forwarded-For, so it is not believed otherwise.
+pub fn client_addr(peer: Option<SocketAddr>, headers: &HeaderMap) -> String {
+    let Some(peer) = peer.map(|p| p.ip()) else {
+        return "unknown".to_string();
+    };
+    if !trusted(&peer) {
+        return peer.to_string();
+    }
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("X-Forwarded-For")
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .filter_map(|a| a.trim().parse().ok())
+        .collect();
+    forwarded.into_iter().rev().find(|ip| !trusted(ip)).unwrap_or(peer).to_string()
+}
+
+// Seconds the address is still banned, None if it may proceed
+pub fn banned(peer: &str) -> Option<i64> {
+    let now = chrono::Utc::now().timestamp();
+    let attempts = ATTEMPTS.lock().unwrap_or_else(|e| e.into_inner());
+    attempts.get(peer).map(|a| a.banned_until - now).filter(|left| *left > 0)
+}
+
+// Count a failed authentication, bans the address when it crosses the threshold
+pub fn failure(peer: &str) {
+    let config = config();
+    if !config.enabled.unwrap_or(true) {
+        return;
+    }
+    let (max_failures, window, ban_secs) =
+        (config.max_failures.unwrap_or(10).max(1), config.window_secs.unwrap_or(600), config.ban_secs.unwrap_or(900));
+    let now = chrono::Utc::now().timestamp();
+    let failures = {
+        let mut all = ATTEMPTS.lock().unwrap_or_else(|e| e.into_inner());
+        all.retain(|_, a| a.banned_until > now || a.failures.back().is_some_and(|t| now - t < window));
+        let attempts = all.entry(peer.to_string()).or_default();
+        while attempts.failures.front().is_some_and(|t| now - t >= window) {
+            attempts.failures.pop_front();
+        }
+        attempts.failures.push_back(now);
+        if attempts.failures.len() < max_failures || attempts.banned_until > now {
+            return;
+        }
+        attempts.banned_until = now + ban_secs;
+        std::mem::take(&mut attempts.failures).len()
+    };
+    warn!("{} failed authentications from {} within {} s, banned for {} s", failures, peer, window, ban_secs);
+    crate::prometheus::inc("auth_lockouts_total", "Addresses banned after repeated authentication failures", &[]);
+    crate::audit::record("auth_lockout", peer, &format!("{} failures, banned for {} s", failures, ban_secs));
+    crate::bus::publish(crate::bus::BusEvent::LockedOut { peer: peer.to_string(), failures, ban_secs });
+}
+
+// Middleware: banned addresses get 429 before any handler runs. Alarms with a valid token still go through,
+// a ban of a shared address (e.g. the Leitstelle NAT) must not stop them
+pub async fn reject_banned(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let peer = client_addr(req.peer_addr(), req.headers());
+    if let Some(left) = banned(&peer).filter(|_| !crate::web_server::is_valid_intake(req.request())) {
+        let mut response = problem(
+            StatusCode::TOO_MANY_REQUESTS,
+            "locked-out",
+            "Too many failed authentications, try again later",
+        );
+        response.headers_mut().insert(
+            actix_web::http::header::RETRY_AFTER,
+            actix_web::http::header::HeaderValue::from(left),
+        );
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
@@ -0,0 +1,51 @@
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+
+// Lines kept for /admin/logs
+const CAPACITY: usize = 1000;
+
+// (number of the first line in the buffer, lines)
+static LINES: Lazy<Mutex<(u64, VecDeque<String>)>> = Lazy::new(|| Mutex::new((0, VecDeque::new())));
+
+// Log target keeping the last lines of the application log in memory
+#[derive(Default)]
+pub struct Writer {
+    partial: Vec<u8>,
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            // whoever reaches the dashboard reads these, tokens and keys in bodies, urls and config lines are masked
+            let line = crate::config_edit::redact(&crate::trace::mask(String::from_utf8_lossy(&line).trim_end()));
+            let mut lines = LINES.lock().unwrap_or_else(|e| e.into_inner());
+            lines.1.push_back(line);
+            if lines.1.len() > CAPACITY {
+                lines.1.pop_front();
+                lines.0 += 1;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Lines from number `from` on (at most the last `max`), and the number of the next line
+pub fn since(from: Option<u64>, max: usize) -> (Vec<String>, u64) {
+    let lines = LINES.lock().unwrap_or_else(|e| e.into_inner());
+    let (first, buffer) = (&lines.0, &lines.1);
+    let next = first + buffer.len() as u64;
+    let skip = match from {
+        Some(from) => from.saturating_sub(*first) as usize,
+        None => 0,
+    }
+    .max(buffer.len().saturating_sub(max));
+    (buffer.iter().skip(skip).cloned().collect(), next)
+}
@@ -0,0 +1,222 @@
+use crate::SubmitPayload;
+use base64::Engine;
+use encoding_rs::Encoding;
+use log::{error, warn};
+use regex::Regex;
+
+// Charset of 8-bit mails that declare none and are not valid UTF-8, as sent by most Leitstellen
+const FALLBACK_CHARSET: &str = "iso-8859-15";
+
+// Parsed RFC 5322 message, only what the alarm pipeline needs
+pub struct Mail {
+    pub subject: String,
+    pub date: Option<i64>,
+    pub message_id: String,
+    pub body: String,
+}
+
+// Split header block into unfolded (name, value) pairs
+fn parse_headers(block: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in block.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, v)) = headers.last_mut() {
+                v.push(' ');
+                v.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+
+// Parameter of a structured header value, e.g. boundary of Content-Type
+fn header_param(value: &str, param: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        if k.trim().eq_ignore_ascii_case(param) {
+            Some(v.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn split_head_body(raw: &str) -> (&str, &str) {
+    raw.split_once("\n\n").unwrap_or((raw, ""))
+}
+
+// The raw message is held as one char per byte (see parse), so 8-bit content survives until its charset is known
+fn raw_bytes(s: &str) -> Vec<u8> {
+    s.chars().map(|c| c as u32 as u8).collect()
+}
+
+// Text from bytes in the declared charset; undeclared or broken UTF-8 is read in the configured charset,
+// without one as ISO-8859-15
+fn decode_charset(bytes: &[u8], declared: Option<&str>, default_charset: Option<&str>) -> String {
+    let label = declared.or(default_charset);
+    let encoding = match label.and_then(|l| Encoding::for_label(l.trim().as_bytes())) {
+        Some(e) => e,
+        None => {
+            if let Some(l) = label {
+                warn!("Unknown mail charset {}, guessing", l);
+            }
+            encoding_rs::UTF_8
+        }
+    };
+    if encoding == encoding_rs::UTF_8 && std::str::from_utf8(bytes).is_err() {
+        let fallback = default_charset
+            .and_then(|l| Encoding::for_label(l.trim().as_bytes()))
+            .or_else(|| Encoding::for_label(FALLBACK_CHARSET.as_bytes()))
+            .unwrap_or(encoding_rs::WINDOWS_1252);
+        return fallback.decode_without_bom_handling(bytes).0.to_string();
+    }
+    encoding.decode_without_bom_handling(bytes).0.to_string()
+}
+
+fn decode_quoted_printable(s: &str) -> Vec<u8> {
+    let mut out = vec![];
+    let bytes = raw_bytes(s);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if i + 1 < bytes.len() && bytes[i + 1] == b'\n' => i += 2,
+            b'=' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => out.push(b),
+                    None => out.extend_from_slice(&bytes[i..i + 3]),
+                }
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn decode_transfer(body: &str, encoding: &str) -> Vec<u8> {
+    match encoding.to_lowercase().as_str() {
+        "quoted-printable" => decode_quoted_printable(body),
+        "base64" => {
+            let compact: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(compact)
+                .unwrap_or_else(|e| {
+                    warn!("Could not decode base64 mail body: {}", e);
+                    raw_bytes(body)
+                })
+        }
+        _ => raw_bytes(body),
+    }
+}
+
+// Decode RFC 2047 encoded words (=?charset?Q|B?...?=) in header values
+fn decode_encoded_words(value: &str, default_charset: Option<&str>) -> String {
+    // whitespace between adjacent encoded words is not significant
+    let value = Regex::new(r"\?=\s+=\?").expect("static regex").replace_all(value, "?==?");
+    let re = Regex::new(r"=\?([^?]+)\?([QqBb])\?([^?]*)\?=").expect("static regex");
+    re.replace_all(&value, |caps: &regex::Captures| {
+        let bytes = if caps[2].eq_ignore_ascii_case("B") {
+            base64::engine::general_purpose::STANDARD
+                .decode(&caps[3])
+                .unwrap_or_default()
+        } else {
+            decode_quoted_printable(&caps[3].replace('_', " "))
+        };
+        decode_charset(&bytes, Some(&caps[1]), default_charset)
+    })
+    .to_string()
+}
+
+// Header value: raw 8-bit text (commonly UTF-8) and RFC 2047 encoded words
+fn decode_header(value: &str, default_charset: Option<&str>) -> String {
+    decode_encoded_words(&decode_charset(&raw_bytes(value), None, default_charset), default_charset)
+}
+
+// Text of the message: the part itself or the first text/plain part of a multipart message
+fn text_part(headers: &[(String, String)], body: &str, default_charset: Option<&str>) -> String {
+    let content_type = header(headers, "content-type").unwrap_or("text/plain");
+    let encoding = header(headers, "content-transfer-encoding").unwrap_or("7bit");
+
+    if content_type.to_lowercase().starts_with("multipart/") {
+        if let Some(boundary) = header_param(content_type, "boundary") {
+            let delimiter = format!("--{}", boundary);
+            for part in body.split(delimiter.as_str()).skip(1) {
+                if part.starts_with("--") {
+                    break;
+                }
+                let (head, part_body) = split_head_body(part.trim_start_matches('\n'));
+                let part_headers = parse_headers(head);
+                let part_type = header(&part_headers, "content-type").unwrap_or("text/plain").to_lowercase();
+                if part_type.starts_with("text/plain") || part_type.starts_with("multipart/") {
+                    return text_part(&part_headers, part_body, default_charset);
+                }
+            }
+        }
+        warn!("No text/plain part found in multipart mail");
+        return String::new();
+    }
+
+    let charset = header_param(content_type, "charset");
+    decode_charset(&decode_transfer(body, encoding), charset.as_deref(), default_charset)
+}
+
+// default_charset is used where the mail declares no charset or its UTF-8 is broken, for Leitstellen
+// that leave it out
+pub fn parse(raw: &[u8], default_charset: Option<&str>) -> Mail {
+    let raw: String = raw.iter().map(|&b| b as char).collect::<String>().replace('\r', "");
+    let (head, body) = split_head_body(&raw);
+    let headers = parse_headers(head);
+
+    Mail {
+        subject: crate::normalize::line(&decode_header(header(&headers, "subject").unwrap_or_default(), default_charset)),
+        date: header(&headers, "date")
+            .and_then(|d| chrono::DateTime::parse_from_rfc2822(d).ok())
+            .map(|d| d.timestamp()),
+        message_id: header(&headers, "message-id").unwrap_or_default().to_string(),
+        body: crate::normalize::text(&text_part(&headers, body, default_charset)),
+    }
+}
+
+fn capture(regex: &Option<String>, text: &str, name: &str) -> Option<String> {
+    let regex = regex.as_ref()?;
+    match Regex::new(regex) {
+        Ok(re) => text
+            .lines()
+            .find_map(|l| re.captures(l).and_then(|c| c.get(1)).map(|m| m.as_str().trim().to_string())),
+        Err(_) => {
+            error!("{} is not a proper regular expression", name);
+            None
+        }
+    }
+}
+
+// Convert an alarm mail into the webhook payload format, so it runs through the same parser
+pub fn to_payload(mail: &Mail, regex_einsatznummer: &Option<String>, regex_adresse: &Option<String>) -> SubmitPayload {
+    let ts = mail.date.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        id: 0,
+        foreign_id: capture(regex_einsatznummer, &mail.body, "regex_einsatznummer")
+            .unwrap_or_else(|| mail.message_id.clone()),
+        title: mail.subject.clone(),
+        text: mail.body.clone(),
+        address: capture(regex_adresse, &mail.body, "regex_adresse").unwrap_or_default(),
+        lat: String::new(),
+        lng: String::new(),
+        priority: 0,
+        cluster: vec![],
+        group: vec![],
+        vehicle: vec![],
+        ts_create: ts,
+        ts_update: ts,
+    }
+}
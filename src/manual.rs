@@ -0,0 +1,47 @@
+use crate::error::ParseError;
+use crate::SubmitPayload;
+
+// Alarm typed into the /manual page, e.g. when the Leitstelle alarms by phone because their system is down.
+// Form fields: stichwort, adresse, koordinaten ("lat,lng"), text, einsatznummer and ric (repeated, RIC texts).
+// The RICs go into the text after the Einsatzmittel marker, so the parser matches them as for Leitstelle texts.
+pub fn to_payload(body: &[u8], einsatzmittel_marker: &str) -> Result<SubmitPayload, ParseError> {
+    let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(body)?;
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim().to_string())
+            .unwrap_or_default()
+    };
+    let rics: Vec<String> = fields
+        .iter()
+        .filter(|(k, v)| k == "ric" && !v.trim().is_empty())
+        .map(|(_, v)| v.trim().to_string())
+        .collect();
+    let title = field("stichwort");
+    if title.is_empty() || rics.is_empty() {
+        return Err(ParseError::ManualIncomplete);
+    }
+
+    let now = chrono::Utc::now();
+    let einsatznummer = match field("einsatznummer") {
+        n if n.is_empty() => format!("MANUELL-{}", now.format("%Y%m%d%H%M%S")),
+        n => n,
+    };
+    let (lat, lng) = field("koordinaten")
+        .split_once(',')
+        .map(|(lat, lng)| (lat.trim().to_string(), lng.trim().to_string()))
+        .unwrap_or_default();
+    Ok(SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        foreign_id: einsatznummer,
+        title,
+        text: format!("{}\n{} {}", field("text"), einsatzmittel_marker, rics.join(", ")),
+        address: field("adresse"),
+        lat,
+        lng,
+        ts_create: now.timestamp(),
+        ts_update: now.timestamp(),
+        ..Default::default()
+    })
+}
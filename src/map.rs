@@ -0,0 +1,118 @@
+use base64::Engine;
+use log::{info, warn};
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+const DEFAULT_TILE_URL: &str = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+const TILE_SIZE: f64 = 256.0;
+
+pub struct MapSettings {
+    pub tile_url: String,
+    pub tile_cache: String,
+}
+
+impl MapSettings {
+    pub fn from_config(configuration: &crate::Configuration) -> MapSettings {
+        MapSettings {
+            tile_url: configuration.map_tile_url.clone().unwrap_or_else(|| DEFAULT_TILE_URL.to_string()),
+            tile_cache: configuration.map_tile_cache.clone().unwrap_or_else(crate::paths::tile_cache),
+        }
+    }
+}
+
+// Web mercator position in tile units
+fn tile_position(lat: f64, lng: f64, zoom: u8) -> (f64, f64) {
+    let n = 2f64.powi(zoom as i32);
+    let lat_rad = lat.to_radians();
+    let x = (lng + 180.0) / 360.0 * n;
+    let y = (1.0 - (lat_rad.tan() + 1.0 / lat_rad.cos()).ln() / std::f64::consts::PI) / 2.0 * n;
+    (x, y)
+}
+
+// Tile from disk cache, fetched from the tile server on first use
+pub fn tile(settings: &MapSettings, z: u8, x: u32, y: u32) -> anyhow::Result<Vec<u8>> {
+    let path = format!("{}/{}/{}/{}.png", settings.tile_cache, z, x, y);
+    if let Ok(bytes) = std::fs::read(&path) {
+        return Ok(bytes);
+    }
+
+    let url = settings
+        .tile_url
+        .replace("{z}", &z.to_string())
+        .replace("{x}", &x.to_string())
+        .replace("{y}", &y.to_string());
+    let bytes = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(concat!("fireplan_alarm_divera/", env!("CARGO_PKG_VERSION")))
+        .build()?
+        .get(&url)
+        .send()?
+        .error_for_status()?
+        .bytes()?
+        .to_vec();
+
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, &bytes)) {
+            warn!("Could not cache map tile {}: {}", path, e);
+        }
+    }
+    info!("Fetched map tile {}/{}/{}", z, x, y);
+    Ok(bytes)
+}
+
+// Self-contained SVG map (tiles embedded as data URIs) centered on the coordinates, with a marker
+// and the nearby water sources
+pub fn render_svg(settings: &MapSettings, lat: f64, lng: f64, zoom: u8, width: u32, height: u32) -> anyhow::Result<String> {
+    let zoom = zoom.min(19);
+    let (cx, cy) = tile_position(lat, lng, zoom);
+    let left = cx - width as f64 / 2.0 / TILE_SIZE;
+    let top = cy - height as f64 / 2.0 / TILE_SIZE;
+    let max_tile = 2u32.pow(zoom as u32);
+
+    let mut images = String::new();
+    for ty in top.floor() as i64..=(top + height as f64 / TILE_SIZE).floor() as i64 {
+        for tx in left.floor() as i64..=(left + width as f64 / TILE_SIZE).floor() as i64 {
+            if ty < 0 || ty >= max_tile as i64 {
+                continue;
+            }
+            let wrapped_x = tx.rem_euclid(max_tile as i64) as u32;
+            let png = match tile(settings, zoom, wrapped_x, ty as u32) {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("Map tile {}/{}/{} unavailable: {}", zoom, wrapped_x, ty, e);
+                    continue;
+                }
+            };
+            images.push_str(&format!(
+                r#"<image x="{:.1}" y="{:.1}" width="256" height="256" href="data:image/png;base64,{}"/>"#,
+                (tx as f64 - left) * TILE_SIZE,
+                (ty as f64 - top) * TILE_SIZE,
+                base64::engine::general_purpose::STANDARD.encode(png)
+            ));
+        }
+    }
+
+    let water: String = crate::hydrants::nearest(lat, lng)
+        .iter()
+        .map(|(source, distance)| {
+            let (sx, sy) = tile_position(source.lat, source.lng, zoom);
+            format!(
+                r##"<circle cx="{:.1}" cy="{:.1}" r="8" fill="#2563eb" stroke="#ffffff" stroke-width="3"><title>{} {:.0} m</title></circle>"##,
+                (sx - left) * TILE_SIZE,
+                (sy - top) * TILE_SIZE,
+                source.kind.replace('&', "&amp;").replace('<', "&lt;"),
+                distance
+            )
+        })
+        .collect();
+
+    let (mx, my) = (width as f64 / 2.0, height as f64 / 2.0);
+    Ok(format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">
+<rect width="100%" height="100%" fill="#e5e7eb"/>{images}{water}
+<circle cx="{mx}" cy="{my}" r="12" fill="#ef4444" stroke="#ffffff" stroke-width="4"/>
+<text x="4" y="{}" font-size="11" fill="#111827">© OpenStreetMap contributors</text>
+</svg>"##,
+        height - 4
+    ))
+}
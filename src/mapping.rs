@@ -0,0 +1,62 @@
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// Converts the JSON of another alarm source into the /submit payload.
+// Each payload field is a JSON pointer ("/alarm/keyword"), a template with pointers in braces
+// ("{/alarm/street} {/alarm/house}") or a constant.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct InputMapping {
+    pub name: String,
+    pub fields: HashMap<String, String>,
+}
+
+// Text of a value for templates, lists are joined with ", "
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(list) => list.iter().map(text).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+pub fn render(template: &str, source: &Value) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let pointer = &rest[start + 1..start + end];
+        out.push_str(&source.pointer(pointer).map(text).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl InputMapping {
+    pub fn apply(&self, source: &Value) -> Value {
+        let mut object = Map::new();
+        for (field, expression) in &self.fields {
+            let value = if expression.starts_with('/') && !expression.contains('{') {
+                // a plain pointer keeps the JSON type, only strings are converted to the field type
+                match source.pointer(expression) {
+                    None | Some(Value::Null) => continue,
+                    Some(Value::String(s)) => crate::form::typed(field, s.clone()),
+                    Some(Value::Array(list)) if list.iter().any(|v| !v.is_string()) => {
+                        crate::form::typed(field, text(&Value::Array(list.clone())))
+                    }
+                    Some(v) => v.clone(),
+                }
+            } else {
+                crate::form::typed(field, render(expression, source))
+            };
+            object.insert(field.clone(), value);
+        }
+        Value::Object(object)
+    }
+}
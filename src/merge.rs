@@ -0,0 +1,87 @@
+use crate::ParsedData;
+use log::info;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Recently seen alarms, used to merge the same incident arriving via mail, webhook and pager
+pub struct MergeWindow {
+    window: Duration,
+    recent: Mutex<Vec<(Instant, ParsedData)>>,
+}
+
+fn address(data: &ParsedData) -> String {
+    format!("{} {} {}", data.strasse, data.hausnummer, data.ort)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Same incident: same Standort and either same Einsatznummer or same address. An alarm without address
+// (e.g. pager) is only merged by its Einsatznummer, it would otherwise swallow any other incident
+fn same_incident(a: &ParsedData, b: &ParsedData) -> bool {
+    if a.standort != b.standort {
+        return false;
+    }
+    if !a.einsatznrlst.is_empty() && a.einsatznrlst == b.einsatznrlst {
+        return true;
+    }
+    let (addr_a, addr_b) = (address(a), address(b));
+    !addr_a.is_empty() && addr_a == addr_b
+}
+
+// Richest text wins: keep the longer value of each field
+fn richer(a: &str, b: &str) -> String {
+    if b.trim().len() > a.trim().len() { b.to_string() } else { a.to_string() }
+}
+
+impl MergeWindow {
+    pub fn new(window: Duration) -> MergeWindow {
+        MergeWindow {
+            window,
+            recent: Mutex::new(vec![]),
+        }
+    }
+
+    // Merge data with a recent alarm of the same incident: union of RICs, richest fields, first Einsatznummer.
+    // The merged alarm keeps the Einsatznummer of the first arrival so already submitted RICs are deduplicated.
+    pub fn merge(&self, data: &mut ParsedData) {
+        let Ok(mut recent) = self.recent.lock() else {
+            return;
+        };
+        recent.retain(|(ts, _)| ts.elapsed() < self.window);
+
+        let Some((ts, known)) = recent.iter_mut().find(|(_, known)| same_incident(known, data)) else {
+            recent.push((Instant::now(), data.clone()));
+            return;
+        };
+
+        info!(
+            "Merging alarm {} into incident {} seen {:?} ago",
+            data.einsatznrlst,
+            known.einsatznrlst,
+            ts.elapsed()
+        );
+
+        for ric in &data.rics {
+            if !known.rics.contains(ric) {
+                known.rics.push(ric.clone());
+            }
+        }
+        known.strasse = richer(&known.strasse, &data.strasse);
+        known.hausnummer = richer(&known.hausnummer, &data.hausnummer);
+        known.ort = richer(&known.ort, &data.ort);
+        known.ortsteil = richer(&known.ortsteil, &data.ortsteil);
+        known.objektname = richer(&known.objektname, &data.objektname);
+        known.koordinaten = richer(&known.koordinaten, &data.koordinaten);
+        known.einsatzstichwort = richer(&known.einsatzstichwort, &data.einsatzstichwort);
+        known.zusatzinfo = richer(&known.zusatzinfo, &data.zusatzinfo);
+        if known.einsatznrlst.is_empty() {
+            known.einsatznrlst = data.einsatznrlst.clone();
+        }
+
+        let submission_id = data.submission_id.clone();
+        *data = known.clone();
+        data.submission_id = submission_id;
+    }
+}
@@ -0,0 +1,144 @@
+use unicode_normalization::UnicodeNormalization;
+
+// Cleanup applied to every alarm text, whichever source it came from (mail, webhook, pager),
+// so the parser regexes only have to deal with one shape of text
+
+// Decoder and Leitstelle may send decomposed umlauts (NFD), compare everything composed
+pub fn nfc(s: &str) -> String {
+    s.nfc().collect()
+}
+
+// UTF-8 that was decoded as Latin-1 somewhere on the way, e.g. "StraÃŸe" or "Ã¼ber"
+fn fix_mojibake(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{c2}' || c == '\u{c3}' {
+            if let Some(&next) = chars.peek() {
+                let continuation = match next {
+                    '\u{80}'..='\u{bf}' => Some(next as u32 as u8),
+                    // Windows-1252 puts these where Latin-1 has control characters
+                    'Ÿ' => Some(0x9f),
+                    'Œ' => Some(0x8c),
+                    'œ' => Some(0x9c),
+                    'Š' => Some(0x8a),
+                    'š' => Some(0x9a),
+                    '„' => Some(0x84),
+                    '–' => Some(0x96),
+                    '—' => Some(0x97),
+                    _ => None,
+                };
+                if let Some(decoded) = continuation.and_then(|b| std::str::from_utf8(&[c as u32 as u8, b]).ok().map(str::to_string)) {
+                    result.push_str(&decoded);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+// Runs of spaces, tabs and non-breaking spaces become one space
+fn collapse_whitespace(line: &str) -> String {
+    line.split([' ', '\t', '\u{a0}'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Words the Leitstelle broke at its line width: "Feuerwehr-\nhaus" -> "Feuerwehrhaus".
+// Only joined when the next line continues in lower case, "Rettungsdienst-\nEinsatz" stays as it is.
+fn join_hyphenation(lines: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = vec![];
+    for line in lines {
+        if let Some(last) = result.last_mut() {
+            let broken = last.ends_with('-')
+                && last.chars().rev().nth(1).map(char::is_alphabetic).unwrap_or(false)
+                && line.chars().next().map(char::is_lowercase).unwrap_or(false);
+            if broken {
+                last.pop();
+                last.push_str(&line);
+                continue;
+            }
+        }
+        result.push(line);
+    }
+    result
+}
+
+// Multi-line texts: line endings, encoding, whitespace and hyphenation, at most one empty line in a row
+pub fn text(s: &str) -> String {
+    let s = s.replace("\r\n", "\n").replace('\r', "\n");
+    let s = nfc(&fix_mojibake(&s));
+    let lines = join_hyphenation(s.lines().map(collapse_whitespace).collect());
+
+    let mut result: Vec<String> = vec![];
+    for line in lines {
+        if line.is_empty() && result.last().map(|l: &String| l.is_empty()).unwrap_or(true) {
+            continue;
+        }
+        result.push(line);
+    }
+    while result.last().map(|l| l.is_empty()).unwrap_or(false) {
+        result.pop();
+    }
+    result.join("\n")
+}
+
+// Single-line fields like title or address; line breaks become spaces
+pub fn line(s: &str) -> String {
+    collapse_whitespace(&nfc(&fix_mojibake(s)).replace(['\r', '\n'], " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_line_endings() {
+        assert_eq!(text("Ort: Musterstadt\r\nOrtsteil: Nord\r\n"), "Ort: Musterstadt\nOrtsteil: Nord");
+        assert_eq!(text("Zeile 1\rZeile 2"), "Zeile 1\nZeile 2");
+    }
+
+    #[test]
+    fn decomposed_umlauts() {
+        assert_eq!(text("Mu\u{308}hlenweg"), "Mühlenweg");
+        assert_eq!(line("Stra\u{df}e U\u{308}berfu\u{308}hrung"), "Straße Überführung");
+    }
+
+    #[test]
+    fn latin1_mojibake() {
+        assert_eq!(text("HauptstraÃŸe 5, MÃ¼nchen"), "Hauptstraße 5, München");
+        assert_eq!(text("Ã„rztehaus Ã–lmÃ¼hle"), "Ärztehaus Ölmühle");
+        assert_eq!(line("Brand Ã¼ber 2 Etagen"), "Brand über 2 Etagen");
+    }
+
+    #[test]
+    fn correct_text_unchanged() {
+        assert_eq!(text("Einsatzmittel: LF 1, HLF 2\nÜberörtliche Hilfe"), "Einsatzmittel: LF 1, HLF 2\nÜberörtliche Hilfe");
+        assert_eq!(text("Ärger über Öl"), "Ärger über Öl");
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(text("Ort:\t  Musterstadt   \n   Objekt:  Schule\u{a0}\u{a0}Nord  "), "Ort: Musterstadt\nObjekt: Schule Nord");
+        assert_eq!(text("\n\nA\n\n\n\nB\n\n"), "A\n\nB");
+        assert_eq!(line("  Brand\n  Wohnhaus \r\n"), "Brand Wohnhaus");
+    }
+
+    #[test]
+    fn hyphenation() {
+        assert_eq!(text("Brand im Feuerwehr-\nhaus Nord"), "Brand im Feuerwehrhaus Nord");
+        assert_eq!(text("Rettungsdienst-\nEinsatz"), "Rettungsdienst-\nEinsatz");
+        assert_eq!(text("Einsatzmittel: UW 1/46-\n1"), "Einsatzmittel: UW 1/46-\n1");
+        assert_eq!(text("Kranken-\r\n  transport"), "Krankentransport");
+    }
+
+    #[test]
+    fn idempotent() {
+        let raw = "Stichwort: B2 Zimmer-\r\nbrand\r\n\r\n\r\nOrt:  MÃ¼hlheim \r\n";
+        assert_eq!(text(&text(raw)), text(raw));
+    }
+}
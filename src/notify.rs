@@ -0,0 +1,217 @@
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Notification channel, currently a generic webhook receiving JSON
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Channel {
+    name: String,
+    url: String,
+    digest: Option<Digest>,
+    // also gets the full alarm text when the submission failed, so people are alerted without the pager backend
+    #[serde(default)]
+    escalation: bool,
+}
+
+// Digest mode for message storms: above the rate, alarm and Einsatzende notifications are collected
+// and sent as one "digest" notification per batch or interval; queue overflows and escalations are always sent at once
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Digest {
+    // notifications within the last hour before the channel switches to digests
+    above_per_hour: usize,
+    // notifications per digest, a full batch is sent right away, defaults to 10
+    batch: Option<usize>,
+    // collected notifications are sent at least this often, defaults to 15
+    interval_mins: Option<u64>,
+}
+
+#[derive(Default)]
+struct DigestState {
+    // send times of the single notifications within the last hour
+    sent: VecDeque<i64>,
+    pending: Vec<Notification>,
+    last_digest: i64,
+}
+
+// channel name -> digest state
+static DIGESTS: Lazy<Mutex<HashMap<String, DigestState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Serialize, Debug)]
+pub struct Notification {
+    // "alarm", "einsatzende", ...
+    pub kind: String,
+    pub einsatznrlst: String,
+    pub title: String,
+    pub text: String,
+    pub timestamp: String,
+    // timestamp in display_timezone, "15.10.2026 08:12:03"
+    pub local_time: String,
+    // the collected notifications of a digest
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Notification>,
+}
+
+impl Notification {
+    pub fn new(kind: &str, einsatznrlst: &str, title: &str, text: &str) -> Notification {
+        Notification {
+            kind: kind.to_string(),
+            einsatznrlst: einsatznrlst.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            local_time: crate::localtime::format(chrono::Utc::now().timestamp()),
+            items: vec![],
+        }
+    }
+
+    fn digest(items: Vec<Notification>) -> Notification {
+        let text = items
+            .iter()
+            .map(|n| format!("{} {} {} {}", n.local_time, n.kind, n.einsatznrlst, n.title).trim().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let numbers = items
+            .iter()
+            .map(|n| n.einsatznrlst.as_str())
+            .filter(|nr| !nr.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let title = format!("{} Meldungen", items.len());
+        Notification {
+            items,
+            ..Notification::new("digest", &numbers, &title, &text)
+        }
+    }
+}
+
+pub fn named(channels: &[Channel], names: &[String]) -> Vec<Channel> {
+    channels.iter().filter(|c| names.contains(&c.name)).cloned().collect()
+}
+
+pub fn escalation(channels: &[Channel]) -> Vec<Channel> {
+    channels.iter().filter(|c| c.escalation).cloned().collect()
+}
+
+fn client() -> Option<Client> {
+    match Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(c) => Some(c),
+        Err(e) => {
+            error!("Notification: could not build HTTP client: {}", e);
+            None
+        }
+    }
+}
+
+fn post(client: &Client, channel: &Channel, notification: &Notification) {
+    match client.post(&channel.url).json(notification).send() {
+        Ok(r) if r.status().is_success() => {
+            info!("Notification {} sent to channel {}", notification.kind, channel.name)
+        }
+        Ok(r) => error!("Notification channel {} answered {}", channel.name, r.status()),
+        Err(e) => error!("Notification channel {} failed: {}", channel.name, e),
+    }
+}
+
+// What to send now: the notification itself, a full digest, or nothing while it waits for the next digest
+fn digest_or_single(channel: &Channel, digest: &Digest, notification: &Notification) -> Option<Notification> {
+    if notification.kind == "queue-overflow" || notification.kind == "escalation" {
+        return Some(notification.clone());
+    }
+    let now = chrono::Utc::now().timestamp();
+    let mut digests = DIGESTS.lock().unwrap_or_else(|e| e.into_inner());
+    let state = digests.entry(channel.name.clone()).or_default();
+    while state.sent.front().is_some_and(|t| now - t >= 3600) {
+        state.sent.pop_front();
+    }
+    if state.sent.len() < digest.above_per_hour && state.pending.is_empty() {
+        state.sent.push_back(now);
+        // back to single notifications
+        state.last_digest = 0;
+        return Some(notification.clone());
+    }
+    // the first digest goes out an interval after the switch
+    if state.last_digest == 0 {
+        state.last_digest = now;
+        warn!("Notification channel {}: more than {} notifications per hour, sending digests", channel.name, digest.above_per_hour);
+    }
+    state.pending.push(notification.clone());
+    if state.pending.len() >= digest.batch.unwrap_or(10).max(1) {
+        state.last_digest = now;
+        return Some(Notification::digest(std::mem::take(&mut state.pending)));
+    }
+    None
+}
+
+// Post the notification to all channels, failures are logged and do not stop other channels
+pub fn send(channels: &[Channel], notification: &Notification) {
+    let Some(client) = client() else {
+        return;
+    };
+
+    let notification = &Notification {
+        title: crate::privacy::redact(&notification.title),
+        text: crate::privacy::redact(&notification.text),
+        ..notification.clone()
+    };
+    for channel in channels {
+        let notification = match &channel.digest {
+            Some(digest) => match digest_or_single(channel, digest, notification) {
+                Some(n) => n,
+                None => continue,
+            },
+            None => notification.clone(),
+        };
+        post(&client, channel, &notification);
+    }
+}
+
+// Drop notifications of the Einsatz still waiting for a digest, returns how many
+pub fn discard(einsatznrlst: &str) -> usize {
+    let mut digests = DIGESTS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut discarded = 0;
+    for state in digests.values_mut() {
+        let before = state.pending.len();
+        state.pending.retain(|n| n.einsatznrlst != einsatznrlst);
+        discarded += before - state.pending.len();
+    }
+    discarded
+}
+
+// Send collected notifications of channels in digest mode once their interval is over
+pub fn start_digests(channels: Vec<Channel>) {
+    if !channels.iter().any(|c| c.digest.is_some()) {
+        return;
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(30));
+        let now = chrono::Utc::now().timestamp();
+        let due: Vec<(Channel, Notification)> = {
+            let mut digests = DIGESTS.lock().unwrap_or_else(|e| e.into_inner());
+            channels
+                .iter()
+                .filter_map(|channel| {
+                    let interval = channel.digest.as_ref()?.interval_mins.unwrap_or(15) as i64 * 60;
+                    let state = digests.get_mut(&channel.name)?;
+                    if state.pending.is_empty() || now - state.last_digest < interval {
+                        return None;
+                    }
+                    state.last_digest = now;
+                    Some((channel.clone(), Notification::digest(std::mem::take(&mut state.pending))))
+                })
+                .collect()
+        };
+        if due.is_empty() {
+            continue;
+        }
+        if let Some(client) = client() {
+            for (channel, notification) in due {
+                post(&client, &channel, &notification);
+            }
+        }
+    });
+}
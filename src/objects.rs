@@ -0,0 +1,94 @@
+use crate::ParsedData;
+use log::{error, info, warn};
+use std::collections::HashMap;
+
+// Local object database (Objektpläne), loaded from a semicolon separated CSV file with header row.
+// Columns "objektname" and "adresse" are used for lookup, all other columns may be appended to the alarm.
+pub struct ObjectDb {
+    objects: Vec<HashMap<String, String>>,
+}
+
+impl ObjectDb {
+    pub fn load(path: &str) -> anyhow::Result<ObjectDb> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .flexible(true)
+            .from_path(path)?;
+
+        let headers: Vec<String> = reader
+            .headers()?
+            .iter()
+            .map(|h| h.trim().to_lowercase())
+            .collect();
+
+        let mut objects = vec![];
+        for record in reader.records() {
+            let record = record?;
+            let object: HashMap<String, String> = headers
+                .iter()
+                .cloned()
+                .zip(record.iter().map(|v| v.trim().to_string()))
+                .collect();
+            objects.push(object);
+        }
+
+        info!("Loaded {} objects from object database {}", objects.len(), path);
+        Ok(ObjectDb { objects })
+    }
+
+    // Find object by objektname first, then by "strasse hausnummer"
+    fn lookup(&self, data: &ParsedData) -> Option<&HashMap<String, String>> {
+        let objektname = data.objektname.trim().to_lowercase();
+        if !objektname.is_empty() {
+            if let Some(o) = self.objects.iter().find(|o| {
+                o.get("objektname").map(|n| n.to_lowercase() == objektname).unwrap_or(false)
+            }) {
+                return Some(o);
+            }
+        }
+
+        let adresse = format!("{} {}", data.strasse.trim(), data.hausnummer.trim())
+            .trim()
+            .to_lowercase();
+        if adresse.is_empty() {
+            return None;
+        }
+        self.objects.iter().find(|o| {
+            o.get("adresse").map(|a| a.to_lowercase() == adresse).unwrap_or(false)
+        })
+    }
+
+    // Append configured fields of a matching object to zusatzinfo
+    pub fn enrich(&self, data: &mut ParsedData, fields: &[String]) {
+        let Some(object) = self.lookup(data) else {
+            return;
+        };
+
+        let mut lines = vec![];
+        for field in fields {
+            match object.get(&field.to_lowercase()) {
+                Some(value) if !value.is_empty() => lines.push(format!("{}: {}", field, value)),
+                Some(_) => {}
+                None => warn!("Object database has no column {}", field),
+            }
+        }
+
+        if lines.is_empty() {
+            return;
+        }
+
+        info!("Enriching alarm {} with object database entry", data.einsatznrlst);
+        data.zusatzinfo = format!("{}\n\nObjektinfo:\n{}", data.zusatzinfo, lines.join("\n"));
+    }
+}
+
+pub fn load_from_config(path: &Option<String>) -> Option<ObjectDb> {
+    let path = path.as_ref()?;
+    match ObjectDb::load(path) {
+        Ok(db) => Some(db),
+        Err(e) => {
+            error!("Could not load object database {}: {}", path, e);
+            None
+        }
+    }
+}
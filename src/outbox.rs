@@ -0,0 +1,147 @@
+use crate::error::FireplanError;
+use crate::ParsedData;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// Alarms Fireplan did not accept, kept across restarts until an operator flushes or deletes them
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct OutboxEntry {
+    // submission id of the alarm
+    pub id: String,
+    pub alarm: ParsedData,
+    // last failure
+    pub reason: String,
+    // false if Fireplan rejected the alarm or the API key, flushing only helps after a config change
+    #[serde(default = "retryable_default")]
+    pub retryable: bool,
+    pub queued: String,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct FlushResult {
+    pub submitted: Vec<String>,
+    // (id, Standort, reason)
+    pub failed: Vec<(String, String, String)>,
+}
+
+fn retryable_default() -> bool {
+    true
+}
+
+static OUTBOX: Lazy<Mutex<Vec<OutboxEntry>>> = Lazy::new(|| Mutex::new(vec![]));
+
+// serializes flushes, an alarm must not be sent twice by parallel flush requests
+static FLUSH_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn load() {
+    let file = crate::paths::outbox_file();
+    let entries: Vec<OutboxEntry> = match std::fs::read(&file) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            warn!("Could not read outbox {}: {}, starting empty", file, e);
+            vec![]
+        }),
+        Err(_) => vec![],
+    };
+    if !entries.is_empty() {
+        warn!("Outbox: {} alarms not accepted by Fireplan, see /api/outbox", entries.len());
+    }
+    *OUTBOX.lock().unwrap_or_else(|e| e.into_inner()) = entries;
+}
+
+fn save(entries: &[OutboxEntry]) {
+    let file = crate::paths::outbox_file();
+    let tmp = format!("{}.tmp", file);
+    let result = serde_json::to_vec(entries)
+        .map_err(std::io::Error::other)
+        .and_then(|content| std::fs::write(&tmp, content))
+        .and_then(|_| std::fs::rename(&tmp, &file));
+    if let Err(e) = result {
+        error!("Could not save outbox {}: {}", file, e);
+    }
+}
+
+// An alarm split over several Standorte is queued once per Standort under the same submission id
+fn is_entry(entry: &OutboxEntry, id: &str, standort: Option<&str>) -> bool {
+    entry.id == id && standort.is_none_or(|s| s == entry.alarm.standort)
+}
+
+// Queue an alarm after a failed Fireplan submission, a repeated failure updates the entry
+pub fn add(alarm: &ParsedData, error: &FireplanError) {
+    let mut entries = OUTBOX.lock().unwrap_or_else(|e| e.into_inner());
+    match entries.iter_mut().find(|e| is_entry(e, &alarm.submission_id, Some(&alarm.standort))) {
+        Some(entry) => {
+            entry.reason = error.to_string();
+            entry.retryable = error.is_retryable();
+            entry.attempts += 1;
+        }
+        None => entries.push(OutboxEntry {
+            id: alarm.submission_id.clone(),
+            alarm: alarm.clone(),
+            reason: error.to_string(),
+            retryable: error.is_retryable(),
+            queued: crate::localtime::now(),
+            attempts: 1,
+        }),
+    }
+    save(&entries);
+    info!("Outbox: alarm {} queued ({} waiting)", alarm.einsatznrlst, entries.len());
+}
+
+pub fn list() -> Vec<OutboxEntry> {
+    OUTBOX.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// False if there is no entry with this id; all Standorte of the submission if standort is None
+pub fn remove(id: &str, standort: Option<&str>) -> bool {
+    let mut entries = OUTBOX.lock().unwrap_or_else(|e| e.into_inner());
+    let before = entries.len();
+    entries.retain(|e| !is_entry(e, id, standort));
+    if entries.len() == before {
+        return false;
+    }
+    save(&entries);
+    true
+}
+
+// Remove the queued alarms of a cancelled Einsatz, returns how many
+pub fn discard(einsatznummer: &str) -> usize {
+    let mut entries = OUTBOX.lock().unwrap_or_else(|e| e.into_inner());
+    let before = entries.len();
+    entries.retain(|e| e.alarm.einsatznrlst != einsatznummer);
+    let removed = before - entries.len();
+    if removed > 0 {
+        save(&entries);
+    }
+    removed
+}
+
+// Submit all queued alarms to Fireplan again, oldest first; accepted alarms leave the outbox
+pub fn flush(api_key: &str) -> FlushResult {
+    let _flushing = FLUSH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut result = FlushResult::default();
+    for entry in list() {
+        let alarm = entry.alarm.clone();
+        // cancelled while the flush was running
+        if crate::cancel::is_cancelled(&alarm.einsatznrlst) {
+            remove(&entry.id, Some(&alarm.standort));
+            continue;
+        }
+        info!("Outbox: submitting alarm {} to Fireplan Standort {} again", alarm.einsatznrlst, alarm.standort);
+        match crate::fireplan::submit(alarm.standort.clone(), api_key.to_string(), alarm.clone()) {
+            Ok(()) => {
+                remove(&entry.id, Some(&alarm.standort));
+                crate::bus::publish(crate::bus::BusEvent::Submitted { alarm });
+                result.submitted.push(entry.id);
+            }
+            Err(e) => {
+                crate::fireplan::count_error(&alarm.standort, &e);
+                add(&alarm, &e);
+                result.failed.push((entry.id, alarm.standort.clone(), e.to_string()));
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,86 @@
+use crate::ParsedData;
+use log::{error, info};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Condition on a text field of the parsed alarm (einsatzstichwort, ortsteil, ort, standort, ...),
+// all given checks must hold, case-insensitive except the regex
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Condition {
+    field: String,
+    equals: Option<String>,
+    starts_with: Option<String>,
+    contains: Option<String>,
+    matches: Option<String>,
+}
+
+// "if ortsteil == 'Musterdorf' then standort = 'Abt2'": field -> text to set, prepend or append
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct OverrideRule {
+    when: Condition,
+    set: Option<HashMap<String, String>>,
+    prepend: Option<HashMap<String, String>>,
+    append: Option<HashMap<String, String>>,
+}
+
+fn holds(condition: &Condition, alarm: &serde_json::Map<String, Value>) -> bool {
+    let Some(value) = alarm.get(&condition.field).and_then(Value::as_str) else {
+        error!("Override rule: {} is not a text field of the alarm", condition.field);
+        return false;
+    };
+    let value = value.to_lowercase();
+    let checks = [
+        condition.equals.as_ref().map(|e| value == e.to_lowercase()),
+        condition.starts_with.as_ref().map(|s| value.starts_with(&s.to_lowercase())),
+        condition.contains.as_ref().map(|c| value.contains(&c.to_lowercase())),
+        condition.matches.as_ref().map(|m| match Regex::new(m) {
+            Ok(re) => re.is_match(alarm.get(&condition.field).and_then(Value::as_str).unwrap_or_default()),
+            Err(e) => {
+                error!("Override rule: {} is not a proper regular expression: {}", m, e);
+                false
+            }
+        }),
+    ];
+    checks.iter().all(|c| c.unwrap_or(true))
+}
+
+fn modify(alarm: &mut serde_json::Map<String, Value>, changes: &Option<HashMap<String, String>>, change: impl Fn(&str, &str) -> String) {
+    for (field, text) in changes.iter().flatten() {
+        match alarm.get_mut(field) {
+            Some(Value::String(value)) => *value = change(value, text),
+            _ => error!("Override rule: {} is not a text field of the alarm", field),
+        }
+    }
+}
+
+// Apply the rules in order after parsing, each sees the changes of the ones before
+pub fn apply(data: &mut ParsedData, rules: &[OverrideRule]) {
+    let Ok(Value::Object(mut alarm)) = serde_json::to_value(&*data) else {
+        return;
+    };
+    let mut changed = false;
+    for rule in rules {
+        if !holds(&rule.when, &alarm) {
+            continue;
+        }
+        info!("Override rule for {} applies to alarm {}", rule.when.field, data.einsatznrlst);
+        modify(&mut alarm, &rule.set, |_, text| text.to_string());
+        modify(&mut alarm, &rule.prepend, |value, text| {
+            if value.is_empty() { text.to_string() } else { format!("{}\n{}", text, value) }
+        });
+        modify(&mut alarm, &rule.append, |value, text| {
+            if value.is_empty() { text.to_string() } else { format!("{}\n{}", value, text) }
+        });
+        changed = true;
+    }
+    if !changed {
+        return;
+    }
+    match serde_json::from_value::<ParsedData>(Value::Object(alarm)) {
+        Ok(modified) => *data = modified,
+        Err(e) => error!("Override rules could not be applied: {}", e),
+    }
+}
@@ -0,0 +1,191 @@
+use crate::format::InputFormat;
+use crate::{Configuration, Event, Incoming, Ric, Route, SubmitPayload};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, UdpSocket};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct PagerInputConfig {
+    // "tcp" or "udp"
+    protocol: String,
+    pub port: u16,
+    allowed_peers: Option<Vec<String>>,
+    pub standort: Option<String>,
+    profile: Option<String>,
+    // RICs received within this window form one alarm, defaults to 5
+    collect_secs: Option<u64>,
+}
+
+// One decoded telegram: RIC/ZVEI code, subric letter and optional alpha text
+#[derive(Clone, Debug)]
+struct Telegram {
+    address: String,
+    subric: Option<String>,
+    text: String,
+}
+
+static POCSAG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"POCSAG\d*:\s*Address:\s*(\d+)\s+Function:\s*(\d)(?:\s+Alpha:\s*(.*))?").expect("static regex")
+});
+static ZVEI: Lazy<Regex> = Lazy::new(|| Regex::new(r"ZVEI\d*:\s*(\d{5})").expect("static regex"));
+
+// Parse multimon-ng / FMS32 style decoder output
+fn parse_line(line: &str) -> Option<Telegram> {
+    if let Some(caps) = POCSAG.captures(line) {
+        let function: u8 = caps[2].parse().unwrap_or(0);
+        return Some(Telegram {
+            address: caps[1].to_string(),
+            subric: Some(((b'A' + function.min(3)) as char).to_string()),
+            text: caps.get(3).map(|m| crate::normalize::line(m.as_str())).unwrap_or_default(),
+        });
+    }
+
+    ZVEI.captures(line).map(|caps| Telegram {
+        address: caps[1].to_string(),
+        subric: None,
+        text: String::new(),
+    })
+}
+
+fn matches(ric: &Ric, telegram: &Telegram) -> bool {
+    let configured = ric.ric.trim_start_matches('0');
+    configured == telegram.address.trim_start_matches('0')
+        && telegram.subric.as_ref().map(|s| s == &ric.subric).unwrap_or(true)
+}
+
+// Build a payload the parser understands: matched RIC texts are listed after "Einsatzmittel:" (or the configured marker)
+fn to_payload(rics: &[Ric], texts: &[String], format: &InputFormat) -> SubmitPayload {
+    let now = chrono::Utc::now();
+    let title = texts.iter().find(|t| !t.is_empty()).cloned().unwrap_or_else(|| "Pageralarm".to_string());
+    let mut text = texts.iter().filter(|t| !t.is_empty()).cloned().collect::<Vec<_>>().join("\n");
+    text.push_str(&format!(
+        "\n{} {}",
+        format.einsatzmittel_marker(),
+        rics.iter().map(|r| r.text.clone()).collect::<Vec<_>>().join(&format!("{} ", format.ric_separator()))
+    ));
+    SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        id: 0,
+        foreign_id: format!("PAGER-{}", now.format("%Y%m%d%H%M%S")),
+        title,
+        text,
+        address: String::new(),
+        lat: String::new(),
+        lng: String::new(),
+        priority: 0,
+        cluster: vec![],
+        group: vec![],
+        vehicle: vec![],
+        ts_create: now.timestamp(),
+        ts_update: now.timestamp(),
+    }
+}
+
+// Collect matching telegrams for a short window and emit them as one alarm
+fn collector(rx: mpsc::Receiver<Telegram>, config: PagerInputConfig, rics: Vec<Ric>, format: InputFormat) {
+    let window = Duration::from_secs(config.collect_secs.unwrap_or(5));
+    let route = Route {
+        standort: config.standort.clone().unwrap_or_else(|| Route::default().standort),
+        profile: config.profile.clone(),
+    };
+
+    while let Ok(first) = rx.recv() {
+        let mut telegrams = vec![first];
+        while let Ok(t) = rx.recv_timeout(window) {
+            telegrams.push(t);
+        }
+
+        let mut matched: Vec<Ric> = vec![];
+        let mut texts: Vec<String> = vec![];
+        for t in &telegrams {
+            for ric in rics.iter().filter(|r| matches(r, t)) {
+                if !matched.contains(ric) {
+                    matched.push(ric.clone());
+                }
+            }
+            if !texts.contains(&t.text) {
+                texts.push(t.text.clone());
+            }
+        }
+
+        if matched.is_empty() {
+            info!("Pager input: no configured RIC in {} telegrams", telegrams.len());
+            continue;
+        }
+
+        let payload = to_payload(&matched, &texts, &format);
+        info!("Pager input: alarm for {} RICs", matched.len());
+        crate::log_received(&payload);
+        if let Err(e) = crate::send_event(Event::Submit(Incoming::new(payload, route.clone()))) {
+            error!("Pager input: could not send event: {}", e);
+        }
+    }
+}
+
+fn peer_allowed(config: &PagerInputConfig, peer: &str) -> bool {
+    config.allowed_peers.as_ref().map(|p| p.iter().any(|a| a == peer)).unwrap_or(true)
+}
+
+// RICs and text format are taken from the pager's parser profile
+pub fn start_input(config: PagerInputConfig, configuration: &Configuration) -> std::io::Result<()> {
+    let configuration = configuration.with_profile(&config.profile);
+    let rics = configuration.rics.clone();
+    let format = configuration.format.clone().unwrap_or_default();
+    let (tx, rx) = mpsc::channel::<Telegram>();
+    {
+        let config = config.clone();
+        std::thread::spawn(move || collector(rx, config, rics, format));
+    }
+
+    info!("Pager input listening on {} port {}", config.protocol, config.port);
+    if config.protocol.eq_ignore_ascii_case("udp") {
+        let socket = UdpSocket::bind(("0.0.0.0", config.port))?;
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            loop {
+                match socket.recv_from(&mut buf) {
+                    Ok((n, peer)) => {
+                        if !peer_allowed(&config, &peer.ip().to_string()) {
+                            warn!("Pager input: ignoring datagram from {}", peer);
+                            continue;
+                        }
+                        for line in String::from_utf8_lossy(&buf[..n]).lines() {
+                            if let Some(t) = parse_line(line) {
+                                let _ = tx.send(t);
+                            }
+                        }
+                    }
+                    Err(e) => error!("Pager input: receive failed: {}", e),
+                }
+            }
+        });
+    } else {
+        let listener = TcpListener::bind(("0.0.0.0", config.port))?;
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let peer = stream.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default();
+                if !peer_allowed(&config, &peer) {
+                    warn!("Pager input: rejecting connection from {}", peer);
+                    continue;
+                }
+                info!("Pager input: decoder connected from {}", peer);
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                        if let Some(t) = parse_line(&line) {
+                            let _ = tx.send(t);
+                        }
+                    }
+                    info!("Pager input: decoder {} disconnected", peer);
+                });
+            }
+        });
+    }
+    Ok(())
+}
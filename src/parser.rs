@@ -1,13 +1,58 @@
 use crate::{Configuration, ParsedData, Ric, SubmitPayload};
 use anyhow::Result;
 use log::{error, warn};
+use once_cell::sync::Lazy;
 use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use crate::format::RemainderField;
+use crate::normalize::{self, nfc};
+use crate::ric_groups;
+
+// Upper bounds for the fields of a parsed alarm, a runaway Leitstelle text must not blow up
+// Fireplan requests, history or the monitor
+pub const MAX_FIELD_CHARS: usize = 256;
+pub const MAX_ZUSATZINFO_CHARS: usize = 8192;
+
+// First capture group, empty if the regex has none or it did not take part in the match
+fn group1(caps: &regex::Captures) -> String {
+    caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default()
+}
+
+fn bounded(s: &mut String, max: usize) {
+    if let Some((index, _)) = s.char_indices().nth(max) {
+        s.truncate(index);
+    }
+}
+
+// Compiled configured regexes by pattern, None for invalid ones; compiling dominated the parse time
+// when it was done per line
+static REGEXES: Lazy<Mutex<HashMap<String, Option<Regex>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn regex(pattern: &str) -> Option<Regex> {
+    let mut regexes = REGEXES.lock().unwrap_or_else(|e| e.into_inner());
+    regexes.entry(pattern.to_string()).or_insert_with(|| Regex::new(&nfc(pattern)).ok()).clone()
+}
+
+fn fold_umlauts(s: &str) -> String {
+    s.replace('ä', "ae")
+        .replace('ö', "oe")
+        .replace('ü', "ue")
+        .replace('Ä', "Ae")
+        .replace('Ö', "Oe")
+        .replace('Ü', "Ue")
+        .replace('ß', "ss")
+}
 
 pub fn parse(
     data: SubmitPayload,
     configuration: Configuration,
 ) -> Result<ParsedData> {
     let mut result = ParsedData {
+        submission_id: String::new(),
+        received: 0,
+        created: data.ts_create,
+        standort: String::new(),
         rics: vec![],
         einsatznrlst: "".to_string(),
         strasse: "".to_string(),
@@ -18,49 +63,46 @@ pub fn parse(
         koordinaten: "".to_string(),
         einsatzstichwort: "".to_string(),
         zusatzinfo: "".to_string(),
+        priority: data.priority,
+        sequence: None,
+        category: String::new(),
+        drill: None,
     };
 
-    // remove creepy windows line endings
-    let body = data.text.replace('\r', "");
-
-    for line in body.lines() {
+    let format = configuration.format.clone().unwrap_or_default();
 
+    // line endings, encoding and whitespace, see normalize
+    let body = normalize::text(&data.text);
 
+    let regex_ort = regex(&configuration.regex_ort);
+    let regex_ortsteil = regex(&configuration.regex_ortsteil);
+    let regex_objektname = regex(&configuration.regex_objektname);
+    if regex_ort.is_none() {
+        error!("regex_ort is not a proper regular expression");
+    }
+    if regex_ortsteil.is_none() {
+        error!("regex_ortsteil is not a proper regular expression");
+    }
+    if regex_objektname.is_none() {
+        error!("regex_objektname is not a proper regular expression");
+    }
 
-        if let Ok(re) = Regex::new(configuration.regex_ort.as_str()) {
-            if let Some(caps) = re.captures(line) {
-                result.ort = caps[1].to_string();
-            }
-        } else {
-            error!(
-                "regex_ort is not a proper regular expression",
-            );
+    for line in body.lines() {
+        if let Some(caps) = regex_ort.as_ref().and_then(|re| re.captures(line)) {
+            result.ort = group1(&caps);
         }
-
-        if let Ok(re) = Regex::new(configuration.regex_ortsteil.as_str()) {
-            if let Some(caps) = re.captures(line) {
-                result.ortsteil = caps[1].to_string();
-            }
-        } else {
-            error!(
-                "regex_ortsteil is not a proper regular expression",
-            );
+        if let Some(caps) = regex_ortsteil.as_ref().and_then(|re| re.captures(line)) {
+            result.ortsteil = group1(&caps);
         }
-
-        if let Ok(re) = Regex::new(configuration.regex_objektname.as_str()) {
-            if let Some(caps) = re.captures(line) {
-                result.objektname = caps[1].to_string();
-            }
-        } else {
-            error!(
-                "regex_objektname is not a proper regular expression",
-            );
+        if let Some(caps) = regex_objektname.as_ref().and_then(|re| re.captures(line)) {
+            result.objektname = group1(&caps);
         }
     }
 
-    // detect rics by text - now only in the substring after "Einsatzmittel:"
-    let rics_source = if let Some(start) = body.find("Einsatzmittel:") {
-        let start_idx = start + "Einsatzmittel:".len();
+    // detect rics by text - now only in the substring after "Einsatzmittel:" (or the configured marker)
+    let marker = format.einsatzmittel_marker();
+    let rics_source = if let Some(start) = body.find(marker) {
+        let start_idx = start + marker.len();
         body[start_idx..].to_string()
     } else {
         String::new()
@@ -70,41 +112,81 @@ pub fn parse(
         text: "Dummy Abt 1".to_string(),
         ric: "0999991".to_string(),
         subric: "B".to_string(),
+        umlaut_tolerant: None,
+        standorte: None,
+        info_only: None,
+        group: None,
     };
 
     let abt2_dummy_ric = Ric {
         text: "Dummy Abt 2".to_string(),
         ric: "0999992".to_string(),
         subric: "B".to_string(),
+        umlaut_tolerant: None,
+        standorte: None,
+        info_only: None,
+        group: None,
     };
 
     let abt3_dummy_ric = Ric {
         text: "Dummy Abt 3".to_string(),
         ric: "0999993".to_string(),
         subric: "B".to_string(),
+        umlaut_tolerant: None,
+        standorte: None,
+        info_only: None,
+        group: None,
     };
 
     let abt4_dummy_ric = Ric {
         text: "Dummy Abt 4".to_string(),
         ric: "0999994".to_string(),
         subric: "B".to_string(),
+        umlaut_tolerant: None,
+        standorte: None,
+        info_only: None,
+        group: None,
     };
 
-    for token in rics_source.split(',') {
+    // RIC texts normalized (and folded for umlaut tolerant RICs) once, not per token
+    let needles: Vec<(&Ric, String)> = configuration
+        .rics
+        .iter()
+        .map(|ric| match ric.umlaut_tolerant.unwrap_or(false) {
+            true => (ric, fold_umlauts(&nfc(&ric.text))),
+            false => (ric, nfc(&ric.text)),
+        })
+        .collect();
+    let any_umlaut_tolerant = configuration.rics.iter().any(|r| r.umlaut_tolerant.unwrap_or(false));
+
+    for token in rics_source.split(format.ric_separator()) {
         let mut temp_lines: Vec<Ric> = vec![];
-        for ric in configuration.rics.clone() {
-            if token.contains(ric.text.as_str()) {
+        let folded = any_umlaut_tolerant.then(|| fold_umlauts(token));
+        for (ric, needle) in &needles {
+            let haystack = match ric.umlaut_tolerant.unwrap_or(false) {
+                true => folded.as_deref().unwrap_or(token),
+                false => token,
+            };
+            if haystack.contains(needle.as_str()) {
                 // remove all previously found entries that are substrings, retain what is not a substring of the newly found
                 // each comma-separated part contains at maximum one RIC, so this is safe
-                temp_lines.retain(|x| !ric.text.contains(x.clone().text.as_str()));
+                temp_lines.retain(|x| !ric.text.contains(x.text.as_str()));
 
-                let new_ric = Ric {
-                    text: ric.text.clone(),
-                    ric: format!("{:0>7}", ric.ric),
-                    subric: ric.subric.clone(),
-                };
-
-                temp_lines.push(new_ric);
+                // a group reference alarms all its members
+                for member in ric_groups::expand(ric) {
+                    let new_ric = Ric {
+                        text: member.text.clone(),
+                        ric: format!("{:0>7}", member.ric),
+                        subric: member.subric.clone(),
+                        umlaut_tolerant: None,
+                        standorte: member.standorte.clone(),
+                        info_only: member.info_only,
+                        group: None,
+                    };
+                    if !temp_lines.contains(&new_ric) {
+                        temp_lines.push(new_ric);
+                    }
+                }
 
             }
         }
@@ -116,12 +198,16 @@ pub fn parse(
         text: "Dummy KdoW".to_string(),
         ric: "0999995".to_string(),
         subric: "B".to_string(),
+        umlaut_tolerant: None,
+        standorte: None,
+        info_only: None,
+        group: None,
     };
 
     result.rics.push(kdow_dummy_ric);
 
     // loop tokens again to check for vehicle names
-    for token in rics_source.split(',') {
+    for token in rics_source.split(format.ric_separator()) {
         if token.contains("UW 1/")
             && ! result.rics.contains(&abt1_dummy_ric) {
                 result.rics.push(abt1_dummy_ric.clone());
@@ -144,34 +230,68 @@ pub fn parse(
     }
 
     // trim spaces from all string fields
-    result.einsatzstichwort = data.title.trim().to_string();
+    result.einsatzstichwort = normalize::line(&data.title);
+    if let Some(regex) = format.regex_stichwort() {
+        match self::regex(regex) {
+            Some(re) => {
+                if let Some(caps) = body.lines().find_map(|l| re.captures(l)) {
+                    result.einsatzstichwort = group1(&caps).trim().to_string();
+                }
+            }
+            None => error!("regex_stichwort is not a proper regular expression"),
+        }
+    }
     result.ortsteil = result.ortsteil.trim().to_string();
     result.objektname = result.objektname.trim().to_string();
     result.ort = result.ort.trim().to_string();
     result.einsatznrlst = data.foreign_id;
 
-    // Google Maps coordinates from lat/lng (format: "lat,lng")
-    result.koordinaten = format!("{},{}", data.lat.trim(), data.lng.trim());
-
-    // Parse German-style address: "Straßenname Hausnummer" or just "Straßenname"
-    // Everything before the first comma is the address part
-    let address_part = data.address.split(',').next().unwrap_or("").trim();
-    // Split into tokens and check if the last token starts with a digit (house number)
-    let tokens: Vec<&str> = address_part.split_whitespace().collect();
-    if let Some(last) = tokens.last() {
-        if last.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
-            result.hausnummer = last.to_string();
-            result.strasse = tokens[..tokens.len() - 1].join(" ");
-        } else {
-            result.strasse = tokens.join(" ");
-            result.hausnummer = String::new();
+    // "lat,lng" with decimal points, empty without coordinates; fireplan_coordinates formats it for Fireplan
+    let (lat, lng) = (data.lat.trim().replace(',', "."), data.lng.trim().replace(',', "."));
+    if !lat.is_empty() || !lng.is_empty() {
+        result.koordinaten = format!("{},{}", lat, lng);
+    }
+
+    // Parse the address line, by default German-style: "Straßenname Hausnummer" or just "Straßenname"
+    let address_line = normalize::line(&data.address);
+    let address = format.split_address(&address_line);
+    result.strasse = address.strasse;
+    result.hausnummer = address.hausnummer;
+
+    // Ort from the postal code when the Ort regex found nothing: canonical name from the PLZ table,
+    // otherwise what follows the PLZ in the address line
+    if result.ort.is_empty() {
+        if let Some((plz, ort)) = format.postal_code(&address_line) {
+            result.ort = crate::plz::ort(&plz).unwrap_or(ort);
         }
-    } else {
-        result.strasse = String::new();
-        result.hausnummer = String::new();
     }
 
-    result.zusatzinfo = data.text;
+    result.zusatzinfo = body;
+    if !address.zusatz.is_empty() {
+        match format.house_number_remainder() {
+            RemainderField::Zusatzinfo if result.zusatzinfo.is_empty() => result.zusatzinfo = format!("Adresse: {}", address.zusatz),
+            RemainderField::Zusatzinfo => result.zusatzinfo = format!("{}\nAdresse: {}", result.zusatzinfo, address.zusatz),
+            RemainderField::Objektname if result.objektname.is_empty() => result.objektname = address.zusatz,
+            RemainderField::Objektname => result.objektname = format!("{}, {}", result.objektname, address.zusatz),
+            RemainderField::None => {}
+        }
+    }
+
+    for field in [
+        &mut result.einsatznrlst,
+        &mut result.strasse,
+        &mut result.hausnummer,
+        &mut result.ort,
+        &mut result.ortsteil,
+        &mut result.objektname,
+        &mut result.koordinaten,
+        &mut result.einsatzstichwort,
+    ] {
+        bounded(field, MAX_FIELD_CHARS);
+    }
+    bounded(&mut result.zusatzinfo, MAX_ZUSATZINFO_CHARS);
+
+    crate::priority::apply(&mut result);
 
     if result.einsatzstichwort.is_empty() {
         warn!("Parser: No EINSATZSTICHWORT found");
@@ -200,3 +320,158 @@ pub fn parse(
 
     Ok(result)
 }
+
+// Randomized tests: generated and mutated Leitstelle texts must never panic the parser and always
+// give bounded fields
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = "Einsatznummer: 2024-123456\nStichwort: B3 Wohnhaus\nOrt: Musterstadt\nOrtsteil: Nord\n\
+        Objekt: Grundschule Am Mühlenweg\nEinsatzmittel: HLF 1, DLK 23, UW 1/46-1, Ruestwagen, ELW";
+
+    const FRAGMENTS: &[&str] = &[
+        "Ort:", "Ortsteil:", "Objekt:", "Einsatzmittel:", "Stichwort:", "HLF", "DLK", "UW 1/", "UW 11", ",", ", ", ":",
+        "\n", "\r\n", "\r", "\t", " ", "\u{a0}", "-\n", "ä", "Ã¼", "Ã", "u\u{308}", "ß", "€", "😀", "\u{0}", "\u{feff}",
+        "\u{202e}", "12a-c / 1", "76131", "Musterstadt", "(", ")", "\\", "$", "{lat}", "\"", "'", "<b>", "&amp;",
+    ];
+
+    fn configuration() -> Configuration {
+        toml::from_str(
+            r#"
+            fireplan_api_key = "key"
+            regex_ort = 'Ort\s*:\s*(.*)'
+            regex_ortsteil = 'Ortsteil\s*:\s*(.*)'
+            regex_objektname = '(?:Objekt\s*:\s*(.*))|Gebäude'
+            http_port = 8080
+            http_host = "localhost"
+            auth_token = "t"
+            rics = [
+                { text = "HLF", ric = "123456", subric = "B" },
+                { text = "DLK", ric = "234567", subric = "C" },
+                { text = "Rüstwagen", ric = "345678", subric = "A", umlaut_tolerant = true },
+            ]
+            format = { regex_stichwort = 'Stichwort\s*:\s*(.*)' }
+            "#,
+        )
+        .expect("test configuration")
+    }
+
+    fn fragment() -> impl Strategy<Value = &'static str> {
+        proptest::sample::select(FRAGMENTS)
+    }
+
+    // up to 200 fragments, now and then followed by a very long line
+    fn generated() -> impl Strategy<Value = String> {
+        (proptest::collection::vec(fragment(), 0..200), proptest::option::weighted(0.1, fragment()))
+            .prop_map(|(fragments, long)| format!("{}{}", fragments.concat(), long.map(|f| f.repeat(5000)).unwrap_or_default()))
+    }
+
+    // deletes, duplicates and inserts at random char positions of the sample
+    fn mutated() -> impl Strategy<Value = String> {
+        proptest::collection::vec((any::<prop::sample::Index>(), 0..3u8, fragment()), 1..20).prop_map(|edits| {
+            let mut chars: Vec<char> = SAMPLE.chars().collect();
+            for (at, op, fragment) in edits {
+                let at = at.index(chars.len() + 1);
+                match op {
+                    0 if at < chars.len() => {
+                        chars.remove(at);
+                    }
+                    1 if at < chars.len() => {
+                        let c = chars[at];
+                        chars.insert(at, c);
+                    }
+                    _ => {
+                        chars.splice(at..at, fragment.chars());
+                    }
+                }
+            }
+            chars.into_iter().collect()
+        })
+    }
+
+    fn check(text: String, title: String, address: String) {
+        let payload = SubmitPayload { text, title, address, lat: "49,0".to_string(), ..Default::default() };
+        let data = parse(payload.clone(), configuration()).expect("parse");
+        for field in [
+            &data.einsatznrlst,
+            &data.strasse,
+            &data.hausnummer,
+            &data.ort,
+            &data.ortsteil,
+            &data.objektname,
+            &data.koordinaten,
+            &data.einsatzstichwort,
+        ] {
+            assert!(field.chars().count() <= MAX_FIELD_CHARS, "field too long for {:?}", payload);
+        }
+        assert!(data.zusatzinfo.chars().count() <= MAX_ZUSATZINFO_CHARS, "zusatzinfo too long for {:?}", payload);
+        assert!(data.rics.iter().any(|r| r.ric == "0999995"), "no KdoW RIC for {:?}", payload);
+    }
+
+    #[test]
+    fn sample_is_parsed() {
+        let data = parse(SubmitPayload { text: SAMPLE.to_string(), ..Default::default() }, configuration()).unwrap();
+        assert_eq!(data.ort, "Musterstadt");
+        assert_eq!(data.objektname, "Grundschule Am Mühlenweg");
+        assert_eq!(data.einsatzstichwort, "B3 Wohnhaus");
+        let rics: Vec<&str> = data.rics.iter().map(|r| r.ric.as_str()).collect();
+        assert_eq!(rics, ["0123456", "0234567", "0345678", "0999995", "0999991"]);
+    }
+
+    #[test]
+    fn house_number_is_the_last_number() {
+        for (address, strasse, hausnummer) in [
+            ("Straße des 17. Juni 5, 10623 Berlin", "Straße des 17. Juni", "5"),
+            ("L 560 12, 76131 Karlsruhe", "L 560", "12"),
+            ("Hauptstraße 12a-c / 1", "Hauptstraße", "12a-c"),
+        ] {
+            let payload = SubmitPayload { address: address.to_string(), ..Default::default() };
+            let data = parse(payload, configuration()).unwrap();
+            assert_eq!((data.strasse.as_str(), data.hausnummer.as_str()), (strasse, hausnummer), "{}", address);
+        }
+    }
+
+    #[test]
+    fn optional_capture_group_does_not_panic() {
+        let data = parse(SubmitPayload { text: "Gebäude".to_string(), ..Default::default() }, configuration()).unwrap();
+        assert_eq!(data.objektname, "");
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+
+        #[test]
+        fn generated_texts(text in generated(), title in generated(), address in generated()) {
+            check(text, title, address);
+        }
+
+        #[test]
+        fn mutated_texts(text in mutated(), title in mutated(), address in mutated()) {
+            check(text, title, address.replace('\n', " "));
+        }
+
+        #[test]
+        fn arbitrary_texts(text in any::<String>(), title in any::<String>(), address in any::<String>()) {
+            check(text, title, address);
+        }
+    }
+
+    #[test]
+    fn oversized_fields_are_cut() {
+        let long = "Ä".repeat(MAX_FIELD_CHARS * 4);
+        let payload = SubmitPayload {
+            text: format!("Ort: {}\n{}", long, "x".repeat(MAX_ZUSATZINFO_CHARS * 2)),
+            title: long.clone(),
+            foreign_id: long.clone(),
+            address: format!("{} 12", long),
+            ..Default::default()
+        };
+        let data = parse(payload, configuration()).unwrap();
+        assert_eq!(data.ort.chars().count(), MAX_FIELD_CHARS);
+        assert_eq!(data.einsatznrlst.chars().count(), MAX_FIELD_CHARS);
+        assert_eq!(data.zusatzinfo.chars().count(), MAX_ZUSATZINFO_CHARS);
+    }
+}
@@ -0,0 +1,97 @@
+use once_cell::sync::OnceCell;
+use std::path::PathBuf;
+
+static STATE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+// Configured state directory (state_dir), set once at startup
+pub fn set_state_dir(dir: &str) {
+    let _ = std::fs::create_dir_all(dir);
+    let _ = STATE_DIR.set(PathBuf::from(dir));
+}
+
+// Logs and state files: /root as before on Linux, %ProgramData%\fireplan_alarm_divera on Windows
+pub fn state_dir() -> PathBuf {
+    if let Some(dir) = STATE_DIR.get() {
+        dir.clone()
+    } else if cfg!(windows) {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        let dir = PathBuf::from(base).join("fireplan_alarm_divera");
+        let _ = std::fs::create_dir_all(&dir);
+        dir
+    } else {
+        PathBuf::from("/root")
+    }
+}
+
+pub fn state_file(name: &str) -> String {
+    state_dir().join(name).to_string_lossy().to_string()
+}
+
+pub fn received_log() -> String {
+    state_file("fireplan_alarm_divera_received")
+}
+
+pub fn submitted_log() -> String {
+    state_file("fireplan_alarm_divera_submitted")
+}
+
+pub fn history_file() -> String {
+    state_file("fireplan_alarm_divera_history.jsonl")
+}
+
+pub fn audit_file() -> String {
+    state_file("fireplan_alarm_divera_audit.jsonl")
+}
+
+pub fn dedup_file() -> String {
+    state_file("fireplan_alarm_divera_dedup.jsonl")
+}
+
+pub fn mail_seen_file() -> String {
+    state_file("fireplan_alarm_divera_mails.json")
+}
+
+pub fn tokens_file() -> String {
+    state_file("fireplan_alarm_divera_tokens.json")
+}
+
+pub fn outbox_file() -> String {
+    state_file("fireplan_alarm_divera_outbox.json")
+}
+
+pub fn samples_file() -> String {
+    state_file("fireplan_alarm_divera_samples.json")
+}
+
+pub fn tile_cache() -> String {
+    state_file("fireplan_alarm_divera_tiles")
+}
+
+// Runtime log file for the Windows service, which has no console
+#[cfg(windows)]
+pub fn service_log() -> String {
+    state_file("fireplan_alarm_divera.log")
+}
+
+// Let's Encrypt layout on Linux, <state dir>\certs\<hostname> on Windows
+pub fn cert_dir(hostname: &str) -> String {
+    if cfg!(windows) {
+        state_dir().join("certs").join(hostname).to_string_lossy().to_string()
+    } else {
+        format!("/etc/letsencrypt/live/{hostname}")
+    }
+}
+
+pub fn default_config_file() -> String {
+    if cfg!(windows) {
+        format!(
+            "{}\\fireplan_alarm_divera.conf",
+            std::env::var("USERPROFILE").unwrap()
+        )
+    } else {
+        format!(
+            "{}/fireplan_alarm_divera.conf",
+            homedir::my_home().unwrap().unwrap().to_string_lossy()
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use crate::SubmitPayload;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// Version of the /submit payload format this build understands
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    UnsignedInteger,
+    Integer,
+    Text,
+    // DIVERA sends coordinates as strings, other sources as numbers
+    TextOrNumber,
+    TextList,
+}
+
+const FIELDS: [(&str, Kind); 14] = [
+    ("schema_version", Kind::UnsignedInteger),
+    ("id", Kind::UnsignedInteger),
+    ("foreign_id", Kind::Text),
+    ("title", Kind::Text),
+    ("text", Kind::Text),
+    ("address", Kind::Text),
+    ("lat", Kind::TextOrNumber),
+    ("lng", Kind::TextOrNumber),
+    ("priority", Kind::UnsignedInteger),
+    ("cluster", Kind::TextList),
+    ("group", Kind::TextList),
+    ("vehicle", Kind::TextList),
+    ("ts_create", Kind::Integer),
+    ("ts_update", Kind::Integer),
+];
+
+// Plausibility checks on /submit beyond the schema, so a leaked token alone cannot page arbitrary text
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct PayloadGuard {
+    // the title has to start with one of these keywords (case-insensitive), e.g. ["B", "THL", "RD"]
+    keywords: Option<Vec<String>>,
+    // coordinates, when present, have to lie within [min_lat, min_lng, max_lat, max_lng]
+    bounding_box: Option<[f64; 4]>,
+    // reject alarms without coordinates, defaults to false
+    require_coordinates: Option<bool>,
+    // in characters
+    max_title_length: Option<usize>,
+    max_text_length: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+fn field_error(field: &str, message: &str) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn check(kind: Kind, value: &Value) -> Result<(), &'static str> {
+    let ok = match kind {
+        Kind::UnsignedInteger => value.is_u64(),
+        Kind::Integer => value.is_i64() || value.is_u64(),
+        Kind::Text => value.is_string(),
+        Kind::TextOrNumber => value.is_string() || value.is_number(),
+        Kind::TextList => value.as_array().map(|a| a.iter().all(|v| v.is_string())).unwrap_or(false),
+    };
+    if ok {
+        return Ok(());
+    }
+    Err(match kind {
+        Kind::UnsignedInteger => "expected a non-negative integer",
+        Kind::Integer => "expected an integer",
+        Kind::Text => "expected a string",
+        Kind::TextOrNumber => "expected a string or number",
+        Kind::TextList => "expected an array of strings",
+    })
+}
+
+// Validate a /submit body field by field; missing fields get defaults, null counts as missing.
+// With strict, fields not in the schema are rejected instead of ignored.
+pub fn validate(body: &Value, strict: bool) -> Result<SubmitPayload, Vec<FieldError>> {
+    let Some(object) = body.as_object() else {
+        return Err(vec![field_error("", "expected a JSON object")]);
+    };
+
+    let mut errors = vec![];
+    let mut normalized = Map::new();
+    for (name, value) in object {
+        if value.is_null() {
+            continue;
+        }
+        match FIELDS.iter().find(|(f, _)| f == name) {
+            Some((_, kind)) => match check(*kind, value) {
+                Ok(()) if *kind == Kind::TextOrNumber && value.is_number() => {
+                    normalized.insert(name.clone(), Value::String(value.to_string()));
+                }
+                Ok(()) => {
+                    normalized.insert(name.clone(), value.clone());
+                }
+                Err(message) => errors.push(field_error(name, message)),
+            },
+            None if strict => errors.push(field_error(name, "unknown field")),
+            None => {}
+        }
+    }
+
+    if let Some(version) = object.get("schema_version").and_then(|v| v.as_u64()) {
+        if version == 0 || version > SCHEMA_VERSION as u64 {
+            errors.push(field_error(
+                "schema_version",
+                &format!("unsupported version, this service understands 1 to {}", SCHEMA_VERSION),
+            ));
+        }
+    }
+    if let Some(priority) = object.get("priority").and_then(|v| v.as_u64()) {
+        if priority > u8::MAX as u64 {
+            errors.push(field_error("priority", "must not exceed 255"));
+        }
+    }
+    let has_text = |f: &str| normalized.get(f).and_then(|v| v.as_str()).map(|s| !s.trim().is_empty()).unwrap_or(false);
+    if !has_text("title") && !has_text("text") && !errors.iter().any(|e| e.field == "title" || e.field == "text") {
+        errors.push(field_error("title", "title or text is required"));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    serde_json::from_value(Value::Object(normalized)).map_err(|e| vec![field_error("", &e.to_string())])
+}
+
+// Semantic checks of a schema-valid payload, empty if it passes the guard
+pub fn guard(payload: &SubmitPayload, guard: &PayloadGuard) -> Vec<FieldError> {
+    let mut errors = vec![];
+    if let Some(keywords) = &guard.keywords {
+        let title = payload.title.trim().to_lowercase();
+        if !keywords.iter().any(|k| title.starts_with(&k.trim().to_lowercase())) {
+            errors.push(field_error("title", "keyword not allowed"));
+        }
+    }
+    if let Some(max) = guard.max_title_length {
+        if payload.title.chars().count() > max {
+            errors.push(field_error("title", &format!("longer than {} characters", max)));
+        }
+    }
+    if let Some(max) = guard.max_text_length {
+        if payload.text.chars().count() > max {
+            errors.push(field_error("text", &format!("longer than {} characters", max)));
+        }
+    }
+    let has_coordinates = !payload.lat.trim().is_empty() || !payload.lng.trim().is_empty();
+    if has_coordinates {
+        match crate::coordinates::parse(&format!("{},{}", payload.lat, payload.lng)) {
+            Some((lat, lng)) => {
+                if let Some([min_lat, min_lng, max_lat, max_lng]) = guard.bounding_box {
+                    if !(min_lat..=max_lat).contains(&lat) || !(min_lng..=max_lng).contains(&lng) {
+                        errors.push(field_error("lat", "coordinates outside the operating area"));
+                    }
+                }
+            }
+            None => errors.push(field_error("lat", "coordinates are not numbers")),
+        }
+    } else if guard.require_coordinates.unwrap_or(false) {
+        errors.push(field_error("lat", "coordinates are required"));
+    }
+    errors
+}
@@ -0,0 +1,48 @@
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+
+// PLZ -> canonical Ort, from plz_file and plz_orte, set once at startup
+static TABLE: OnceCell<HashMap<String, String>> = OnceCell::new();
+
+// plz_file is a semicolon separated "PLZ;Ort" list, a header row and further columns are ignored
+fn load_file(path: &str) -> anyhow::Result<HashMap<String, String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .flexible(true)
+        .has_headers(false)
+        .from_path(path)?;
+    let mut table = HashMap::new();
+    for record in reader.records() {
+        let record = record?;
+        if let (Some(plz), Some(ort)) = (record.get(0), record.get(1)) {
+            let plz = plz.trim();
+            if !plz.is_empty() && plz.chars().all(|c| c.is_ascii_digit()) {
+                table.entry(plz.to_string()).or_insert_with(|| ort.trim().to_string());
+            }
+        }
+    }
+    Ok(table)
+}
+
+// Entries of plz_orte override those of plz_file
+pub fn init(file: &Option<String>, orte: &Option<HashMap<String, String>>) {
+    let mut table = HashMap::new();
+    if let Some(path) = file {
+        match load_file(path) {
+            Ok(loaded) => {
+                info!("Loaded {} postal codes from {}", loaded.len(), path);
+                table = loaded;
+            }
+            Err(e) => error!("Could not load PLZ table {}: {}", path, e),
+        }
+    }
+    for (plz, ort) in orte.iter().flatten() {
+        table.insert(plz.trim().to_string(), ort.trim().to_string());
+    }
+    let _ = TABLE.set(table);
+}
+
+pub fn ort(plz: &str) -> Option<String> {
+    TABLE.get()?.get(plz).cloned()
+}
@@ -0,0 +1,58 @@
+use crate::ParsedData;
+use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+// Handling of one Einsatz priority (the payload's priority field, DIVERA: 1 = Sonderrechte)
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct PriorityLevel {
+    priority: u8,
+    // subRIC for all RICs of the alarm, e.g. "A" for the loud alarm tone
+    subric: Option<String>,
+    // notification channels (by name) that get an "alarm" notification
+    channels: Option<Vec<String>>,
+    // monitor page: keyword color and a label shown next to it
+    color: Option<String>,
+    label: Option<String>,
+}
+
+// Monitor styling of a priority level
+#[derive(Clone, Serialize, Debug)]
+pub struct Style {
+    pub color: Option<String>,
+    pub label: Option<String>,
+}
+
+static LEVELS: OnceCell<Vec<PriorityLevel>> = OnceCell::new();
+
+pub fn init(levels: Option<Vec<PriorityLevel>>) {
+    let _ = LEVELS.set(levels.unwrap_or_default());
+}
+
+fn level(priority: u8) -> Option<&'static PriorityLevel> {
+    LEVELS.get()?.iter().find(|l| l.priority == priority)
+}
+
+// Set the subRIC of the alarm's priority level
+pub fn apply(data: &mut ParsedData) {
+    if let Some(subric) = level(data.priority).and_then(|l| l.subric.as_ref()) {
+        for ric in data.rics.iter_mut() {
+            ric.subric = subric.clone();
+        }
+    }
+}
+
+// Notification channels for alarms of this priority, empty when none are configured
+pub fn channels(priority: u8) -> Vec<String> {
+    level(priority).and_then(|l| l.channels.clone()).unwrap_or_default()
+}
+
+// priority -> style for the monitor page
+pub fn styles() -> std::collections::BTreeMap<u8, Style> {
+    LEVELS
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|l| (l.priority, Style { color: l.color.clone(), label: l.label.clone() }))
+        .collect()
+}
@@ -0,0 +1,106 @@
+use crate::ParsedData;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+const DEFAULT_REPLACEMENT: &str = "[entfernt]";
+
+// Personal data (caller names, patient info) removed before storing or notifying; Fireplan still gets the full text
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Redaction {
+    regex: String,
+    replacement: Option<String>,
+}
+
+static REDACTIONS: OnceCell<Vec<(Regex, String)>> = OnceCell::new();
+
+pub fn init(redactions: &Option<Vec<Redaction>>) {
+    let compiled = redactions
+        .iter()
+        .flatten()
+        .filter_map(|r| match Regex::new(&r.regex) {
+            Ok(re) => Some((re, r.replacement.clone().unwrap_or_else(|| DEFAULT_REPLACEMENT.to_string()))),
+            Err(e) => {
+                error!("Redaction regex {} is not a proper regular expression: {}", r.regex, e);
+                None
+            }
+        })
+        .collect();
+    let _ = REDACTIONS.set(compiled);
+}
+
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for (re, replacement) in REDACTIONS.get().into_iter().flatten() {
+        text = re.replace_all(&text, replacement.as_str()).to_string();
+    }
+    text
+}
+
+pub fn redact_alarm(alarm: &ParsedData) -> ParsedData {
+    let mut alarm = alarm.clone();
+    alarm.einsatzstichwort = redact(&alarm.einsatzstichwort);
+    alarm.objektname = redact(&alarm.objektname);
+    alarm.zusatzinfo = redact(&alarm.zusatzinfo);
+    alarm
+}
+
+// Timestamp of a log line, "<ts>\t..." or "OK - <ts>\t..."
+fn line_time(line: &str) -> Option<DateTime<Utc>> {
+    let head = line.split('\t').next()?;
+    let ts = head.rsplit(" - ").next()?;
+    DateTime::parse_from_rfc3339(ts.trim()).ok().map(|d| d.with_timezone(&Utc))
+}
+
+// Held while a log line is appended and while the retention rewrites a log, so no line written in between is lost
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn append_log(path: &str, line: &str) -> std::io::Result<()> {
+    let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+fn prune_log(path: String, cutoff: DateTime<Utc>) {
+    let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    let kept: Vec<&str> = content
+        .lines()
+        .filter(|l| line_time(l).map(|t| t >= cutoff).unwrap_or(true))
+        .collect();
+    let removed = content.lines().count() - kept.len();
+    if removed == 0 {
+        return;
+    }
+    let tmp = format!("{}.tmp", path);
+    let mut out = kept.join("\n");
+    out.push('\n');
+    match std::fs::write(&tmp, out).and_then(|_| std::fs::rename(&tmp, &path)) {
+        Ok(()) => info!("Retention: removed {} lines from {}", removed, path),
+        Err(e) => error!("Retention: could not prune {}: {}", path, e),
+    }
+}
+
+pub fn prune(days: u32) {
+    let cutoff = Utc::now() - Duration::days(days as i64);
+    crate::history::prune(cutoff.timestamp());
+    prune_log(crate::paths::received_log(), cutoff);
+    prune_log(crate::paths::submitted_log(), cutoff);
+    crate::samples::prune(cutoff);
+}
+
+// Delete history, log entries and sample alarms older than the retention period, checked hourly
+pub fn start_retention(days: u32) {
+    info!("Retention: keeping alarm data for {} days", days);
+    std::thread::spawn(move || loop {
+        prune(days);
+        std::thread::sleep(std::time::Duration::from_secs(3600));
+    });
+}
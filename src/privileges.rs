@@ -0,0 +1,22 @@
+// Ports below 1024 need root only for bind(); afterwards the service continues as the given user.
+// Alternatively run as that user from the start and grant CAP_NET_BIND_SERVICE (see fireplan_alarm_divera.service).
+#[cfg(unix)]
+pub fn drop_to(name: &str) -> anyhow::Result<()> {
+    use log::info;
+    use nix::unistd::{setgid, setgroups, setuid, User};
+
+    let user = User::from_name(name)?.ok_or_else(|| anyhow::anyhow!("unknown user {}", name))?;
+    setgroups(&[user.gid])?;
+    setgid(user.gid)?;
+    setuid(user.uid)?;
+    if setuid(nix::unistd::Uid::from_raw(0)).is_ok() && !user.uid.is_root() {
+        anyhow::bail!("could regain root after switching user");
+    }
+    info!("Running as user {} (uid {}, gid {})", name, user.uid, user.gid);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn drop_to(_name: &str) -> anyhow::Result<()> {
+    anyhow::bail!("run_as_user is only supported on Unix")
+}
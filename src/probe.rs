@@ -0,0 +1,131 @@
+use crate::routing::Sink;
+use crate::{Event, Incoming, Route, SubmitPayload};
+use chrono::{DateTime, Datelike, Timelike};
+use chrono_tz::Tz;
+use log::{error, info};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Scheduled test alarm, e.g. the weekly siren probe on Saturday noon ("0 12 * * 6")
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ProbeAlarm {
+    name: String,
+    // "minute hour day-of-month month day-of-week" in the configured timezone, with *, lists, ranges and steps;
+    // day-of-week 0 or 7 is Sunday
+    cron: String,
+    // defaults to "Probealarm"
+    stichwort: Option<String>,
+    text: Option<String>,
+    // RIC texts, matched like the Einsatzmittel of a Leitstelle text
+    rics: Vec<String>,
+    // defaults to default_standort
+    standort: Option<String>,
+    // only these sinks get the alarm, all if omitted
+    sinks: Option<Vec<Sink>>,
+}
+
+// Marks an alarm as drill in the history; routing keeps it away from sinks it is not meant for
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Drill {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sinks: Option<Vec<Sink>>,
+}
+
+impl Drill {
+    pub fn allows(&self, sink: Sink) -> bool {
+        self.sinks.as_ref().is_none_or(|s| s.contains(&sink))
+    }
+}
+
+// One cron field: "*", "*/15", "1-5", "0,30" or "8-18/2"
+fn field_matches(field: &str, value: u32, min: u32, max: u32) -> Option<bool> {
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (a.parse().ok()?, b.parse().ok()?),
+                None => {
+                    let v: u32 = r.parse().ok()?;
+                    (v, if part.contains('/') { max } else { v })
+                }
+            },
+        };
+        if value >= from && value <= to && (value - from).is_multiple_of(step) {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+// None if the expression is invalid
+fn cron_matches(cron: &str, time: &DateTime<Tz>) -> Option<bool> {
+    let fields: Vec<&str> = cron.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields.as_slice() else {
+        return None;
+    };
+    let sunday = time.weekday().num_days_from_sunday();
+    let weekday_matches = field_matches(weekday, sunday, 0, 7)? || (sunday == 0 && field_matches(weekday, 7, 0, 7)?);
+    Some(
+        field_matches(minute, time.minute(), 0, 59)?
+            && field_matches(hour, time.hour(), 0, 23)?
+            && field_matches(day, time.day(), 1, 31)?
+            && field_matches(month, time.month(), 1, 12)?
+            && weekday_matches,
+    )
+}
+
+fn payload(probe: &ProbeAlarm, einsatzmittel_marker: &str, time: &DateTime<Tz>) -> SubmitPayload {
+    let now = time.timestamp();
+    SubmitPayload {
+        schema_version: crate::payload::SCHEMA_VERSION,
+        foreign_id: format!("PROBE-{}", time.format("%Y%m%d%H%M")),
+        title: probe.stichwort.clone().unwrap_or_else(|| "Probealarm".to_string()),
+        text: format!("{}\n{} {}", probe.text.as_deref().unwrap_or_default(), einsatzmittel_marker, probe.rics.join(", ")),
+        ts_create: now,
+        ts_update: now,
+        ..Default::default()
+    }
+}
+
+fn inject(probe: &ProbeAlarm, einsatzmittel_marker: &str, time: &DateTime<Tz>) {
+    let route = match &probe.standort {
+        Some(standort) => Route { standort: standort.clone(), profile: None },
+        None => Route::default(),
+    };
+    let drill = Drill { name: probe.name.clone(), sinks: probe.sinks.clone() };
+    let incoming = Incoming::new(payload(probe, einsatzmittel_marker, time), route).with_drill(drill);
+    info!("Probe alarm {}: injecting submission {}", probe.name, incoming.id());
+    if let Err(e) = crate::send_event(Event::Submit(incoming)) {
+        error!("Probe alarm {}: could not inject: {}", probe.name, e);
+    }
+}
+
+// Checks the schedules once a minute; a probe missed while the service was down is not caught up
+pub fn start(probes: Vec<ProbeAlarm>, einsatzmittel_marker: String, tz: Tz) {
+    for probe in &probes {
+        if cron_matches(&probe.cron, &chrono::Utc::now().with_timezone(&tz)).is_none() {
+            error!("Probe alarm {}: invalid cron expression {}, never runs", probe.name, probe.cron);
+        }
+    }
+    std::thread::spawn(move || {
+        info!("Probe alarm scheduler started with {} schedules", probes.len());
+        let mut last_minute = None;
+        loop {
+            let now = chrono::Utc::now().with_timezone(&tz);
+            let minute = now.timestamp() / 60;
+            if last_minute != Some(minute) {
+                last_minute = Some(minute);
+                for probe in probes.iter().filter(|p| cron_matches(&p.cron, &now) == Some(true)) {
+                    inject(probe, &einsatzmittel_marker, &now);
+                }
+            }
+            std::thread::sleep(Duration::from_secs(60 - now.second().min(59) as u64));
+        }
+    });
+}
@@ -0,0 +1,44 @@
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use log::error;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn correlation_id() -> String {
+    format!(
+        "{:x}-{:04x}",
+        chrono::Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff
+    )
+}
+
+// RFC 7807 application/problem+json error response, kind is a short slug like "unauthorized"
+pub fn problem(status: StatusCode, kind: &str, detail: impl Into<String>) -> HttpResponse {
+    problem_with(status, kind, detail, serde_json::json!({}))
+}
+
+// Same as problem, with additional extension members merged into the body
+pub fn problem_with(status: StatusCode, kind: &str, detail: impl Into<String>, extensions: serde_json::Value) -> HttpResponse {
+    let id = correlation_id();
+    let detail = detail.into();
+    error!("[{}] {} - {}", id, status, detail);
+
+    let mut body = serde_json::json!({
+        "type": format!("/problems/{}", kind),
+        "title": status.canonical_reason().unwrap_or("Error"),
+        "status": status.as_u16(),
+        "detail": detail,
+        "instance": format!("urn:correlation:{}", id),
+    });
+    if let (Some(body), Some(ext)) = (body.as_object_mut(), extensions.as_object()) {
+        for (k, v) in ext {
+            body.insert(k.clone(), v.clone());
+        }
+    }
+
+    HttpResponse::build(status)
+        .content_type("application/problem+json")
+        .insert_header(("X-Correlation-Id", id))
+        .body(body.to_string())
+}
@@ -0,0 +1,207 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{get, App, HttpResponse, HttpServer, Responder};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+const PREFIX: &str = "fireplan_alarm_divera";
+
+// upper bounds in seconds of the response time histogram
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Internal-only listener for the Prometheus export; with it configured the public port has no /metrics page
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct MetricsListenerConfig {
+    // interface to bind, defaults to "127.0.0.1"
+    host: Option<String>,
+    pub port: u16,
+}
+
+// metric name -> (help, labels -> value)
+type Counters = BTreeMap<&'static str, (&'static str, BTreeMap<String, f64>)>;
+
+static COUNTERS: Lazy<Mutex<Counters>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+#[derive(Default)]
+struct Histogram {
+    // per bucket of BUCKETS, not cumulative
+    counts: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+// metric name -> (help, labels -> histogram)
+type Histograms = BTreeMap<&'static str, (&'static str, BTreeMap<String, Histogram>)>;
+
+static HISTOGRAMS: Lazy<Mutex<Histograms>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+fn labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+// {a="1"} + le="0.5" -> {a="1",le="0.5"}
+fn with_label(labels: &str, name: &str, value: &str) -> String {
+    let pair = self::labels(&[(name, value)]);
+    match labels.strip_suffix('}') {
+        Some(inner) => format!("{},{}", inner, &pair[1..]),
+        None => pair,
+    }
+}
+
+// Count one, the counter is created on first use
+pub fn inc(name: &'static str, help: &'static str, label_pairs: &[(&str, &str)]) {
+    let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+    let (_, series) = counters.entry(name).or_insert((help, BTreeMap::new()));
+    *series.entry(labels(label_pairs)).or_insert(0.0) += 1.0;
+}
+
+pub fn observe(name: &'static str, help: &'static str, label_pairs: &[(&str, &str)], value: f64) {
+    let mut histograms = HISTOGRAMS.lock().unwrap_or_else(|e| e.into_inner());
+    let (_, series) = histograms.entry(name).or_insert((help, BTreeMap::new()));
+    let histogram = series.entry(labels(label_pairs)).or_default();
+    if let Some(bucket) = BUCKETS.iter().position(|le| value <= *le) {
+        histogram.counts[bucket] += 1;
+    }
+    histogram.sum += value;
+    histogram.count += 1;
+}
+
+// Series of a tenant carry its name, so one Prometheus job can scrape all departments
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, series: &[(String, f64)]) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    for (labels, value) in series {
+        match crate::tenants::current() {
+            Some(tenant) => {
+                // histogram lines come as "_bucket{...}"
+                let (suffix, labels) = labels.split_at(labels.find('{').unwrap_or(labels.len()));
+                let _ = writeln!(out, "{}_{}{}{} {}", PREFIX, name, suffix, with_label(labels, "tenant", tenant), value);
+            }
+            None => {
+                let _ = writeln!(out, "{}_{}{} {}", PREFIX, name, labels, value);
+            }
+        }
+    }
+}
+
+// Text exposition format
+pub fn render() -> String {
+    let mut out = String::new();
+    metric(
+        &mut out,
+        "build_info",
+        "gauge",
+        "Version of the running binary",
+        &[(labels(&[("version", env!("CARGO_PKG_VERSION"))]), 1.0)],
+    );
+    let events: Vec<(String, f64)> = crate::stats::pipeline_counts_by_standort()
+        .into_iter()
+        .map(|((event, standort), n)| (labels(&[("event", event), ("standort", &standort)]), n as f64))
+        .collect();
+    metric(&mut out, "events_total", "counter", "Pipeline events by type and Standort", &events);
+    for (name, (help, series)) in COUNTERS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let series: Vec<(String, f64)> = series.iter().map(|(l, v)| (l.clone(), *v)).collect();
+        metric(&mut out, name, "counter", help, &series);
+    }
+    for (name, (help, series)) in HISTOGRAMS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let mut lines = vec![];
+        for (labels, histogram) in series {
+            let mut cumulative = 0;
+            for (le, n) in BUCKETS.iter().zip(histogram.counts) {
+                cumulative += n;
+                lines.push((format!("_bucket{}", with_label(labels, "le", &le.to_string())), cumulative as f64));
+            }
+            lines.push((format!("_bucket{}", with_label(labels, "le", "+Inf")), histogram.count as f64));
+            lines.push((format!("_sum{}", labels), histogram.sum));
+            lines.push((format!("_count{}", labels), histogram.count as f64));
+        }
+        metric(&mut out, name, "histogram", help, &lines);
+    }
+
+    let queues = crate::health::queue_levels();
+    let entries: Vec<(String, f64)> = queues.iter().map(|(q, len, _)| (labels(&[("queue", q)]), *len as f64)).collect();
+    metric(&mut out, "queue_entries", "gauge", "Entries waiting in the pipeline queues", &entries);
+    let capacity: Vec<(String, f64)> = queues.iter().map(|(q, _, cap)| (labels(&[("queue", q)]), *cap as f64)).collect();
+    metric(&mut out, "queue_capacity", "gauge", "Capacity of the pipeline queues", &capacity);
+
+    let report = crate::health::report();
+    let ready = if report.status == crate::health::Readiness::Ready { 1.0 } else { 0.0 };
+    metric(&mut out, "ready", "gauge", "1 when the service is READY", &[(String::new(), ready)]);
+    let fireplan_ok = if report.components.get("fireplan").map(|s| s == "ok").unwrap_or(false) { 1.0 } else { 0.0 };
+    metric(&mut out, "fireplan_ok", "gauge", "1 when the last Fireplan call succeeded", &[(String::new(), fireplan_ok)]);
+    out
+}
+
+// Middleware on the public server: requests and response times per route pattern, so ids in paths
+// do not create new series and probing unknown paths ends up in "unmatched"
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let endpoint = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let response = next.call(req).await;
+    let status = match &response {
+        Ok(r) => r.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    let labels = [("method", method.as_str()), ("endpoint", endpoint.as_str())];
+    inc(
+        "http_requests_total",
+        "HTTP requests by method, endpoint and status",
+        &[labels[0], labels[1], ("status", status.as_str())],
+    );
+    observe(
+        "http_request_duration_seconds",
+        "HTTP response times by method and endpoint",
+        &labels,
+        started.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+#[get("/metrics")]
+async fn metrics() -> impl Responder {
+    match actix_web::web::block(render).await {
+        Ok(text) => HttpResponse::Ok().content_type("text/plain; version=0.0.4; charset=utf-8").body(text),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Plain HTTP without authentication, meant for an interface only the monitoring host reaches
+pub fn start_listener(config: &MetricsListenerConfig) -> std::io::Result<JoinHandle<()>> {
+    let host = config.host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
+    let listener = std::net::TcpListener::bind((host.as_str(), config.port))?;
+    info!("Prometheus metrics on http://{}:{}/metrics", host, config.port);
+    Ok(std::thread::spawn(move || {
+        let sys = actix_web::rt::System::new();
+        sys.block_on(async move {
+            let server = match HttpServer::new(|| App::new().service(metrics)).workers(1).listen(listener) {
+                Ok(server) => server.run(),
+                Err(e) => {
+                    error!("Metrics listener failed: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = server.await {
+                error!("Metrics listener error: {e}");
+            }
+        });
+    }))
+}
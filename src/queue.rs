@@ -0,0 +1,92 @@
+use log::error;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // the sending stage waits until there is room again
+    #[default]
+    Block,
+    // the oldest queued item is discarded and handed to the drop handler
+    DropOldest,
+}
+
+// Bounded queue between two pipeline stages, so a stuck stage cannot grow memory without limit
+pub struct BoundedQueue<T> {
+    name: &'static str,
+    capacity: usize,
+    policy: OverflowPolicy,
+    items: Mutex<VecDeque<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    on_drop: Box<dyn Fn(T) + Send + Sync>,
+}
+
+impl<T> BoundedQueue<T> {
+    pub fn new(
+        name: &'static str,
+        capacity: usize,
+        policy: OverflowPolicy,
+        on_drop: impl Fn(T) + Send + Sync + 'static,
+    ) -> BoundedQueue<T> {
+        BoundedQueue {
+            name,
+            capacity: capacity.max(1),
+            policy,
+            items: Mutex::new(VecDeque::new()),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            on_drop: Box::new(on_drop),
+        }
+    }
+
+    pub fn push(&self, item: T) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let mut dropped = None;
+        while items.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => items = self.not_full.wait(items).unwrap_or_else(|e| e.into_inner()),
+                OverflowPolicy::DropOldest => {
+                    dropped = items.pop_front();
+                    break;
+                }
+            }
+        }
+        items.push_back(item);
+        drop(items);
+        self.not_empty.notify_one();
+
+        if let Some(dropped) = dropped {
+            error!("Queue {} full ({} items), dropped the oldest entry", self.name, self.capacity);
+            (self.on_drop)(dropped);
+        }
+    }
+
+    // Ignores the capacity, for control messages that must not wait or get lost
+    pub fn push_front(&self, item: T) {
+        self.items.lock().unwrap_or_else(|e| e.into_inner()).push_front(item);
+        self.not_empty.notify_one();
+    }
+
+    // (queued items, capacity)
+    pub fn level(&self) -> (usize, usize) {
+        (self.items.lock().unwrap_or_else(|e| e.into_inner()).len(), self.capacity)
+    }
+
+    pub fn pop(&self) -> T {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(item) = items.pop_front() {
+                drop(items);
+                self.not_full.notify_one();
+                return item;
+            }
+            items = self.not_empty.wait(items).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
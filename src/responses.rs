@@ -0,0 +1,121 @@
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_URL: &str = "https://app.divera247.com/api/v2/alarms";
+
+// Rückmeldungen ("kommen" / "kommen nicht") of the active DIVERA alarm, for the headcount on the monitor page.
+// Status ids are those of the unit's DIVERA status configuration.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ResponsesConfig {
+    accesskey: String,
+    coming_status_ids: Vec<u64>,
+    not_coming_status_ids: Option<Vec<u64>>,
+    interval_secs: Option<u64>,
+    url: Option<String>,
+}
+
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct Headcount {
+    pub alarm_id: u64,
+    pub foreign_id: String,
+    pub title: String,
+    pub coming: usize,
+    pub not_coming: usize,
+    // answered with another status
+    pub other: usize,
+    pub addressed: usize,
+}
+
+static CURRENT: Lazy<Mutex<Option<Headcount>>> = Lazy::new(|| Mutex::new(None));
+
+// Headcount of the newest open alarm, None when there is none or responses are not configured
+pub fn current() -> Option<Headcount> {
+    CURRENT.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// ucr_answered is { status_id: { user_cluster_relation_id: { ts, note } } }
+fn headcount(config: &ResponsesConfig, alarm: &Value) -> Headcount {
+    let not_coming_ids = config.not_coming_status_ids.clone().unwrap_or_default();
+    let (mut coming, mut not_coming, mut other) = (0, 0, 0);
+    for (status, answers) in alarm["ucr_answered"].as_object().into_iter().flatten() {
+        let count = answers.as_object().map(|a| a.len()).unwrap_or(0);
+        match status.parse::<u64>() {
+            Ok(id) if config.coming_status_ids.contains(&id) => coming += count,
+            Ok(id) if not_coming_ids.contains(&id) => not_coming += count,
+            _ => other += count,
+        }
+    }
+    Headcount {
+        alarm_id: alarm["id"].as_u64().unwrap_or(0),
+        foreign_id: alarm["foreign_id"].as_str().unwrap_or_default().to_string(),
+        title: alarm["title"].as_str().unwrap_or_default().to_string(),
+        coming,
+        not_coming,
+        other,
+        addressed: alarm["ucr_addressed"].as_array().map(|a| a.len()).unwrap_or(0),
+    }
+}
+
+fn fetch(client: &Client, url: &str, config: &ResponsesConfig) -> anyhow::Result<Option<Headcount>> {
+    let request_url = format!("{}?accesskey={}", url, config.accesskey);
+    let r = client.get(&request_url).send()?;
+    let status = r.status();
+    let body = r.text()?;
+    crate::trace::exchange("DIVERA", "GET", &request_url, "", status.as_str(), &body);
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("DIVERA answered {}", status));
+    }
+    let response: Value = serde_json::from_str(&body)?;
+
+    // items by id, sorting lists the ids newest first
+    let data = &response["data"];
+    let newest_open = data["sorting"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|id| data["items"].get(as_key(id)))
+        .find(|alarm| !alarm["closed"].as_bool().unwrap_or(false));
+    Ok(newest_open.map(|alarm| headcount(config, alarm)))
+}
+
+fn as_key(id: &Value) -> String {
+    match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn start_poller(config: ResponsesConfig) {
+    std::thread::spawn(move || {
+        let url = config.url.clone().unwrap_or_else(|| DEFAULT_URL.to_string());
+        let interval = Duration::from_secs(config.interval_secs.unwrap_or(15).max(5));
+        let client = match Client::builder().timeout(Duration::from_secs(20)).build() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("DIVERA responses: could not build HTTP client: {}", e);
+                return;
+            }
+        };
+        info!("DIVERA responses poller started, polling {} every {:?}", url, interval);
+
+        loop {
+            match fetch(&client, &url, &config) {
+                Ok(headcount) => {
+                    let mut current = CURRENT.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(h) = headcount.as_ref().filter(|h| current.as_ref() != Some(*h)) {
+                        info!("DIVERA responses for alarm {}: {} coming, {} not coming", h.alarm_id, h.coming, h.not_coming);
+                    }
+                    *current = headcount;
+                }
+                Err(e) => warn!("DIVERA responses: request failed: {}", e),
+            }
+            std::thread::sleep(interval);
+        }
+    });
+}
@@ -0,0 +1,58 @@
+use crate::Ric;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Named set of RICs, e.g. "Zug 1", referenced by parser RICs ({ text = "Zug 1", group = "Zug 1" })
+// and AAO rules (groups = ["Zug 1"]) instead of repeating the members in every profile
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct RicGroup {
+    name: String,
+    rics: Vec<Ric>,
+}
+
+impl RicGroup {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rics(&self) -> &[Ric] {
+        &self.rics
+    }
+}
+
+static GROUPS: OnceCell<HashMap<String, Vec<Ric>>> = OnceCell::new();
+
+pub fn init(groups: &[RicGroup]) {
+    let mut map = HashMap::new();
+    for group in groups {
+        if group.rics.iter().any(|r| r.group.is_some()) {
+            error!("RIC group {}: members must not be groups themselves, ignoring them", group.name);
+        }
+        let members: Vec<Ric> = group.rics.iter().filter(|r| r.group.is_none()).cloned().collect();
+        info!("RIC group {} with {} RICs", group.name, members.len());
+        map.insert(group.name.clone(), members);
+    }
+    let _ = GROUPS.set(map);
+}
+
+// Members of the named group, empty if it is unknown
+pub fn members(name: &str) -> Vec<Ric> {
+    match GROUPS.get().and_then(|groups| groups.get(name)) {
+        Some(members) => members.clone(),
+        None => {
+            error!("Unknown RIC group {}", name);
+            vec![]
+        }
+    }
+}
+
+// The RICs to alarm for a configured RIC: the group members for a group reference, the RIC itself otherwise
+pub fn expand(ric: &Ric) -> Vec<Ric> {
+    match &ric.group {
+        Some(name) => members(name),
+        None => vec![ric.clone()],
+    }
+}
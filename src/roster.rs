@@ -0,0 +1,244 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DIVERA_URL: &str = "https://app.divera247.com/api/v2/pull/all";
+
+// Duty roster: members available per duty group, for AAO rules that depend on the staffing of a group.
+// The counts of all configured sources are added up.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct RosterConfig {
+    // CSV file "group;member;from;to" with local times "YYYY-MM-DD HH:MM", empty from/to is open
+    csv: Option<String>,
+    // iCalendar feed (e.g. a CalDAV calendar export), every current event counts for the group in its SUMMARY,
+    // with one member per ATTENDEE or one if there are none
+    calendar: Option<RosterCalendar>,
+    divera: Option<RosterDivera>,
+    // defaults to 60
+    refresh_secs: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct RosterCalendar {
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+// DIVERA groups, members count while their status is one of available_status_ids
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct RosterDivera {
+    accesskey: String,
+    available_status_ids: Vec<u64>,
+    url: Option<String>,
+}
+
+// Condition of an AAO rule on a duty group, unknown availability never holds
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct DutyCondition {
+    group: String,
+    // holds with at least this many available
+    min_available: Option<usize>,
+    // holds with fewer than this many available
+    below: Option<usize>,
+}
+
+// duty group -> members available, None until the first refresh
+static AVAILABLE: Lazy<Mutex<Option<BTreeMap<String, usize>>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn available(group: &str) -> Option<usize> {
+    let available = AVAILABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let available = available.as_ref()?;
+    available
+        .iter()
+        .find(|(g, _)| g.eq_ignore_ascii_case(group.trim()))
+        .map(|(_, count)| *count)
+        // a known roster without entries for the group means nobody is on duty
+        .or(Some(0))
+}
+
+pub fn snapshot() -> Option<BTreeMap<String, usize>> {
+    AVAILABLE.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+impl DutyCondition {
+    pub fn holds(&self) -> bool {
+        let Some(count) = available(&self.group) else {
+            warn!("Duty roster: availability of {} unknown, condition does not hold", self.group);
+            return false;
+        };
+        self.min_available.map(|min| count >= min).unwrap_or(true) && self.below.map(|below| count < below).unwrap_or(true)
+    }
+}
+
+fn local(text: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(text.trim(), "%Y-%m-%d %H:%M").ok()?;
+    tz.from_local_datetime(&naive).earliest().map(|d| d.with_timezone(&Utc))
+}
+
+// Members on duty now per group from the CSV roster
+fn from_csv(path: &str, now: DateTime<Utc>, tz: Tz) -> anyhow::Result<BTreeMap<String, usize>> {
+    let mut reader = csv::ReaderBuilder::new().delimiter(b';').flexible(true).from_path(path)?;
+    let mut members: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default().trim();
+        if field(0).is_empty() {
+            continue;
+        }
+        let bound = |text: &str| if text.is_empty() { Ok(None) } else { local(text, tz).map(Some).ok_or(()) };
+        let (Ok(from), Ok(to)) = (bound(field(2)), bound(field(3))) else {
+            warn!("Duty roster: invalid time in {} for {} / {}", path, field(0), field(1));
+            continue;
+        };
+        let on_duty = from.map(|f| now >= f).unwrap_or(true) && to.map(|t| now < t).unwrap_or(true);
+        if on_duty {
+            members.entry(field(0).to_string()).or_default().insert(field(1).to_string());
+        }
+    }
+    Ok(members.into_iter().map(|(group, m)| (group, m.len())).collect())
+}
+
+// DTSTART/DTEND value: UTC ("...Z"), local time of the roster timezone, or a date (whole day)
+fn ics_time(property: &str, value: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if property.contains("VALUE=DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return tz.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|d| d.with_timezone(&Utc));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok().map(|n| n.and_utc());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    tz.from_local_datetime(&naive).earliest().map(|d| d.with_timezone(&Utc))
+}
+
+fn from_ics(body: &str, now: DateTime<Utc>, tz: Tz) -> BTreeMap<String, usize> {
+    // unfold continuation lines (RFC 5545 3.1)
+    let unfolded = body.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let (mut summary, mut start, mut end, mut attendees) = (String::new(), None, None, 0);
+    for line in unfolded.lines() {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = property.split(';').next().unwrap_or_default();
+        match name {
+            "BEGIN" if value == "VEVENT" => (summary, start, end, attendees) = (String::new(), None, None, 0),
+            "SUMMARY" => summary = value.replace("\\,", ",").replace("\\;", ";").trim().to_string(),
+            "DTSTART" => start = ics_time(property, value, tz),
+            "DTEND" => end = ics_time(property, value, tz),
+            "ATTENDEE" => attendees += 1,
+            "END" if value == "VEVENT" => {
+                let current = start.is_some_and(|s| now >= s) && end.map(|e| now < e).unwrap_or(true);
+                if current && !summary.is_empty() {
+                    *counts.entry(summary.clone()).or_default() += attendees.max(1);
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+fn fetch_calendar(client: &Client, calendar: &RosterCalendar, now: DateTime<Utc>, tz: Tz) -> anyhow::Result<BTreeMap<String, usize>> {
+    let mut request = client.get(&calendar.url);
+    if let Some(user) = &calendar.user {
+        request = request.basic_auth(user, calendar.password.as_ref());
+    }
+    let r = request.send()?;
+    anyhow::ensure!(r.status().is_success(), "calendar answered {}", r.status());
+    Ok(from_ics(&r.text()?, now, tz))
+}
+
+// pull/all lists the groups in cluster.group { id: { name } } and the members in
+// cluster.consumer { ucr_id: { groups: [group ids] } }, their status in monitor.ucr { ucr_id: { status_id } }
+fn from_divera(divera: &RosterDivera, data: &Value) -> BTreeMap<String, usize> {
+    let cluster = &data["data"]["cluster"];
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for (group_id, group) in cluster["group"].as_object().into_iter().flatten() {
+        counts.insert(group["name"].as_str().unwrap_or(group_id).to_string(), 0);
+    }
+    for (ucr, consumer) in cluster["consumer"].as_object().into_iter().flatten() {
+        let status = data["data"]["monitor"]["ucr"][ucr]["status_id"].as_u64();
+        if !status.is_some_and(|s| divera.available_status_ids.contains(&s)) {
+            continue;
+        }
+        for group_id in consumer["groups"].as_array().into_iter().flatten() {
+            let key = match group_id {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            let name = cluster["group"][&key]["name"].as_str().unwrap_or(&key).to_string();
+            *counts.entry(name).or_default() += 1;
+        }
+    }
+    counts
+}
+
+fn fetch_divera(client: &Client, divera: &RosterDivera) -> anyhow::Result<BTreeMap<String, usize>> {
+    let url = format!("{}?accesskey={}", divera.url.as_deref().unwrap_or(DIVERA_URL), divera.accesskey);
+    let r = client.get(&url).send()?;
+    anyhow::ensure!(r.status().is_success(), "DIVERA answered {}", r.status());
+    Ok(from_divera(divera, &r.json()?))
+}
+
+// All sources added up; a failing source keeps the roster unknown rather than reporting too few members
+fn refresh(client: &Client, config: &RosterConfig, tz: Tz) -> anyhow::Result<BTreeMap<String, usize>> {
+    let now = Utc::now();
+    let mut total: BTreeMap<String, usize> = BTreeMap::new();
+    let mut add = |counts: BTreeMap<String, usize>| {
+        for (group, count) in counts {
+            *total.entry(group).or_default() += count;
+        }
+    };
+    if let Some(path) = &config.csv {
+        add(from_csv(path, now, tz).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?);
+    }
+    if let Some(calendar) = &config.calendar {
+        add(fetch_calendar(client, calendar, now, tz)?);
+    }
+    if let Some(divera) = &config.divera {
+        add(fetch_divera(client, divera)?);
+    }
+    Ok(total)
+}
+
+pub fn start(config: RosterConfig, tz: Tz) {
+    std::thread::spawn(move || {
+        let interval = Duration::from_secs(config.refresh_secs.unwrap_or(60).max(10));
+        let client = match Client::builder().timeout(Duration::from_secs(20)).build() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Duty roster: could not build HTTP client: {}", e);
+                return;
+            }
+        };
+        info!("Duty roster started, refreshing every {:?}", interval);
+
+        loop {
+            match refresh(&client, &config, tz) {
+                Ok(counts) => {
+                    let mut available = AVAILABLE.lock().unwrap_or_else(|e| e.into_inner());
+                    if available.as_ref() != Some(&counts) {
+                        info!("Duty roster: {:?}", counts);
+                    }
+                    *available = Some(counts);
+                }
+                Err(e) => {
+                    error!("Duty roster: refresh failed, availability unknown: {}", e);
+                    *AVAILABLE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                }
+            }
+            std::thread::sleep(interval);
+        }
+    });
+}
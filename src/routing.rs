@@ -0,0 +1,89 @@
+use crate::{ParsedData, Ric};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+// Output sinks alarms can be routed to
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Sink {
+    Fireplan,
+    #[serde(rename = "feuersoftware_connect")]
+    FeuerSoftwareConnect,
+    // LED ticker / e-paper displays
+    Ticker,
+    // gong played on the host or a network speaker
+    Sound,
+}
+
+// Routing rule: the sink receives alarms matching all given criteria.
+// A sink with rules only gets alarms matching one of them, sinks without rules get every alarm.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct SinkRoute {
+    sink: Sink,
+    // Einsatzstichwort prefixes, e.g. ["RD", "FR"]
+    keywords: Option<Vec<String>>,
+    // RIC numbers or texts; the sink then only gets these RICs of the alarm
+    rics: Option<Vec<String>>,
+    standorte: Option<Vec<String>>,
+}
+
+impl Sink {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sink::Fireplan => "fireplan",
+            Sink::FeuerSoftwareConnect => "feuersoftware_connect",
+            Sink::Ticker => "ticker",
+            Sink::Sound => "sound",
+        }
+    }
+}
+
+impl SinkRoute {
+    // RICs of the alarm this rule passes to its sink, None if it does not match. Keywords and Standorte
+    // decide on their own, so a rule without RIC list also matches an alarm without RICs
+    fn matching_rics(&self, data: &ParsedData) -> Option<Vec<Ric>> {
+        if let Some(standorte) = &self.standorte {
+            if !standorte.iter().any(|s| s == &data.standort) {
+                return None;
+            }
+        }
+        if let Some(keywords) = &self.keywords {
+            let stichwort = data.einsatzstichwort.trim().to_uppercase();
+            if !keywords.iter().any(|k| stichwort.starts_with(&k.trim().to_uppercase())) {
+                return None;
+            }
+        }
+        let Some(wanted) = &self.rics else {
+            return Some(data.rics.clone());
+        };
+        let rics: Vec<Ric> = data
+            .rics
+            .iter()
+            .filter(|r| wanted.iter().any(|w| w == &r.ric || w.eq_ignore_ascii_case(&r.text)))
+            .cloned()
+            .collect();
+        (!rics.is_empty()).then_some(rics)
+    }
+}
+
+// RICs of the alarm the sink should get, None if the alarm is not routed to it
+pub fn rics_for(sink: Sink, routes: &[SinkRoute], data: &ParsedData) -> Option<Vec<Ric>> {
+    if data.drill.as_ref().is_some_and(|d| !d.allows(sink)) {
+        return None;
+    }
+    let rules: Vec<&SinkRoute> = routes.iter().filter(|r| r.sink == sink).collect();
+    if rules.is_empty() {
+        return Some(data.rics.clone());
+    }
+    let matched: Vec<Vec<Ric>> = rules.iter().filter_map(|r| r.matching_rics(data)).collect();
+    if matched.is_empty() {
+        return None;
+    }
+    let mut rics: Vec<Ric> = vec![];
+    for ric in matched.into_iter().flatten() {
+        if !rics.iter().any(|r| r.ric == ric.ric && r.subric == ric.subric) {
+            rics.push(ric);
+        }
+    }
+    Some(rics)
+}
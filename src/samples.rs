@@ -0,0 +1,82 @@
+use crate::{Incoming, SubmitPayload};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// how many recent alarms are kept
+const KEEP: usize = 20;
+
+// Recent incoming alarm as it arrived with personal data redacted, so config changes can be tried on real
+// Leitstelle texts before applying them
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Sample {
+    pub received: String,
+    pub payload: SubmitPayload,
+    // parser profile of the token it came with
+    pub profile: Option<String>,
+}
+
+static SAMPLES: Lazy<Mutex<Vec<Sample>>> = Lazy::new(|| Mutex::new(vec![]));
+// held while the file is written, so an older list never replaces a newer one
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn load() {
+    let file = crate::paths::samples_file();
+    let samples: Vec<Sample> = match std::fs::read(&file) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            warn!("Could not read sample alarms {}: {}, starting empty", file, e);
+            vec![]
+        }),
+        Err(_) => vec![],
+    };
+    *SAMPLES.lock().unwrap_or_else(|e| e.into_inner()) = samples;
+}
+
+fn save() {
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let samples = list();
+    let file = crate::paths::samples_file();
+    let tmp = format!("{}.tmp", file);
+    let result = serde_json::to_vec(&samples)
+        .map_err(std::io::Error::other)
+        .and_then(|content| std::fs::write(&tmp, content))
+        .and_then(|_| std::fs::rename(&tmp, &file));
+    if let Err(e) = result {
+        error!("Could not save sample alarms {}: {}", file, e);
+    }
+}
+
+// Called on the router loop, the file is written on a thread
+pub fn record(incoming: &Incoming) {
+    let mut payload = incoming.payload.clone();
+    payload.title = crate::privacy::redact(&payload.title);
+    payload.text = crate::privacy::redact(&payload.text);
+    payload.address = crate::privacy::redact(&payload.address);
+    {
+        let mut samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+        samples.push(Sample { received: crate::localtime::now(), payload, profile: incoming.route.profile.clone() });
+        let excess = samples.len().saturating_sub(KEEP);
+        samples.drain(..excess);
+    }
+    std::thread::spawn(save);
+}
+
+// Drop the samples received before the retention cutoff
+pub fn prune(cutoff: DateTime<Utc>) {
+    let removed = {
+        let mut samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+        let before = samples.len();
+        samples.retain(|s| DateTime::parse_from_rfc3339(&s.received).map(|t| t >= cutoff).unwrap_or(true));
+        before - samples.len()
+    };
+    if removed > 0 {
+        save();
+        info!("Retention: removed {} sample alarms", removed);
+    }
+}
+
+pub fn list() -> Vec<Sample> {
+    SAMPLES.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
@@ -0,0 +1,128 @@
+use crate::error::ConfigError;
+use crate::{Configuration, Ric};
+use regex::Regex;
+use serde_derive::Serialize;
+use serde_json::Value;
+
+// Outcome of the dry validation of an uploaded config, nothing is applied
+#[derive(Serialize, Debug, Default)]
+pub struct Validation {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    // keys the service ignores, usually typos
+    pub warnings: Vec<String>,
+}
+
+// JSON Schema of the config file, for external editors; secrets and secrets_key_file are not part of it
+pub fn schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Configuration)).unwrap_or_default()
+}
+
+fn check_regex(errors: &mut Vec<String>, name: &str, pattern: &str) {
+    if let Err(e) = Regex::new(pattern) {
+        errors.push(format!("{} is not a proper regular expression: {}", name, e));
+    }
+}
+
+fn check_timezone(errors: &mut Vec<String>, name: &str, value: &Option<String>) {
+    if let Some(tz) = value {
+        if tz.parse::<chrono_tz::Tz>().is_err() {
+            errors.push(format!("{} {} is not a known timezone", name, tz));
+        }
+    }
+}
+
+// A RIC entry alarms nothing without a ric number or a group reference
+pub fn check_ric(errors: &mut Vec<String>, context: &str, ric: &Ric) {
+    if ric.ric.trim().is_empty() && ric.group.is_none() {
+        errors.push(format!("{} RIC {} has neither ric nor group", context, ric.text));
+    }
+}
+
+// Mistakes the service refuses to start with, checked at every config load
+pub fn rejected(configuration: &Configuration) -> Vec<String> {
+    let mut errors = crate::aao::check(configuration.aao_rules.as_deref().unwrap_or_default());
+    for ric in &configuration.rics {
+        check_ric(&mut errors, "rics:", ric);
+    }
+    for profile in configuration.profiles.iter().flatten() {
+        for ric in profile.rics.iter().flatten() {
+            check_ric(&mut errors, &format!("profile {}:", profile.name), ric);
+        }
+    }
+    for group in configuration.ric_groups.iter().flatten() {
+        for ric in group.rics() {
+            check_ric(&mut errors, &format!("ric_groups {}:", group.name()), ric);
+        }
+    }
+    // the sources know the submit tokens, an admin token equal to one would hand them the admin endpoints
+    let approval_tokens = configuration.config_approval.iter().flat_map(|a| a.tokens());
+    for admin_token in configuration.admin_token.iter().chain(approval_tokens) {
+        let mut submit = configuration.submit_tokens.iter().flatten().map(|t| &t.token);
+        if admin_token == &configuration.auth_token || submit.any(|t| t == admin_token) {
+            errors.push("admin tokens must differ from auth_token and the submit tokens".to_string());
+            break;
+        }
+    }
+    errors
+}
+
+// What the service checks while running, so a config passing here does not log errors later
+fn check(configuration: &Configuration) -> Vec<String> {
+    let mut errors = vec![];
+    check_regex(&mut errors, "regex_ort", &configuration.regex_ort);
+    check_regex(&mut errors, "regex_ortsteil", &configuration.regex_ortsteil);
+    check_regex(&mut errors, "regex_objektname", &configuration.regex_objektname);
+    if let Some(pattern) = &configuration.regex_einsatzende {
+        check_regex(&mut errors, "regex_einsatzende", pattern);
+    }
+    for profile in configuration.profiles.iter().flatten() {
+        for (field, pattern) in [
+            ("regex_ort", &profile.regex_ort),
+            ("regex_ortsteil", &profile.regex_ortsteil),
+            ("regex_objektname", &profile.regex_objektname),
+        ] {
+            if let Some(pattern) = pattern {
+                check_regex(&mut errors, &format!("profile {} {}", profile.name, field), pattern);
+            }
+        }
+    }
+    check_timezone(&mut errors, "timezone", &configuration.timezone);
+    check_timezone(&mut errors, "display_timezone", &configuration.display_timezone);
+    for ric in configuration.rics.iter().filter(|r| r.group.is_none()) {
+        if ric.ric.is_empty() || !ric.ric.chars().all(|c| c.is_ascii_digit()) {
+            errors.push(format!("RIC {} needs a numeric ric", ric.text));
+        }
+    }
+    errors
+}
+
+// Parse and check a config file content without applying it
+pub fn validate(content: &str) -> Validation {
+    check_content(content).0
+}
+
+// The validation and, when it passes, the configuration
+pub fn check_content(content: &str) -> (Validation, Option<Configuration>) {
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(t) => t,
+        Err(e) => {
+            let errors = vec![format!("not valid TOML: {}", e.to_string().trim())];
+            return (Validation { errors, ..Default::default() }, None);
+        }
+    };
+    let known = schema()["properties"].as_object().cloned().unwrap_or_default();
+    let warnings = table
+        .keys()
+        .filter(|k| !known.contains_key(*k) && *k != "secrets" && *k != "secrets_key_file")
+        .map(|k| format!("unknown key {}", k))
+        .collect();
+    let (errors, configuration) = match crate::into_configuration(table, "uploaded config") {
+        Ok(configuration) => (check(&configuration), Some(configuration)),
+        Err(ConfigError::Invalid { source, .. }) => (vec![source.to_string().trim().to_string()], None),
+        Err(ConfigError::Rejected { reasons, .. }) => (reasons, None),
+        Err(e) => (vec![e.to_string()], None),
+    };
+    let valid = errors.is_empty();
+    (Validation { valid, errors, warnings }, configuration.filter(|_| valid))
+}
@@ -0,0 +1,96 @@
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use rhai::{Dynamic, Engine, Scope, AST};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Rhai script post-processing the parsed alarm before submission. The script sees the alarm as map
+// `alarm` and may change it, and the sink names in `sinks`, from which it may remove sinks to skip.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ScriptConfig {
+    name: String,
+    file: String,
+    // resource limits, defaults 100000 operations and 200 ms
+    max_operations: Option<u64>,
+    max_millis: Option<u64>,
+}
+
+struct Script {
+    config: ScriptConfig,
+    ast: AST,
+}
+
+static SCRIPTS: OnceCell<Vec<Script>> = OnceCell::new();
+
+fn engine(config: &ScriptConfig) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(config.max_operations.unwrap_or(100_000));
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(1000);
+    engine.set_max_map_size(1000);
+    engine.on_print(|s| info!("script: {}", s));
+    engine
+}
+
+// Compile all scripts at startup, a script that does not compile is left out
+pub fn init(configs: Vec<ScriptConfig>) {
+    let mut scripts = vec![];
+    for config in configs {
+        let engine = engine(&config);
+        match std::fs::read_to_string(&config.file).map_err(|e| e.to_string()).and_then(|source| {
+            engine.compile(source).map_err(|e| e.to_string())
+        }) {
+            Ok(ast) => {
+                info!("Loaded script {} from {}", config.name, config.file);
+                scripts.push(Script { config, ast });
+            }
+            Err(e) => error!("Script {} ({}) not loaded: {}", config.name, config.file, e),
+        }
+    }
+    let _ = SCRIPTS.set(scripts);
+}
+
+fn run(script: &Script, data: &ParsedData, sinks: Vec<String>) -> anyhow::Result<(ParsedData, Vec<String>)> {
+    let mut scope = Scope::new();
+    scope.push("alarm", rhai::serde::to_dynamic(data).map_err(|e| anyhow::anyhow!("{}", e))?);
+    scope.push("sinks", sinks.into_iter().map(Dynamic::from).collect::<rhai::Array>());
+
+    // a fresh engine per run for the time limit
+    let deadline = Instant::now() + Duration::from_millis(script.config.max_millis.unwrap_or(200));
+    let mut engine = engine(&script.config);
+    engine.on_progress(move |_| (Instant::now() > deadline).then(|| Dynamic::from("time limit exceeded")));
+    engine.run_ast_with_scope(&mut scope, &script.ast).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let alarm = scope.get_value::<Dynamic>("alarm").ok_or_else(|| anyhow::anyhow!("alarm removed"))?;
+    let mut modified: ParsedData = rhai::serde::from_dynamic(&alarm).map_err(|e| anyhow::anyhow!("invalid alarm: {}", e))?;
+    modified.submission_id = data.submission_id.clone();
+    let sinks = scope
+        .get_value::<rhai::Array>("sinks")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|s| s.into_string().ok())
+        .collect();
+    Ok((modified, sinks))
+}
+
+pub fn enabled() -> bool {
+    SCRIPTS.get().map(|s| !s.is_empty()).unwrap_or(false)
+}
+
+// Run all scripts in order; a failing script is skipped and leaves alarm and sinks as they were
+pub fn apply(mut data: ParsedData, mut sinks: Vec<String>) -> (ParsedData, Vec<String>) {
+    for script in SCRIPTS.get().into_iter().flatten() {
+        match run(script, &data, sinks.clone()) {
+            Ok((modified, remaining)) => {
+                data = modified;
+                sinks = remaining;
+            }
+            Err(e) => error!("Script {} failed: {}", script.config.name, e),
+        }
+    }
+    (data, sinks)
+}
@@ -0,0 +1,210 @@
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::SecretString;
+use anyhow::Context;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use std::io::{Read, Write};
+use std::num::NonZeroU32;
+
+// Secrets are age encrypted (https://age-encryption.org) and ASCII armored, so age and sops can create
+// and read them as well. Earlier versions wrote "fad1:<salt>:<nonce>:<ciphertext>" (base64 parts,
+// PBKDF2-HMAC-SHA256 and ChaCha20-Poly1305); these are still decrypted, encrypt-secrets writes age.
+const LEGACY_FORMAT: &str = "fad1";
+const LEGACY_ITERATIONS: u32 = 200_000;
+pub const PASSPHRASE_ENV: &str = "FIREPLAN_ALARM_DIVERA_SECRETS_PASSPHRASE";
+
+// What the secrets are encrypted to and decrypted with
+pub enum Key {
+    // content of an age identity file (age-keygen), the secrets_key_file
+    File(String),
+    // from the environment, age scrypt passphrase
+    Passphrase(String),
+    // age1... public keys, only for encrypting
+    Recipients(Vec<String>),
+}
+
+fn identity_file(content: &str) -> anyhow::Result<age::IdentityFile<age::NoCallbacks>> {
+    age::IdentityFile::from_buffer(content.as_bytes()).context("key file is not an age identity file (age-keygen)")
+}
+
+pub fn encrypt(plain: &str, key: &Key) -> anyhow::Result<String> {
+    let recipients: Vec<Box<dyn age::Recipient + Send>> = match key {
+        Key::File(content) => identity_file(content)?.to_recipients()?,
+        Key::Passphrase(passphrase) => vec![Box::new(age::scrypt::Recipient::new(SecretString::from(passphrase.as_str())))],
+        Key::Recipients(keys) => keys
+            .iter()
+            .map(|k| k.parse::<age::x25519::Recipient>().map(|r| Box::new(r) as _).map_err(|e| anyhow::anyhow!("recipient {}: {}", k, e)))
+            .collect::<anyhow::Result<_>>()?,
+    };
+    let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref() as &dyn age::Recipient))?;
+    let mut sealed = vec![];
+    let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(&mut sealed, Format::AsciiArmor)?)?;
+    writer.write_all(plain.as_bytes())?;
+    writer.finish()?.finish()?;
+    Ok(String::from_utf8(sealed)?)
+}
+
+pub fn decrypt(sealed: &str, key: &Key) -> anyhow::Result<String> {
+    let identities: Vec<Box<dyn age::Identity>> = match key {
+        Key::File(content) if sealed.trim().starts_with(LEGACY_FORMAT) => return decrypt_legacy(sealed, content.trim()),
+        Key::Passphrase(passphrase) if sealed.trim().starts_with(LEGACY_FORMAT) => return decrypt_legacy(sealed, passphrase),
+        Key::File(content) => identity_file(content)?.into_identities()?,
+        Key::Passphrase(passphrase) => vec![Box::new(age::scrypt::Identity::new(SecretString::from(passphrase.as_str())))],
+        Key::Recipients(_) => anyhow::bail!("recipients only encrypt, decrypting takes the identity file"),
+    };
+    let decryptor = age::Decryptor::new(ArmoredReader::new(sealed.trim_start().as_bytes())).context("not age encrypted")?;
+    let mut plain = String::new();
+    decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref() as &dyn age::Identity))
+        .map_err(|e| anyhow::anyhow!("wrong key or passphrase, or the secrets were changed ({})", e))?
+        .read_to_string(&mut plain)?;
+    Ok(plain)
+}
+
+fn decrypt_legacy(sealed: &str, passphrase: &str) -> anyhow::Result<String> {
+    let parts: Vec<&str> = sealed.trim().split(':').collect();
+    let [_, salt, nonce, ciphertext] = parts[..] else {
+        anyhow::bail!("not in {}:<salt>:<nonce>:<ciphertext> format", LEGACY_FORMAT);
+    };
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let salt = b64.decode(salt).context("salt")?;
+    let nonce = Nonce::try_assume_unique_for_key(&b64.decode(nonce).context("nonce")?).map_err(|_| anyhow::anyhow!("invalid nonce"))?;
+    let mut content = b64.decode(ciphertext).context("ciphertext")?;
+    let mut key = [0u8; 32];
+    let iterations = NonZeroU32::new(LEGACY_ITERATIONS).context("iterations")?;
+    ring::pbkdf2::derive(ring::pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, passphrase.as_bytes(), &mut key);
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(|_| anyhow::anyhow!("invalid key"))?);
+    let plain = key
+        .open_in_place(nonce, Aad::from(LEGACY_FORMAT), &mut content)
+        .map_err(|_| anyhow::anyhow!("wrong key or passphrase, or the secrets were changed"))?;
+    Ok(String::from_utf8(plain.to_vec())?)
+}
+
+// The key file if given, otherwise the passphrase from the environment
+pub fn key(key_file: Option<&str>) -> anyhow::Result<Key> {
+    match key_file {
+        Some(file) => {
+            let key = std::fs::read_to_string(file).with_context(|| format!("key file {}", file))?;
+            anyhow::ensure!(!key.trim().is_empty(), "key file {} is empty", file);
+            Ok(Key::File(key))
+        }
+        None => std::env::var(PASSPHRASE_ENV)
+            .map(Key::Passphrase)
+            .map_err(|_| anyhow::anyhow!("neither secrets_key_file nor {} set", PASSPHRASE_ENV)),
+    }
+}
+
+// Replace the encrypted `secrets` entry of the config by the keys it contains, e.g. fireplan_api_key
+// or auth_token; a key must not be set both in plain and in the secrets
+pub fn merge(table: &mut toml::Table) -> anyhow::Result<()> {
+    let key_file = table.remove("secrets_key_file");
+    let Some(sealed) = table.remove("secrets") else {
+        return Ok(());
+    };
+    let sealed = sealed.as_str().context("secrets must be a string")?;
+    let key_file = key_file.as_ref().map(|f| f.as_str().context("secrets_key_file must be a string")).transpose()?;
+    let plain = decrypt(sealed, &key(key_file)?)?;
+    let secrets: toml::Table = toml::from_str(&plain).context("decrypted secrets are not TOML")?;
+    let routing = crate::approval::routing_keys(table);
+    for (name, value) in secrets {
+        anyhow::ensure!(!table.contains_key(&name), "{} is set both in plain and in the secrets", name);
+        anyhow::ensure!(!routing.contains(&name), "{} decides the routing and cannot be kept in the secrets", name);
+        table.insert(name, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    const PLAIN: &str = "fireplan_api_key = \"key\"\n";
+
+    fn identity() -> (Key, String) {
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        (Key::File(format!("# public key: {}\n{}\n", recipient, identity.to_string().expose_secret())), recipient)
+    }
+
+    fn config(sealed: &str, key_file: &std::path::Path) -> toml::Table {
+        let mut table = toml::Table::new();
+        table.insert("secrets".to_string(), sealed.into());
+        table.insert("secrets_key_file".to_string(), key_file.to_string_lossy().to_string().into());
+        table.insert("rics".to_string(), toml::Value::Array(vec![]));
+        table
+    }
+
+    #[test]
+    fn roundtrip_with_key_file() {
+        let (key, _) = identity();
+        let sealed = encrypt(PLAIN, &key).unwrap();
+        assert!(sealed.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert_eq!(decrypt(&sealed, &key).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn roundtrip_to_recipient() {
+        let (key, recipient) = identity();
+        let sealed = encrypt(PLAIN, &Key::Recipients(vec![recipient])).unwrap();
+        assert_eq!(decrypt(&sealed, &key).unwrap(), PLAIN);
+        assert!(decrypt(&sealed, &Key::Recipients(vec![])).is_err());
+    }
+
+    #[test]
+    fn roundtrip_with_passphrase() {
+        let sealed = encrypt(PLAIN, &Key::Passphrase("correct horse".to_string())).unwrap();
+        assert_eq!(decrypt(&sealed, &Key::Passphrase("correct horse".to_string())).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn wrong_key_or_passphrase_is_refused() {
+        let sealed = encrypt(PLAIN, &identity().0).unwrap();
+        let error = decrypt(&sealed, &identity().0).unwrap_err();
+        assert!(error.to_string().starts_with("wrong key or passphrase"), "{}", error);
+        let sealed = encrypt(PLAIN, &Key::Passphrase("correct horse".to_string())).unwrap();
+        assert!(decrypt(&sealed, &Key::Passphrase("battery staple".to_string())).is_err());
+    }
+
+    #[test]
+    fn changed_secrets_are_refused() {
+        let (key, _) = identity();
+        let sealed = encrypt(PLAIN, &key).unwrap();
+        let mut lines: Vec<String> = sealed.lines().map(str::to_string).collect();
+        lines[1] = lines[1].chars().rev().collect();
+        assert!(decrypt(&lines.join("\n"), &key).is_err());
+    }
+
+    #[test]
+    fn legacy_format_is_still_read() {
+        let sealed = "fad1:zNFvqNjV/+g74n0fwwPD6g==:9Z317Knul7r8uO+D:tbteaKeDxN7SL4GLOlTKXZzoUGpmgABXqx3HO+xhwhNF8nqH/xsYmi0O7R0LehOt";
+        let plain = decrypt(sealed, &Key::File("legacy passphrase\n".to_string())).unwrap();
+        assert_eq!(plain, "fireplan_api_key = \"legacy-key\"\n");
+        assert!(decrypt(sealed, &Key::Passphrase("other".to_string())).is_err());
+    }
+
+    #[test]
+    fn merge_takes_secrets_but_not_routing_keys() {
+        let dir = std::env::temp_dir().join(format!("fireplan_alarm_divera_secrets_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("key");
+        let (key, _) = identity();
+        let Key::File(content) = &key else { unreachable!() };
+        std::fs::write(&key_file, content).unwrap();
+
+        let mut table = config(&encrypt(PLAIN, &key).unwrap(), &key_file);
+        merge(&mut table).unwrap();
+        assert_eq!(table["fireplan_api_key"].as_str(), Some("key"));
+        assert!(!table.contains_key("secrets") && !table.contains_key("secrets_key_file"));
+
+        let mut table = config(&encrypt("rics = []\n", &key).unwrap(), &key_file);
+        table.remove("rics");
+        let error = merge(&mut table).unwrap_err();
+        assert!(error.to_string().contains("decides the routing"), "{}", error);
+
+        let mut table = config(&encrypt(PLAIN, &key).unwrap(), &key_file);
+        table.insert("fireplan_api_key".to_string(), "plain".into());
+        assert!(merge(&mut table).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
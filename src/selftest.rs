@@ -0,0 +1,83 @@
+use crate::Configuration;
+use serde_derive::Serialize;
+use std::fs::OpenOptions;
+use std::net::ToSocketAddrs;
+use std::time::Instant;
+
+#[derive(Clone, Serialize, Debug)]
+pub struct Check {
+    component: String,
+    pub ok: bool,
+    detail: String,
+    duration_ms: u128,
+}
+
+fn check(component: String, f: impl FnOnce() -> anyhow::Result<String>) -> Check {
+    let started = Instant::now();
+    let (ok, detail) = match f() {
+        Ok(d) => (true, d),
+        Err(e) => (false, e.to_string()),
+    };
+    Check {
+        component,
+        ok,
+        detail,
+        duration_ms: started.elapsed().as_millis(),
+    }
+}
+
+fn resolve(host: &str) -> anyhow::Result<String> {
+    let addrs: Vec<String> = (host, 443).to_socket_addrs()?.map(|a| a.ip().to_string()).collect();
+    Ok(addrs.join(", "))
+}
+
+fn writable(path: &str) -> anyhow::Result<String> {
+    OpenOptions::new().create(true).append(true).open(path)?;
+    Ok("writable".to_string())
+}
+
+// Run all preflight checks, blocking, one entry per component
+pub fn run(configuration: &Configuration) -> Vec<Check> {
+    let mut checks = vec![];
+
+    let mut hosts = vec!["data.fireplan.de".to_string()];
+    if configuration.divera_poll.is_some() {
+        hosts.push("app.divera247.com".to_string());
+    }
+    for host in hosts {
+        checks.push(check(format!("dns:{}", host), || resolve(&host)));
+    }
+
+    for standort in configuration.standorte() {
+        checks.push(check(format!("fireplan_token:{}", standort), || {
+            crate::fireplan::verify_token(&standort, &configuration.fireplan_api_key)
+                .map(|_| "token received".to_string())
+                .map_err(anyhow::Error::from)
+        }));
+    }
+
+    if let Some(listener) = &configuration.mail_listener {
+        checks.push(check(format!("mail_listener:{}", listener.port), || {
+            crate::smtp::probe(listener).map_err(anyhow::Error::from)
+        }));
+    }
+
+    for path in [crate::paths::received_log(), crate::paths::submitted_log()] {
+        checks.push(check(format!("disk:{}", path), || writable(&path)));
+    }
+
+    if let Some(path) = &configuration.object_db {
+        checks.push(check("object_db".to_string(), || {
+            crate::objects::ObjectDb::load(path).map(|_| "loaded".to_string())
+        }));
+    }
+
+    if configuration.hydrants.is_some() {
+        checks.push(check("hydrants".to_string(), || {
+            anyhow::ensure!(crate::hydrants::enabled(), "water sources not loaded, see log");
+            Ok("loaded".to_string())
+        }));
+    }
+
+    checks
+}
@@ -0,0 +1,109 @@
+// Windows service integration: "install" / "uninstall" register the binary with the service manager,
+// which then starts it as "service --config <file>"
+use log::error;
+use std::ffi::OsString;
+use std::time::Duration;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+    ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+const SERVICE_NAME: &str = "fireplan_alarm_divera";
+const DISPLAY_NAME: &str = "Fireplan DIVERA Alarmierung";
+
+define_windows_service!(ffi_service_main, service_main);
+
+pub fn run_dispatcher() -> windows_service::Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+fn status(state: ServiceState) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: if state == ServiceState::Running {
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+        } else {
+            ServiceControlAccept::empty()
+        },
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+fn service_main(arguments: Vec<OsString>) {
+    let handler = |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = crate::send_event(crate::Event::Shutdown);
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+        Ok(h) => h,
+        Err(e) => {
+            error!("Could not register service control handler: {e}");
+            return;
+        }
+    };
+    let _ = status_handle.set_service_status(status(ServiceState::Running));
+
+    // the dispatcher passes the launch arguments of the service again
+    let arguments: Vec<String> = std::env::args()
+        .chain(arguments.iter().map(|a| a.to_string_lossy().to_string()))
+        .collect();
+    let file = arguments
+        .windows(2)
+        .find(|w| w[0] == "--config")
+        .map(|w| w[1].clone())
+        .unwrap_or_else(crate::paths::default_config_file);
+    crate::run(&file, false);
+
+    let _ = status_handle.set_service_status(status(ServiceState::Stopped));
+}
+
+pub fn install(config_file: &str) -> anyhow::Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let config_file = std::fs::canonicalize(config_file)?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![
+            OsString::from("service"),
+            OsString::from("--config"),
+            config_file.into_os_string(),
+        ],
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description("Forwards DIVERA and Leitstelle alarms to Fireplan")?;
+    Ok(())
+}
+
+pub fn uninstall() -> anyhow::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    service.delete()?;
+    Ok(())
+}
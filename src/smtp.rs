@@ -0,0 +1,370 @@
+use crate::error::MailError;
+use crate::{Event, Incoming, Route, Sequence};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// Longest command or message line read, RFC 5321 allows 1000 bytes but some MTAs pass longer ones through
+const MAX_LINE_BYTES: u64 = 16 * 1024;
+
+// How long delivered Message-IDs are remembered
+const SEEN_RETENTION_SECS: i64 = 14 * 24 * 3600;
+
+// Message-ID -> unix time of delivery, kept across restarts so a mail the MTA delivers again
+// (e.g. after it did not get our answer) or a requeued mailbox does not alarm twice
+static SEEN: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// The only mail intake: the MTA pushes alarm mails over SMTP/LMTP, there are no IMAP mailboxes to poll
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct MailListenerConfig {
+    pub port: u16,
+    // peers allowed to deliver, e.g. the local MTA
+    allowed_peers: Vec<String>,
+    // speak LMTP instead of SMTP
+    lmtp: Option<bool>,
+    pub standort: Option<String>,
+    profile: Option<String>,
+    // first capture group is used, searched line by line in the mail body
+    regex_einsatznummer: Option<String>,
+    regex_adresse: Option<String>,
+    max_message_bytes: Option<usize>,
+    // mails of one mailbox (recipient address) are processed one after another in arrival order,
+    // different mailboxes in parallel up to this many, defaults to 4
+    max_parallel_mails: Option<usize>,
+    // longest wait for a mail to go through the pipeline before the next one of its mailbox starts, defaults to 60
+    order_timeout_secs: Option<u64>,
+}
+
+pub fn load_seen() {
+    let file = crate::paths::mail_seen_file();
+    let seen: HashMap<String, i64> = match std::fs::read(&file) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            warn!("Could not read delivered mails {}: {}, starting empty", file, e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    };
+    info!("Mail listener: {} delivered mails remembered", seen.len());
+    *SEEN.lock().unwrap_or_else(|e| e.into_inner()) = seen;
+}
+
+fn save_seen(seen: &HashMap<String, i64>) {
+    let file = crate::paths::mail_seen_file();
+    let tmp = format!("{}.tmp", file);
+    let result = serde_json::to_vec(seen)
+        .map_err(std::io::Error::other)
+        .and_then(|content| std::fs::write(&tmp, content))
+        .and_then(|_| std::fs::rename(&tmp, &file));
+    if let Err(e) = result {
+        error!("Could not save delivered mails {}: {}", file, e);
+    }
+}
+
+// False if the mail was delivered before; mails without Message-ID are always processed
+fn first_delivery(message_id: &str) -> bool {
+    if message_id.is_empty() {
+        return true;
+    }
+    let now = chrono::Utc::now().timestamp();
+    let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+    seen.retain(|_, delivered| now - *delivered < SEEN_RETENTION_SECS);
+    if seen.contains_key(message_id) {
+        return false;
+    }
+    seen.insert(message_id.to_string(), now);
+    save_seen(&seen);
+    true
+}
+
+// Per mailbox: tickets handed out in arrival order and the ticket being processed
+#[derive(Default)]
+struct Turns {
+    issued: u64,
+    done: u64,
+}
+
+static MAILBOXES: Lazy<(Mutex<HashMap<String, Turns>>, Condvar)> = Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+static RUNNING: Lazy<(Mutex<usize>, Condvar)> = Lazy::new(|| (Mutex::new(0), Condvar::new()));
+
+// Turn of one mail in its mailbox, the next mail may start when it is dropped
+struct Turn(String);
+
+impl Turn {
+    fn wait(mailbox: &str) -> (Turn, u64) {
+        let (lock, changed) = &*MAILBOXES;
+        let mut mailboxes = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let turns = mailboxes.entry(mailbox.to_string()).or_default();
+        turns.issued += 1;
+        let number = turns.issued;
+        while mailboxes.get(mailbox).map(|t| t.done + 1 < number).unwrap_or(false) {
+            mailboxes = changed.wait(mailboxes).unwrap_or_else(|e| e.into_inner());
+        }
+        (Turn(mailbox.to_string()), number)
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let (lock, changed) = &*MAILBOXES;
+        let mut mailboxes = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(turns) = mailboxes.get_mut(&self.0) {
+            turns.done += 1;
+        }
+        drop(mailboxes);
+        changed.notify_all();
+    }
+}
+
+// Slot for one mail in the pipeline (max_parallel_mails), released on drop
+struct Slot;
+
+impl Slot {
+    fn acquire(max: usize) -> Slot {
+        let (lock, released) = &*RUNNING;
+        let mut running = lock.lock().unwrap_or_else(|e| e.into_inner());
+        while *running >= max {
+            running = released.wait(running).unwrap_or_else(|e| e.into_inner());
+        }
+        *running += 1;
+        Slot
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let (lock, released) = &*RUNNING;
+        let mut running = lock.lock().unwrap_or_else(|e| e.into_inner());
+        *running = running.saturating_sub(1);
+        drop(running);
+        released.notify_all();
+    }
+}
+
+fn reply(stream: &mut TcpStream, line: &str) -> std::io::Result<()> {
+    stream.write_all(format!("{}\r\n", line).as_bytes())
+}
+
+// One line up to MAX_LINE_BYTES, false at the end of the connection; a longer line is an error
+fn read_line(reader: &mut BufReader<TcpStream>, line: &mut Vec<u8>) -> std::io::Result<bool> {
+    line.clear();
+    if reader.by_ref().take(MAX_LINE_BYTES).read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    if !line.ends_with(b"\n") && line.len() as u64 >= MAX_LINE_BYTES {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "line too long"));
+    }
+    Ok(true)
+}
+
+// Minimal SMTP/LMTP dialogue: accept any sender/recipient from allowed peers, hand the DATA to the pipeline
+fn handle(stream: TcpStream, config: &MailListenerConfig, charsets: &HashMap<String, String>) -> std::io::Result<()> {
+    let lmtp = config.lmtp.unwrap_or(false);
+    let max_bytes = config.max_message_bytes.unwrap_or(1024 * 1024);
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    reply(&mut writer, "220 fireplan_alarm_divera ready")?;
+
+    let mut recipients = 0usize;
+    let mut mailbox = String::new();
+    let mut line = Vec::new();
+    loop {
+        match read_line(&mut reader, &mut line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                reply(&mut writer, "500 line too long")?;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        }
+        let command = String::from_utf8_lossy(&line).trim().to_string();
+        let verb = command.split_whitespace().next().unwrap_or("").to_uppercase();
+
+        match verb.as_str() {
+            "HELO" | "EHLO" | "LHLO" => reply(&mut writer, "250 fireplan_alarm_divera")?,
+            "MAIL" => {
+                recipients = 0;
+                reply(&mut writer, "250 OK")?
+            }
+            "RCPT" => {
+                // the first recipient decides the mailbox the mail is ordered in
+                if recipients == 0 {
+                    mailbox = address(&command);
+                }
+                recipients += 1;
+                reply(&mut writer, "250 OK")?
+            }
+            "DATA" => {
+                if recipients == 0 {
+                    reply(&mut writer, "503 need RCPT first")?;
+                    continue;
+                }
+                reply(&mut writer, "354 end with <CRLF>.<CRLF>")?;
+                let mut message = Vec::new();
+                let mut too_large = false;
+                loop {
+                    match read_line(&mut reader, &mut line) {
+                        Ok(true) => {}
+                        Ok(false) => return Ok(()),
+                        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                            too_large = true;
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                    if line == b".\r\n" || line == b".\n" {
+                        break;
+                    }
+                    // undo dot stuffing
+                    let data = if line.starts_with(b"..") { &line[1..] } else { &line[..] };
+                    if message.len() + data.len() > max_bytes {
+                        too_large = true;
+                        break;
+                    }
+                    message.extend_from_slice(data);
+                }
+
+                let answer = if too_large {
+                    warn!("Mail listener: message exceeds {} bytes or {} bytes per line, rejected", max_bytes, MAX_LINE_BYTES);
+                    "552 message too large"
+                } else {
+                    deliver(&message, &mailbox, config, charsets);
+                    "250 OK"
+                };
+                // LMTP answers once per accepted recipient
+                for _ in 0..if lmtp { recipients } else { 1 } {
+                    reply(&mut writer, answer)?;
+                }
+                // the rest of an oversized message is not read, the connection ends here
+                if too_large {
+                    return Ok(());
+                }
+                recipients = 0;
+            }
+            "RSET" => {
+                recipients = 0;
+                reply(&mut writer, "250 OK")?
+            }
+            "NOOP" => reply(&mut writer, "250 OK")?,
+            "QUIT" => {
+                reply(&mut writer, "221 bye")?;
+                return Ok(());
+            }
+            _ => reply(&mut writer, "502 command not implemented")?,
+        }
+    }
+}
+
+// "RCPT TO:<Alarm@Example.org>" -> "alarm@example.org"
+fn address(command: &str) -> String {
+    let argument = command.split_once(':').map(|(_, a)| a).unwrap_or_default();
+    let argument = argument.split_whitespace().next().unwrap_or_default();
+    argument.trim_matches(|c| c == '<' || c == '>').to_lowercase()
+}
+
+fn deliver(message: &[u8], mailbox: &str, config: &MailListenerConfig, charsets: &HashMap<String, String>) {
+    let route = Route {
+        standort: config.standort.clone().unwrap_or_else(|| Route::default().standort),
+        profile: config.profile.clone(),
+    };
+    let mail = crate::mail::parse(message, charsets.get(&route.standort).map(|c| c.as_str()));
+    if !first_delivery(&mail.message_id) {
+        info!("Mail listener: mail {} '{}' was already processed, ignored", mail.message_id, mail.subject);
+        return;
+    }
+    info!("Mail listener: received alarm mail '{}'", mail.subject);
+    let payload = crate::mail::to_payload(&mail, &config.regex_einsatznummer, &config.regex_adresse);
+    crate::log_received(&payload);
+
+    // an update must not overtake the alarm it belongs to, so wait for the mail before
+    let (_turn, number) = Turn::wait(mailbox);
+    let _slot = Slot::acquire(config.max_parallel_mails.unwrap_or(4).max(1));
+    let sequence = Sequence {
+        mailbox: mailbox.to_string(),
+        number,
+    };
+    let incoming = Incoming::new(payload, route).with_sequence(sequence.clone());
+    let id = incoming.id().to_string();
+    if let Err(e) = crate::send_event(Event::Submit(incoming)) {
+        error!("Mail listener: could not send event: {}", e);
+        return;
+    }
+    let timeout = Duration::from_secs(config.order_timeout_secs.unwrap_or(60));
+    if crate::submissions::wait_finished(&id, timeout).is_none() {
+        warn!("Mail listener: mail {} still in the pipeline after {:?}, continuing with the next one", sequence, timeout);
+    }
+}
+
+// Talk to the running listener like the MTA does (greeting, EHLO/LHLO, QUIT), for verify-mail
+pub fn probe(config: &MailListenerConfig) -> Result<String, MailError> {
+    let lmtp = config.lmtp.unwrap_or(false);
+    let stream = TcpStream::connect_timeout(&([127, 0, 0, 1], config.port).into(), Duration::from_secs(5))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut answer = |expected: &str| -> Result<(), MailError> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with(expected) {
+            return Err(MailError::UnexpectedAnswer(line.trim().to_string()));
+        }
+        Ok(())
+    };
+    answer("220")?;
+    reply(&mut writer, if lmtp { "LHLO verify" } else { "EHLO verify" })?;
+    answer("250")?;
+    reply(&mut writer, "QUIT")?;
+    answer("221")?;
+    let remembered = SEEN.lock().map(|s| s.len()).unwrap_or(0);
+    Ok(format!(
+        "{} listener on port {} answers, {} delivered mails remembered",
+        if lmtp { "LMTP" } else { "SMTP" },
+        config.port,
+        remembered
+    ))
+}
+
+// charsets: per-Standort charset of mails that declare none
+pub fn start_listener(config: MailListenerConfig, charsets: HashMap<String, String>) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(("0.0.0.0", config.port))?;
+    load_seen();
+    info!(
+        "Mail listener ({}) on port {}, allowed peers {:?}",
+        if config.lmtp.unwrap_or(false) { "LMTP" } else { "SMTP" },
+        config.port,
+        config.allowed_peers
+    );
+
+    Ok(std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Mail listener: accept failed: {}", e);
+                    continue;
+                }
+            };
+            let peer = stream.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default();
+            if !config.allowed_peers.contains(&peer) {
+                warn!("Mail listener: rejecting connection from {}", peer);
+                continue;
+            }
+            let config = config.clone();
+            let charsets = charsets.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handle(stream, &config, &charsets) {
+                    warn!("Mail listener: connection from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }))
+}
@@ -0,0 +1,143 @@
+use crate::bus::BusEvent;
+use crate::routing::{self, Sink, SinkRoute};
+use crate::ParsedData;
+use chrono::{NaiveTime, Timelike};
+use chrono_tz::Tz;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::Duration;
+
+#[cfg(unix)]
+const DEFAULT_PLAYER: &str = "aplay -q {file}";
+#[cfg(windows)]
+const DEFAULT_PLAYER: &str = "powershell -NoProfile -Command (New-Object Media.SoundPlayer '{file}').PlaySync()";
+
+// Gong played when an alarm passes routing (sink "sound"), on the host's audio output or a network speaker
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct SoundConfig {
+    // default sound file
+    file: String,
+    // command line, {file} is replaced by the sound, defaults to aplay (Linux) or the PowerShell SoundPlayer (Windows)
+    player: Option<String>,
+    // network speaker: the file is POSTed there instead of playing it locally
+    speaker_url: Option<String>,
+    // first rule with a matching Stichwort prefix picks the sound
+    sounds: Option<Vec<KeywordSound>>,
+    quiet_hours: Option<QuietHours>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct KeywordSound {
+    // Einsatzstichwort prefixes, e.g. ["B3", "B4"]
+    keywords: Vec<String>,
+    file: String,
+    // played with this file also during quiet hours
+    #[serde(default)]
+    ignore_quiet_hours: bool,
+}
+
+// "HH:MM", may wrap around midnight; plays the quieter file, or nothing without one
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct QuietHours {
+    from: String,
+    to: String,
+    file: Option<String>,
+}
+
+struct Sound {
+    config: SoundConfig,
+    routes: Vec<SinkRoute>,
+    timezone: Tz,
+}
+
+static SOUND: OnceCell<Sound> = OnceCell::new();
+
+pub fn init(config: SoundConfig, routes: Vec<SinkRoute>, timezone: Tz) {
+    let _ = SOUND.set(Sound { config, routes, timezone });
+}
+
+impl QuietHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        let parse = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M");
+        match (parse(&self.from), parse(&self.to)) {
+            (Ok(from), Ok(to)) if from <= to => time >= from && time < to,
+            (Ok(from), Ok(to)) => time >= from || time < to,
+            _ => {
+                error!("Sound: invalid quiet hours {} - {}", self.from, self.to);
+                false
+            }
+        }
+    }
+}
+
+// File to play for the alarm, None when quiet hours silence it
+fn choose(config: &SoundConfig, data: &ParsedData, time: NaiveTime) -> Option<String> {
+    let stichwort = data.einsatzstichwort.trim().to_uppercase();
+    let rule = config
+        .sounds
+        .iter()
+        .flatten()
+        .find(|s| s.keywords.iter().any(|k| stichwort.starts_with(&k.trim().to_uppercase())));
+    if rule.is_some_and(|r| r.ignore_quiet_hours) {
+        return rule.map(|r| r.file.clone());
+    }
+    match &config.quiet_hours {
+        Some(quiet) if quiet.contains(time) => quiet.file.clone(),
+        _ => Some(rule.map(|r| r.file.clone()).unwrap_or_else(|| config.file.clone())),
+    }
+}
+
+fn play(config: &SoundConfig, file: &str) -> anyhow::Result<()> {
+    if let Some(url) = &config.speaker_url {
+        let r = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?
+            .post(url)
+            .header("Content-Type", "application/octet-stream")
+            .body(std::fs::read(file)?)
+            .send()?;
+        anyhow::ensure!(r.status().is_success(), "speaker answered {}", r.status());
+        return Ok(());
+    }
+    let line = config.player.as_deref().unwrap_or(DEFAULT_PLAYER);
+    let mut words = line.split_whitespace().map(|w| w.replace("{file}", file));
+    let program = words.next().ok_or_else(|| anyhow::anyhow!("empty player"))?;
+    let status = Command::new(program).args(words).status()?;
+    anyhow::ensure!(status.success(), "player exited with {}", status);
+    Ok(())
+}
+
+fn ring(data: &ParsedData) {
+    let Some(sound) = SOUND.get() else {
+        return;
+    };
+    if routing::rics_for(Sink::Sound, &sound.routes, data).is_none() {
+        return;
+    }
+    let now = chrono::Utc::now().with_timezone(&sound.timezone);
+    let time = NaiveTime::from_hms_opt(now.hour(), now.minute(), 0).unwrap_or_default();
+    let Some(file) = choose(&sound.config, data, time) else {
+        info!("Sound: quiet hours, not playing for {}", data.einsatznrlst);
+        return;
+    };
+    match play(&sound.config, &file) {
+        Ok(()) => info!("Sound: played {} for {}", file, data.einsatznrlst),
+        Err(e) => error!("Sound: could not play {}: {}", file, e),
+    }
+}
+
+// The gong sounds for every alarm that went through the pipeline, also when Fireplan failed
+pub fn on_event(event: &BusEvent) {
+    let alarm = match event {
+        BusEvent::Submitted { alarm } => alarm,
+        BusEvent::Failed { alarm: Some(alarm), .. } => alarm,
+        _ => return,
+    };
+    // playback takes seconds and must not hold up the submitter
+    let alarm = alarm.clone();
+    std::thread::spawn(move || ring(&alarm));
+}
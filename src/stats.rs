@@ -0,0 +1,148 @@
+use crate::export::Operation;
+use crate::history::HistoryEntry;
+use crate::ParsedData;
+use chrono::{Datelike, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_derive::Serialize;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// Pipeline events since startup by event name and Standort ("" for events without alarm), shown on /metrics
+static PIPELINE_COUNTS: Lazy<Mutex<BTreeMap<(&'static str, String), u64>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn on_event(event: &crate::bus::BusEvent) {
+    let key = (event.name(), event.standort().unwrap_or_default().to_string());
+    *PIPELINE_COUNTS.lock().unwrap_or_else(|e| e.into_inner()).entry(key).or_insert(0) += 1;
+}
+
+pub fn pipeline_counts() -> BTreeMap<&'static str, u64> {
+    let mut counts = BTreeMap::new();
+    for ((event, _), n) in PIPELINE_COUNTS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        *counts.entry(*event).or_insert(0) += n;
+    }
+    counts
+}
+
+pub fn pipeline_counts_by_standort() -> BTreeMap<(&'static str, String), u64> {
+    PIPELINE_COUNTS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct Stats {
+    pub total: usize,
+    // "YYYY-MM" -> operations
+    pub per_month: BTreeMap<String, usize>,
+    pub per_category: BTreeMap<String, usize>,
+    pub per_ric: BTreeMap<String, usize>,
+    // seconds from reception to successful Fireplan submission
+    pub average_dispatch_latency_secs: Option<f64>,
+    // seconds from the Leitstelle timestamp to successful Fireplan submission
+    pub end_to_end_latency_p50_secs: Option<i64>,
+    pub end_to_end_latency_p95_secs: Option<i64>,
+}
+
+// Category of an alarm: the one tagged by the category rules, otherwise the leading letters of the
+// Einsatzstichwort, e.g. "FEUER3" -> "FEUER", "THL 1" -> "THL"
+pub fn category(alarm: &ParsedData) -> String {
+    if !alarm.category.is_empty() {
+        return alarm.category.clone();
+    }
+    let prefix: String = alarm
+        .einsatzstichwort
+        .trim()
+        .chars()
+        .take_while(|c| c.is_alphabetic())
+        .collect();
+    if prefix.is_empty() {
+        "SONSTIGE".to_string()
+    } else {
+        prefix.to_uppercase()
+    }
+}
+
+fn year_of(ts: i64, tz: Tz) -> Option<i32> {
+    Utc.timestamp_opt(ts, 0).single().map(|d| d.with_timezone(&tz).year())
+}
+
+// Aggregate operations (optionally of one year) and the submission latencies of the history entries
+pub fn compute(operations: &[Operation], entries: &[HistoryEntry], year: Option<i32>, tz: Tz) -> Stats {
+    let mut stats = Stats::default();
+    let in_year = |ts: i64| year.map(|y| year_of(ts, tz) == Some(y)).unwrap_or(true);
+
+    for op in operations.iter().filter(|op| in_year(op.start)) {
+        stats.total += 1;
+        if let Some(d) = Utc.timestamp_opt(op.start, 0).single() {
+            let month = d.with_timezone(&tz).format("%Y-%m").to_string();
+            *stats.per_month.entry(month).or_default() += 1;
+        }
+        *stats.per_category.entry(category(&op.alarm)).or_default() += 1;
+        for ric in &op.alarm.rics {
+            *stats.per_ric.entry(ric.text.clone()).or_default() += 1;
+        }
+    }
+
+    let latencies: Vec<i64> = entries
+        .iter()
+        .filter(|e| e.result == "submitted" && e.received > 0 && in_year(e.received))
+        .map(|e| e.processed - e.received)
+        .collect();
+    if !latencies.is_empty() {
+        stats.average_dispatch_latency_secs = Some(latencies.iter().sum::<i64>() as f64 / latencies.len() as f64);
+    }
+
+    let mut end_to_end = end_to_end_latencies(entries, in_year);
+    end_to_end.sort_unstable();
+    stats.end_to_end_latency_p50_secs = percentile(&end_to_end, 50);
+    stats.end_to_end_latency_p95_secs = percentile(&end_to_end, 95);
+
+    stats
+}
+
+fn end_to_end_latencies(entries: &[HistoryEntry], in_year: impl Fn(i64) -> bool) -> Vec<i64> {
+    entries
+        .iter()
+        .filter(|e| e.result == "submitted" && e.alarm.created > 0 && in_year(e.alarm.created))
+        .map(|e| (e.processed - e.alarm.created).max(0))
+        .collect()
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[i64], p: usize) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+// (p50, p95) end-to-end latency over all submitted alarms
+pub fn latency_percentiles(entries: &[HistoryEntry]) -> (Option<i64>, Option<i64>) {
+    let mut values = end_to_end_latencies(entries, |_| true);
+    values.sort_unstable();
+    (percentile(&values, 50), percentile(&values, 95))
+}
+
+// Bar chart of operations per month for the dashboard
+pub fn month_chart_html(stats: &Stats) -> String {
+    let max = stats.per_month.values().copied().max().unwrap_or(0).max(1);
+    let bars: String = stats
+        .per_month
+        .iter()
+        .rev()
+        .take(12)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .map(|(month, count)| {
+            format!(
+                r#"<div class="barcol"><div class="barval" style="height: {}%;" title="{count}"></div><span>{month}</span><b>{count}</b></div>"#,
+                count * 100 / max
+            )
+        })
+        .collect();
+    if bars.is_empty() {
+        return "<p>No operations recorded yet</p>".to_string();
+    }
+    format!(r#"<div class="chart">{bars}</div>"#)
+}
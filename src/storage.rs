@@ -0,0 +1,296 @@
+use anyhow::Context;
+use log::{error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect, TlsStream};
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    File,
+    Postgres,
+}
+
+// Where the alarm history is kept: the JSONL file in the state directory, or a table on a PostgreSQL server
+// shared by several departments
+#[derive(Clone, Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct StorageConfig {
+    backend: Option<Backend>,
+    // "postgres://user@db.kreis.local/alarme", with "?sslmode=require" over TLS checked against the system CAs
+    url: Option<String>,
+    // PEM file with the CA of the database server, for a certificate of an own CA
+    ca_file: Option<String>,
+    // kept out of the url so the config editor masks it
+    password: Option<String>,
+    // defaults to fireplan_alarm_history, fireplan_alarm_history_<name> for a tenant; one table per department on a
+    // shared database
+    table: Option<String>,
+}
+
+// Append-only store of the history lines, the JSON of each entry exactly as it was hashed
+pub trait Store: Send + Sync {
+    // for messages: file path or table
+    fn name(&self) -> String;
+    // all lines, oldest first
+    fn lines(&self) -> anyhow::Result<Vec<String>>;
+    fn last_line(&self) -> anyhow::Result<Option<String>>;
+    fn append(&self, received: i64, line: &str) -> anyhow::Result<()>;
+    // remove the lines received before the cutoff, returns how many
+    fn prune(&self, cutoff: i64) -> anyhow::Result<usize>;
+}
+
+static TABLE_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z_][a-z0-9_]{0,62}$").expect("table name pattern"));
+
+static HISTORY: OnceCell<Box<dyn Store>> = OnceCell::new();
+
+pub fn init(config: &StorageConfig) -> anyhow::Result<()> {
+    let store: Box<dyn Store> = match config.backend.unwrap_or_default() {
+        Backend::File => Box::new(FileStore),
+        Backend::Postgres => Box::new(PostgresStore::connect(config)?),
+    };
+    let _ = HISTORY.set(store);
+    Ok(())
+}
+
+// History store, the file if none was configured
+pub fn history() -> &'static dyn Store {
+    HISTORY.get_or_init(|| Box::new(FileStore)).as_ref()
+}
+
+fn received(line: &str) -> Option<i64> {
+    serde_json::from_str::<serde_json::Value>(line).ok()?["received"].as_i64()
+}
+
+// JSON lines in the history file of the state directory
+pub struct FileStore;
+
+impl Store for FileStore {
+    fn name(&self) -> String {
+        crate::paths::history_file()
+    }
+
+    fn lines(&self) -> anyhow::Result<Vec<String>> {
+        match std::fs::read_to_string(crate::paths::history_file()) {
+            Ok(content) => Ok(content.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn last_line(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.lines()?.pop())
+    }
+
+    fn append(&self, _received: i64, line: &str) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(crate::paths::history_file())?;
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        Ok(())
+    }
+
+    // kept lines are copied unchanged, unreadable ones are kept
+    fn prune(&self, cutoff: i64) -> anyhow::Result<usize> {
+        let lines = self.lines()?;
+        let kept: Vec<&String> = lines.iter().filter(|l| received(l).is_none_or(|r| r >= cutoff)).collect();
+        if kept.len() == lines.len() {
+            return Ok(0);
+        }
+        let mut content = kept.iter().map(|l| l.as_str()).collect::<Vec<_>>().join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        let tmp = format!("{}.tmp", crate::paths::history_file());
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, crate::paths::history_file())?;
+        Ok(lines.len() - kept.len())
+    }
+}
+
+// Connect and statements time out, a database that stops answering must not hold the submit workers
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const STATEMENT_TIMEOUT_MS: u32 = 5000;
+
+// One row per history line; the line is stored as text so the hash chain stays verifiable
+pub struct PostgresStore {
+    config: postgres::Config,
+    // with sslmode=require, else the connection is plain
+    tls: Option<Arc<rustls::ClientConfig>>,
+    table: String,
+    client: Mutex<Option<postgres::Client>>,
+}
+
+impl PostgresStore {
+    fn connect(config: &StorageConfig) -> anyhow::Result<PostgresStore> {
+        let url = config.url.as_deref().context("history_storage: postgres needs a url")?;
+        let mut pg: postgres::Config = url.parse().context("history_storage: invalid url")?;
+        if let Some(password) = &config.password {
+            pg.password(password);
+        }
+        if pg.get_connect_timeout().is_none() {
+            pg.connect_timeout(CONNECT_TIMEOUT);
+        }
+        if pg.get_tcp_user_timeout().is_none() {
+            pg.tcp_user_timeout(CONNECT_TIMEOUT);
+        }
+        let options = format!("{} -c statement_timeout={}", pg.get_options().unwrap_or_default(), STATEMENT_TIMEOUT_MS);
+        pg.options(options.trim_start());
+        let tls = match pg.get_ssl_mode() {
+            postgres::config::SslMode::Require => Some(Arc::new(tls_config(config.ca_file.as_deref())?)),
+            _ => None,
+        };
+        // a tenant keeps its history apart from the host and the other tenants on the same database
+        let table = config.table.clone().unwrap_or_else(|| match crate::tenants::current() {
+            Some(tenant) => format!("fireplan_alarm_history_{}", tenant.to_lowercase().replace('-', "_")),
+            None => "fireplan_alarm_history".to_string(),
+        });
+        anyhow::ensure!(TABLE_NAME.is_match(&table), "history_storage: invalid table name {}, set table", table);
+        let store = PostgresStore { config: pg, tls, table, client: Mutex::new(None) };
+        // a database down at startup must not keep alarms from going out, the store connects again on use
+        match store.with_client(|_, _| Ok(())) {
+            Ok(()) => info!("History kept in PostgreSQL table {}", store.table),
+            Err(e) => error!("History in PostgreSQL table {}: {:#}, retrying on use", store.table, e),
+        }
+        Ok(store)
+    }
+
+    fn open(&self) -> anyhow::Result<postgres::Client> {
+        let connected = match &self.tls {
+            Some(tls) => self.config.connect(RustlsConnect(tls.clone())),
+            None => self.config.connect(postgres::NoTls),
+        };
+        let mut client = connected.context("could not connect to PostgreSQL")?;
+        let table = &self.table;
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (id BIGSERIAL PRIMARY KEY, received BIGINT NOT NULL, line TEXT NOT NULL);
+                 CREATE INDEX IF NOT EXISTS {table}_received ON {table} (received);"
+            ))
+            .with_context(|| format!("could not create table {}", table))?;
+        Ok(client)
+    }
+
+    // Runs the statement on the connection, connecting again after an error
+    fn with_client<T>(
+        &self,
+        f: impl FnOnce(&mut postgres::Client, &str) -> Result<T, postgres::Error>,
+    ) -> anyhow::Result<T> {
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        if client.as_ref().is_none_or(|c| c.is_closed()) {
+            *client = Some(self.open()?);
+        }
+        let result = f(client.as_mut().expect("connected above"), &self.table);
+        if result.is_err() {
+            *client = None;
+        }
+        Ok(result?)
+    }
+}
+
+impl Store for PostgresStore {
+    fn name(&self) -> String {
+        format!("PostgreSQL table {}", self.table)
+    }
+
+    fn lines(&self) -> anyhow::Result<Vec<String>> {
+        let rows = self.with_client(|c, table| c.query(&format!("SELECT line FROM {table} ORDER BY id"), &[]))?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    fn last_line(&self) -> anyhow::Result<Option<String>> {
+        let row = self.with_client(|c, table| c.query_opt(&format!("SELECT line FROM {table} ORDER BY id DESC LIMIT 1"), &[]))?;
+        Ok(row.map(|r| r.get(0)))
+    }
+
+    fn append(&self, received: i64, line: &str) -> anyhow::Result<()> {
+        self.with_client(|c, table| c.execute(&format!("INSERT INTO {table} (received, line) VALUES ($1, $2)"), &[&received, &line]))?;
+        Ok(())
+    }
+
+    fn prune(&self, cutoff: i64) -> anyhow::Result<usize> {
+        let removed = self.with_client(|c, table| c.execute(&format!("DELETE FROM {table} WHERE received < $1"), &[&cutoff]))?;
+        Ok(removed as usize)
+    }
+}
+
+// Roots for the database certificate: the system CAs and the ca_file
+fn tls_config(ca_file: Option<&str>) -> anyhow::Result<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    if let Some(ca_file) = ca_file {
+        let mut reader = std::io::BufReader::new(
+            std::fs::File::open(crate::paths::state_file(ca_file)).with_context(|| format!("history_storage: could not open {}", ca_file))?,
+        );
+        for cert in rustls_pemfile::certs(&mut reader) {
+            roots.add(cert.with_context(|| format!("history_storage: invalid certificate in {}", ca_file))?)?;
+        }
+    }
+    anyhow::ensure!(!roots.is_empty(), "history_storage: sslmode=require without CA certificates");
+    Ok(rustls::ClientConfig::builder().with_root_certificates(roots).with_no_client_auth())
+}
+
+// TLS for sslmode=require with rustls, the server certificate and host name are verified
+struct RustlsConnect(Arc<rustls::ClientConfig>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> MakeTlsConnect<S> for RustlsConnect {
+    type Stream = RustlsStream<S>;
+    type TlsConnect = RustlsHandshake;
+    type Error = rustls::pki_types::InvalidDnsNameError;
+
+    fn make_tls_connect(&mut self, domain: &str) -> Result<RustlsHandshake, Self::Error> {
+        let server_name = rustls::pki_types::ServerName::try_from(domain)?.to_owned();
+        Ok(RustlsHandshake(tokio_rustls::TlsConnector::from(self.0.clone()), server_name))
+    }
+}
+
+struct RustlsHandshake(tokio_rustls::TlsConnector, rustls::pki_types::ServerName<'static>);
+
+type Handshake<S> = Pin<Box<dyn Future<Output = std::io::Result<RustlsStream<S>>> + Send>>;
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> TlsConnect<S> for RustlsHandshake {
+    type Stream = RustlsStream<S>;
+    type Error = std::io::Error;
+    type Future = Handshake<S>;
+
+    fn connect(self, stream: S) -> Handshake<S> {
+        let RustlsHandshake(connector, server_name) = self;
+        Box::pin(async move { Ok(RustlsStream(connector.connect(server_name, stream).await?)) })
+    }
+}
+
+struct RustlsStream<S>(tokio_rustls::client::TlsStream<S>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream for RustlsStream<S> {
+    fn channel_binding(&self) -> ChannelBinding {
+        ChannelBinding::none()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for RustlsStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for RustlsStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
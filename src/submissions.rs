@@ -0,0 +1,147 @@
+use crate::bus::BusEvent;
+use log::warn;
+use once_cell::sync::Lazy;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Keep processing states for one day
+const RETENTION_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Received,
+    Parsed,
+    Deduped,
+    Submitted,
+    Failed,
+    Vetoed,
+    Expired,
+    Cancelled,
+    Closed,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct SubmissionStatus {
+    id: String,
+    state: State,
+    reason: Option<String>,
+    einsatznrlst: Option<String>,
+    created: String,
+    updated: String,
+}
+
+// Processing state per submission id
+static SUBMISSIONS: Lazy<Mutex<HashMap<String, (SubmissionStatus, i64)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Register a new submission in state Received and return its processing id
+pub fn create() -> String {
+    let now = chrono::Utc::now();
+    let id = format!(
+        "{:x}-{:04x}",
+        now.timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff
+    );
+
+    if let Ok(mut map) = SUBMISSIONS.lock() {
+        map.retain(|_, (_, created)| now.timestamp() - *created < RETENTION_SECS);
+        map.insert(
+            id.clone(),
+            (
+                SubmissionStatus {
+                    id: id.clone(),
+                    state: State::Received,
+                    reason: None,
+                    einsatznrlst: None,
+                    created: now.to_rfc3339(),
+                    updated: now.to_rfc3339(),
+                },
+                now.timestamp(),
+            ),
+        );
+    } else {
+        warn!("Could not lock submission states");
+    }
+    id
+}
+
+pub fn update(id: &str, state: State, einsatznrlst: Option<&str>, reason: Option<String>) {
+    let Ok(mut map) = SUBMISSIONS.lock() else {
+        warn!("Could not lock submission states");
+        return;
+    };
+    if let Some((status, _)) = map.get_mut(id) {
+        status.state = state;
+        status.reason = reason;
+        if let Some(nr) = einsatznrlst {
+            status.einsatznrlst = Some(nr.to_string());
+        }
+        status.updated = chrono::Utc::now().to_rfc3339();
+    }
+}
+
+pub fn get(id: &str) -> Option<SubmissionStatus> {
+    SUBMISSIONS.lock().ok()?.get(id).map(|(status, _)| status.clone())
+}
+
+// Wait until the submission left the pipeline, None if it is still running after the timeout
+pub fn wait_finished(id: &str, timeout: Duration) -> Option<State> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match get(id).map(|status| status.state) {
+            Some(State::Received | State::Parsed) => {}
+            // unknown ids are not tracked, nothing to wait for
+            state => return state,
+        }
+        if Instant::now() > deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// Processing state follows the pipeline events
+pub fn on_event(event: &BusEvent) {
+    match event {
+        BusEvent::Parsed { submission_id, einsatznrlst, .. } => {
+            update(submission_id, State::Parsed, Some(einsatznrlst), None)
+        }
+        BusEvent::Deduped { alarm } => update(&alarm.submission_id, State::Deduped, None, None),
+        BusEvent::Submitted { alarm } => update(&alarm.submission_id, State::Submitted, None, None),
+        BusEvent::Failed { submission_id, reason, .. } => {
+            update(submission_id, State::Failed, None, Some(reason.clone()))
+        }
+        BusEvent::Vetoed { alarm, hook, reason } => update(
+            &alarm.submission_id,
+            State::Vetoed,
+            None,
+            Some(format!("{}: {}", hook, reason)),
+        ),
+        BusEvent::Expired { alarm, age_secs } => update(
+            &alarm.submission_id,
+            State::Expired,
+            Some(&alarm.einsatznrlst),
+            Some(format!("{} s old", age_secs)),
+        ),
+        BusEvent::Cancelled { alarm: Some(alarm), reason, .. } => update(
+            &alarm.submission_id,
+            State::Cancelled,
+            Some(&alarm.einsatznrlst),
+            Some(reason.clone()),
+        ),
+        BusEvent::Dropped { submission_id, stage, .. } => update(
+            submission_id,
+            State::Failed,
+            None,
+            Some(format!("dropped from full {} queue", stage)),
+        ),
+        BusEvent::Closed { submission_id, foreign_id, .. } => {
+            update(submission_id, State::Closed, Some(foreign_id), None)
+        }
+        _ => {}
+    }
+}
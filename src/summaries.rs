@@ -0,0 +1,59 @@
+use log::error;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// how many Einsätze keep their summary for the monitor page
+const KEEP: usize = 20;
+
+// Line from a slow external service (weather, route) computed once per Einsatz on its own thread, for the
+// alarm notification and the monitor page. The submit path only reads what is already there.
+pub struct SummaryCache {
+    // for messages
+    name: &'static str,
+    // Einsatznummer -> summary, None while the fetch runs; newest last
+    entries: Mutex<VecDeque<(String, Option<String>)>>,
+}
+
+impl SummaryCache {
+    pub const fn new(name: &'static str) -> SummaryCache {
+        SummaryCache { name, entries: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn get(&self, einsatznrlst: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().find(|(nr, _)| nr == einsatznrlst).and_then(|(_, s)| s.clone())
+    }
+
+    // Start the fetch unless the Einsatz has its summary or the fetch already runs. An empty summary or
+    // an error is not kept, so a later alarm for the Einsatz tries again.
+    pub fn fetch(&'static self, einsatznrlst: &str, fetch: impl FnOnce() -> anyhow::Result<String> + Send + 'static) {
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.iter().any(|(nr, _)| nr == einsatznrlst) {
+                return;
+            }
+            entries.push_back((einsatznrlst.to_string(), None));
+            while entries.len() > KEEP {
+                entries.pop_front();
+            }
+        }
+        let einsatznrlst = einsatznrlst.to_string();
+        std::thread::spawn(move || {
+            let result = fetch();
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(position) = entries.iter().position(|(nr, _)| *nr == einsatznrlst) else {
+                return;
+            };
+            match result {
+                Ok(summary) if !summary.is_empty() => entries[position].1 = Some(summary),
+                Ok(_) => {
+                    entries.remove(position);
+                }
+                Err(e) => {
+                    error!("{}: could not fetch for {}: {:#}", self.name, einsatznrlst, e);
+                    entries.remove(position);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,27 @@
+use log::error;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Panics caught in the pipeline since startup, shown on /metrics
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+pub fn panic_count() -> u64 {
+    PANICS.load(Ordering::Relaxed)
+}
+
+// Run one unit of work, a panic is logged with the given context and counted instead of killing the thread
+pub fn guard<T>(stage: &str, context: &str, work: impl FnOnce() -> T) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(work)) {
+        Ok(result) => Some(result),
+        Err(panic) => {
+            PANICS.fetch_add(1, Ordering::Relaxed);
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            error!("Panic in {} stage: {}, continuing with the next alarm. Input: {}", stage, message, context);
+            None
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Another department hosted by this process: a child process with its own config file (tokens, Standorte,
+// port) and its own state directory <state_dir>/tenants/<name>
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Tenant {
+    // letters, digits, "_" and "-"; names the state directory and the tenant metrics label
+    name: String,
+    config: String,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TenantStatus {
+    name: String,
+    config: String,
+    state_dir: String,
+    running: bool,
+    pid: Option<u32>,
+    started: Option<String>,
+    restarts: u32,
+    last_exit: Option<String>,
+}
+
+static TENANT_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9_-]{1,64}$").expect("tenant name pattern"));
+
+// (name, state directory) when this process runs as tenant of another one
+static CURRENT: OnceCell<(String, String)> = OnceCell::new();
+
+static STATUS: Lazy<Mutex<Vec<TenantStatus>>> = Lazy::new(|| Mutex::new(vec![]));
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+// time a tenant gets after SIGTERM to finish the alarms in flight before it is killed
+const GRACE: Duration = Duration::from_secs(10);
+
+// --tenant <name> --state-dir <dir> from the hosting process
+pub fn set_current(name: String, state_dir: String) {
+    let _ = CURRENT.set((name, state_dir));
+}
+
+pub fn current() -> Option<&'static str> {
+    CURRENT.get().map(|(name, _)| name.as_str())
+}
+
+// A tenant keeps its state where the hosting process put it
+pub fn apply(state_dir: &mut Option<String>) {
+    if let Some((_, dir)) = CURRENT.get() {
+        *state_dir = Some(dir.clone());
+    }
+}
+
+pub fn status() -> Vec<TenantStatus> {
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn update(name: &str, f: impl FnOnce(&mut TenantStatus)) {
+    let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(s) = status.iter_mut().find(|s| s.name == name) {
+        f(s);
+    }
+}
+
+// Config problems keeping the tenants from running side by side: names and ports must be distinct
+fn check(tenants: &[Tenant], host_ports: &[u16]) -> Vec<(String, String)> {
+    let mut problems = vec![];
+    let mut ports: Vec<u16> = host_ports.to_vec();
+    let mut names = vec![];
+    for tenant in tenants {
+        if !TENANT_NAME.is_match(&tenant.name) || names.contains(&tenant.name) {
+            problems.push((tenant.name.clone(), "invalid or duplicate tenant name".to_string()));
+            continue;
+        }
+        names.push(tenant.name.clone());
+        match crate::try_load_configuration(&tenant.config, false) {
+            Ok(configuration) => {
+                let own: Vec<u16> = configuration.listening_ports();
+                if let Some(port) = own.iter().find(|p| ports.contains(p)) {
+                    problems.push((tenant.name.clone(), format!("port {} is already used by another department", port)));
+                }
+                ports.extend(own);
+            }
+            Err(e) => problems.push((tenant.name.clone(), e.to_string())),
+        }
+    }
+    problems
+}
+
+// Forward the output of a tenant to the own output with the tenant name in front
+fn forward(name: String, stream: impl std::io::Read + Send + 'static, stderr: bool) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if stderr {
+                eprintln!("[{}] {}", name, line);
+            } else {
+                println!("[{}] {}", name, line);
+            }
+        }
+    });
+}
+
+// A tenant gets SIGTERM when the host exits, also when the host is killed, so no tenant keeps running with its
+// ports bound. The signal follows the thread that started the tenant, its supervise thread runs as long as the
+// tenant does.
+#[cfg(target_os = "linux")]
+fn bind_to_host(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    let host = nix::unistd::getpid();
+    // SAFETY: only async-signal-safe system calls between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            nix::sys::prctl::set_pdeathsig(nix::sys::signal::Signal::SIGTERM)?;
+            // the host exited before the signal was set
+            if nix::unistd::getppid() != host {
+                return Err(std::io::Error::other("host exited"));
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_to_host(_command: &mut Command) {}
+
+// Switching to run_as_user clears the signal set in bind_to_host, the tenant sets it again afterwards
+pub fn rebind_to_host() {
+    #[cfg(target_os = "linux")]
+    if let Some(name) = current() {
+        if let Err(e) = nix::sys::prctl::set_pdeathsig(nix::sys::signal::Signal::SIGTERM) {
+            warn!("Tenant {}: exit with the host not set again: {}", name, e);
+        }
+    }
+}
+
+// Stop a tenant the way the service manager stops the host, with SIGTERM; killed if it is still running after GRACE
+fn terminate(name: &str, child: &mut Child) -> String {
+    #[cfg(unix)]
+    {
+        let pid = nix::unistd::Pid::from_raw(child.id() as i32);
+        if nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGTERM).is_ok() {
+            let deadline = Instant::now() + GRACE;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    return "stopped".to_string();
+                }
+                std::thread::sleep(Duration::from_millis(250));
+            }
+            warn!("Tenant {} still running {} s after SIGTERM, killing it", name, GRACE.as_secs());
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    "killed".to_string()
+}
+
+// Runs the tenant until shutdown, starting it again after an exit with growing delay
+fn supervise(tenant: Tenant, state_dir: String) {
+    let Ok(exe) = std::env::current_exe() else {
+        error!("Tenant {}: own executable not found", tenant.name);
+        return;
+    };
+    let mut restarts = 0;
+    while !STOPPING.load(Ordering::SeqCst) {
+        let mut command = Command::new(&exe);
+        command
+            .args(["--config", &tenant.config, "--tenant", &tenant.name, "--state-dir", &state_dir])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        bind_to_host(&mut command);
+        let spawned = command.spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                error!("Tenant {}: could not start: {}", tenant.name, e);
+                update(&tenant.name, |s| s.last_exit = Some(e.to_string()));
+                return;
+            }
+        };
+        info!("Tenant {} started with pid {}", tenant.name, child.id());
+        update(&tenant.name, |s| {
+            s.running = true;
+            s.pid = Some(child.id());
+            s.started = Some(crate::localtime::format(chrono::Utc::now().timestamp()));
+            s.restarts = restarts;
+        });
+        if let Some(out) = child.stdout.take() {
+            forward(tenant.name.clone(), out, false);
+        }
+        if let Some(err) = child.stderr.take() {
+            forward(tenant.name.clone(), err, true);
+        }
+        let exit = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status.to_string(),
+                Ok(None) if STOPPING.load(Ordering::SeqCst) => break terminate(&tenant.name, &mut child),
+                Ok(None) => std::thread::sleep(Duration::from_millis(500)),
+                Err(e) => break e.to_string(),
+            }
+        };
+        update(&tenant.name, |s| {
+            s.running = false;
+            s.pid = None;
+            s.last_exit = Some(exit.clone());
+        });
+        if STOPPING.load(Ordering::SeqCst) {
+            break;
+        }
+        restarts += 1;
+        let delay = 5 * restarts.min(12) as u64;
+        warn!("Tenant {} exited ({}), starting again in {} s", tenant.name, exit, delay);
+        std::thread::sleep(Duration::from_secs(delay));
+    }
+}
+
+// Start the tenants next to the own pipeline; tenants whose config cannot run are reported and skipped
+pub fn start(tenants: Vec<Tenant>, host_ports: &[u16]) {
+    let problems = check(&tenants, host_ports);
+    for tenant in tenants {
+        let state_dir = crate::paths::state_dir().join("tenants").join(&tenant.name).to_string_lossy().to_string();
+        let problem = problems.iter().find(|(name, _)| name == &tenant.name).map(|(_, p)| p.clone());
+        STATUS.lock().unwrap_or_else(|e| e.into_inner()).push(TenantStatus {
+            name: tenant.name.clone(),
+            config: tenant.config.clone(),
+            state_dir: state_dir.clone(),
+            running: false,
+            pid: None,
+            started: None,
+            restarts: 0,
+            last_exit: problem.clone(),
+        });
+        if let Some(problem) = problem {
+            error!("Tenant {} not started: {}", tenant.name, problem);
+            continue;
+        }
+        if let Err(e) = std::fs::create_dir_all(&state_dir) {
+            error!("Tenant {}: could not create {}: {}", tenant.name, state_dir, e);
+            continue;
+        }
+        std::thread::spawn(move || supervise(tenant, state_dir));
+    }
+}
+
+// Stop the tenants with the own shutdown, waiting for them to exit or be killed
+pub fn stop() {
+    STOPPING.store(true, Ordering::SeqCst);
+    let deadline = Instant::now() + GRACE + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        if !status().iter().any(|s| s.running) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
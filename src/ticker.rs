@@ -0,0 +1,159 @@
+use crate::bus::BusEvent;
+use crate::routing::{self, Sink, SinkRoute};
+use crate::ParsedData;
+use chrono_tz::Tz;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::Duration;
+
+const DEFAULT_FORMAT: &str = "{stichwort} - {adresse} {ort} - {rics}";
+
+// Station LED ticker or e-paper display, fed with one condensed line per alarm over raw TCP or a serial port
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct TickerConfig {
+    name: String,
+    // "host:port"
+    tcp: Option<String>,
+    // device, e.g. "/dev/ttyUSB0" or "COM3"; baud sets the line speed before writing (stty/mode)
+    serial: Option<String>,
+    baud: Option<u32>,
+    // placeholders {stichwort}, {adresse}, {ort}, {ortsteil}, {objekt}, {einsatznummer}, {rics}, {zeit}
+    format: Option<String>,
+    // framing the display protocol expects, e.g. "\u0002" and "\u0003\r\n"
+    prefix: Option<String>,
+    suffix: Option<String>,
+    max_length: Option<usize>,
+    // "ascii" replaces umlauts (ä -> ae), otherwise an encoding label like "iso-8859-1", defaults to UTF-8
+    charset: Option<String>,
+}
+
+struct Tickers {
+    tickers: Vec<TickerConfig>,
+    routes: Vec<SinkRoute>,
+    timezone: Tz,
+}
+
+static TICKERS: OnceCell<Tickers> = OnceCell::new();
+
+pub fn init(tickers: Vec<TickerConfig>, routes: Vec<SinkRoute>, timezone: Tz) {
+    let _ = TICKERS.set(Tickers { tickers, routes, timezone });
+}
+
+fn line(config: &TickerConfig, data: &ParsedData, timezone: Tz) -> String {
+    let time = chrono::Utc::now().with_timezone(&timezone).format("%H:%M").to_string();
+    let adresse = format!("{} {}", data.strasse, data.hausnummer);
+    let rics = data.rics.iter().map(|r| r.text.as_str()).collect::<Vec<_>>().join(", ");
+    let text = config
+        .format
+        .as_deref()
+        .unwrap_or(DEFAULT_FORMAT)
+        .replace("{stichwort}", &data.einsatzstichwort)
+        .replace("{adresse}", adresse.trim())
+        .replace("{ort}", &data.ort)
+        .replace("{ortsteil}", &data.ortsteil)
+        .replace("{objekt}", &data.objektname)
+        .replace("{einsatznummer}", &data.einsatznrlst)
+        .replace("{rics}", &rics)
+        .replace("{zeit}", &time);
+    // displays show a single line
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match config.max_length {
+        Some(max) => text.chars().take(max).collect(),
+        None => text,
+    }
+}
+
+fn encode(config: &TickerConfig, text: &str) -> Vec<u8> {
+    match config.charset.as_deref() {
+        Some(c) if c.eq_ignore_ascii_case("ascii") => text
+            .replace('ä', "ae")
+            .replace('ö', "oe")
+            .replace('ü', "ue")
+            .replace('Ä', "Ae")
+            .replace('Ö', "Oe")
+            .replace('Ü', "Ue")
+            .replace('ß', "ss")
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '?' })
+            .collect::<String>()
+            .into_bytes(),
+        Some(label) => match encoding_rs::Encoding::for_label(label.as_bytes()) {
+            Some(encoding) => encoding.encode(text).0.into_owned(),
+            None => {
+                error!("Ticker {}: unknown charset {}, sending UTF-8", config.name, label);
+                text.as_bytes().to_vec()
+            }
+        },
+        None => text.as_bytes().to_vec(),
+    }
+}
+
+#[cfg(unix)]
+fn set_baud(device: &str, baud: u32) -> anyhow::Result<()> {
+    let status = std::process::Command::new("stty").args(["-F", device, &baud.to_string(), "raw"]).status()?;
+    anyhow::ensure!(status.success(), "stty exited with {}", status);
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_baud(device: &str, baud: u32) -> anyhow::Result<()> {
+    let status = std::process::Command::new("mode").arg(format!("{}: BAUD={}", device, baud)).status()?;
+    anyhow::ensure!(status.success(), "mode exited with {}", status);
+    Ok(())
+}
+
+fn send(config: &TickerConfig, data: &ParsedData, timezone: Tz) -> anyhow::Result<()> {
+    let text = line(config, data, timezone);
+    let mut bytes = config.prefix.clone().unwrap_or_default().into_bytes();
+    bytes.extend(encode(config, &text));
+    bytes.extend(config.suffix.clone().unwrap_or_else(|| "\r\n".to_string()).into_bytes());
+
+    if let Some(address) = &config.tcp {
+        let address = std::net::ToSocketAddrs::to_socket_addrs(address)?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("could not resolve {}", address))?;
+        let mut stream = TcpStream::connect_timeout(&address, Duration::from_secs(5))?;
+        stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+        stream.write_all(&bytes)?;
+    } else if let Some(device) = &config.serial {
+        if let Some(baud) = config.baud {
+            set_baud(device, baud)?;
+        }
+        std::fs::OpenOptions::new().write(true).open(device)?.write_all(&bytes)?;
+    } else {
+        anyhow::bail!("neither tcp nor serial configured");
+    }
+    info!("Ticker {}: {}", config.name, text);
+    Ok(())
+}
+
+fn show(data: &ParsedData) {
+    let Some(config) = TICKERS.get() else {
+        return;
+    };
+    let Some(rics) = routing::rics_for(Sink::Ticker, &config.routes, data) else {
+        return;
+    };
+    let data = ParsedData { rics, ..data.clone() };
+    for ticker in &config.tickers {
+        if let Err(e) = send(ticker, &data, config.timezone) {
+            error!("Ticker {}: {}", ticker.name, e);
+        }
+    }
+}
+
+// Displays show every alarm that went through the pipeline, also when Fireplan failed
+pub fn on_event(event: &BusEvent) {
+    let alarm = match event {
+        BusEvent::Submitted { alarm } => alarm,
+        BusEvent::Failed { alarm: Some(alarm), .. } => alarm,
+        _ => return,
+    };
+    // slow or unreachable displays must not hold up the submitter
+    let alarm = alarm.clone();
+    std::thread::spawn(move || show(&alarm));
+}
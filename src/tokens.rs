@@ -0,0 +1,257 @@
+use crate::SubmitToken;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// Submit token minted through /api/tokens, kept in the state directory until revoked. Only the SHA-256 hash
+// of the token is kept ("sha256:<hex>" as `token`), with its first characters for listings; the tokens are
+// random, so an unsalted hash is enough
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MintedToken {
+    pub id: String,
+    #[serde(flatten)]
+    pub token: SubmitToken,
+    #[serde(default)]
+    pub prefix: String,
+    pub created: String,
+}
+
+const HASHED: &str = "sha256:";
+
+// Token with its own Standort, profile or mapping waiting for a second admin when config_approval guards
+// submit_tokens; kept in memory only, like a staged config change
+#[derive(Clone, Debug)]
+pub struct PendingMint {
+    pub id: String,
+    pub by: String,
+    pub at: i64,
+    pub template: SubmitToken,
+}
+
+static MINTED: Lazy<Mutex<Vec<MintedToken>>> = Lazy::new(|| Mutex::new(vec![]));
+static PENDING: Mutex<Vec<PendingMint>> = Mutex::new(vec![]);
+
+pub fn load() {
+    let file = crate::paths::tokens_file();
+    let tokens: Vec<MintedToken> = match std::fs::read(&file) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            error!("Could not read minted tokens {}: {}, none are valid", file, e);
+            vec![]
+        }),
+        Err(_) => vec![],
+    };
+    let mut tokens = tokens;
+    // files of earlier versions kept the tokens in plain
+    let plain: Vec<&mut MintedToken> = tokens.iter_mut().filter(|m| !m.token.token.starts_with(HASHED)).collect();
+    if !plain.is_empty() {
+        info!("Replacing {} minted submit tokens by their hashes", plain.len());
+        for minted in plain {
+            minted.prefix = prefix(&minted.token.token);
+            minted.token.token = hashed(&minted.token.token);
+        }
+        save(&tokens);
+    }
+    info!("{} minted submit tokens", tokens.len());
+    *MINTED.lock().unwrap_or_else(|e| e.into_inner()) = tokens;
+}
+
+fn save(tokens: &[MintedToken]) {
+    let file = crate::paths::tokens_file();
+    let tmp = format!("{}.tmp", file);
+    let result = serde_json::to_vec(tokens)
+        .map_err(std::io::Error::other)
+        .and_then(|content| std::fs::write(&tmp, content))
+        .and_then(|_| std::fs::rename(&tmp, &file));
+    if let Err(e) = result {
+        error!("Could not save minted tokens {}: {}", file, e);
+    }
+}
+
+// Unix time of an RFC 3339 validity bound; an unparsable bound makes the token invalid
+fn bound(value: &Option<String>) -> Result<Option<i64>, ()> {
+    match value {
+        Some(v) => match chrono::DateTime::parse_from_rfc3339(v) {
+            Ok(t) => Ok(Some(t.timestamp())),
+            Err(e) => {
+                warn!("Invalid token validity {}: {}", v, e);
+                Err(())
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+// Within valid_from..valid_until, open ends are unbounded
+pub fn valid_now(valid_from: &Option<String>, valid_until: &Option<String>) -> bool {
+    let now = chrono::Utc::now().timestamp();
+    match (bound(valid_from), bound(valid_until)) {
+        (Ok(from), Ok(until)) => from.is_none_or(|f| now >= f) && until.is_none_or(|u| now < u),
+        _ => false,
+    }
+}
+
+fn hashed(token: &str) -> String {
+    format!("{}{}", HASHED, crate::history::sha256(token))
+}
+
+fn prefix(token: &str) -> String {
+    token.chars().take(4).collect()
+}
+
+// A currently valid minted token, with its hash as `token`
+pub fn find(token: &str) -> Option<SubmitToken> {
+    let hash = hashed(token);
+    MINTED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|m| &m.token)
+        .find(|t| t.token == hash && valid_now(&t.valid_from, &t.valid_until))
+        .cloned()
+}
+
+fn random_hex(bytes: usize) -> anyhow::Result<String> {
+    let mut buffer = vec![0u8; bytes];
+    SystemRandom::new().fill(&mut buffer).map_err(|_| anyhow::anyhow!("no random numbers"))?;
+    Ok(buffer.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// New random token for the settings of `template` (its token is replaced); the token itself is only
+// returned here, the minted entry has its hash
+pub fn mint(template: SubmitToken) -> anyhow::Result<(MintedToken, String)> {
+    bound(&template.valid_from).and(bound(&template.valid_until)).map_err(|_| anyhow::anyhow!("valid_from and valid_until must be RFC 3339"))?;
+    let token = random_hex(24)?;
+    let minted = MintedToken {
+        id: random_hex(4)?,
+        token: SubmitToken { token: hashed(&token), ..template },
+        prefix: prefix(&token),
+        created: chrono::Utc::now().to_rfc3339(),
+    };
+    let mut tokens = MINTED.lock().unwrap_or_else(|e| e.into_inner());
+    tokens.push(minted.clone());
+    save(&tokens);
+    Ok((minted, token))
+}
+
+// Keep a mint for the approval of a second admin
+pub fn request(template: SubmitToken, by: &str) -> anyhow::Result<PendingMint> {
+    bound(&template.valid_from).and(bound(&template.valid_until)).map_err(|_| anyhow::anyhow!("valid_from and valid_until must be RFC 3339"))?;
+    let request = PendingMint { id: random_hex(4)?, by: by.to_string(), at: chrono::Utc::now().timestamp(), template };
+    lock_pending().push(request.clone());
+    Ok(request)
+}
+
+// Pending mints, dropped after the approval expiry
+fn lock_pending() -> std::sync::MutexGuard<'static, Vec<PendingMint>> {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let now = chrono::Utc::now().timestamp();
+    pending.retain(|p| now - p.at <= crate::approval::expiry_secs());
+    pending
+}
+
+pub fn pending() -> Vec<PendingMint> {
+    lock_pending().clone()
+}
+
+// Take a pending request for minting, approved by another admin than the one who asked for it;
+// Ok(None) for an unknown id
+pub fn approve(id: &str, by: &str) -> anyhow::Result<Option<PendingMint>> {
+    let mut pending = lock_pending();
+    let Some(index) = pending.iter().position(|p| p.id == id) else {
+        return Ok(None);
+    };
+    anyhow::ensure!(pending[index].by != by, "The token needs the approval of a second admin");
+    Ok(Some(pending.remove(index)))
+}
+
+pub fn list() -> Vec<MintedToken> {
+    MINTED.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// False if there is no minted token with this id
+pub fn revoke(id: &str) -> bool {
+    let mut tokens = MINTED.lock().unwrap_or_else(|e| e.into_inner());
+    let before = tokens.len();
+    tokens.retain(|t| t.id != id);
+    if tokens.len() == before {
+        return false;
+    }
+    save(&tokens);
+    true
+}
+
+// First characters only, enough to tell tokens apart in listings
+pub fn masked(token: &str) -> String {
+    format!("{}…", prefix(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(valid_from: Option<&str>, valid_until: Option<&str>) -> SubmitToken {
+        SubmitToken {
+            token: String::new(),
+            standort: "Nord".to_string(),
+            profile: None,
+            mapping: None,
+            valid_from: valid_from.map(str::to_string),
+            valid_until: valid_until.map(str::to_string),
+        }
+    }
+
+    fn state_dir() {
+        crate::paths::set_state_dir(&std::env::temp_dir().join(format!("fireplan_alarm_divera_tokens_{}", std::process::id())).to_string_lossy());
+    }
+
+    #[test]
+    fn validity_window() {
+        let past = Some("2020-01-01T00:00:00Z".to_string());
+        let future = Some("2999-01-01T00:00:00+01:00".to_string());
+        assert!(valid_now(&None, &None));
+        assert!(valid_now(&past, &future));
+        assert!(!valid_now(&future, &None));
+        assert!(!valid_now(&None, &past));
+        assert!(!valid_now(&None, &Some("tomorrow".to_string())));
+    }
+
+    #[test]
+    fn minted_token_is_stored_as_hash_and_found_until_revoked() {
+        state_dir();
+        let (minted, token) = mint(template(None, None)).unwrap();
+        assert_eq!(minted.token.token, hashed(&token));
+        assert_eq!(masked(&minted.prefix), masked(&token));
+        let stored = std::fs::read_to_string(crate::paths::tokens_file()).unwrap();
+        assert!(stored.contains(&minted.token.token) && !stored.contains(&token));
+        assert_eq!(find(&token).map(|t| t.standort), Some("Nord".to_string()));
+        assert!(find(&minted.token.token).is_none());
+
+        assert!(revoke(&minted.id));
+        assert!(find(&token).is_none());
+        assert!(!revoke(&minted.id));
+    }
+
+    #[test]
+    fn minted_token_is_only_found_within_its_window() {
+        state_dir();
+        let (expired, expired_token) = mint(template(None, Some("2020-01-01T00:00:00Z"))).unwrap();
+        let (future, future_token) = mint(template(Some("2999-01-01T00:00:00Z"), None)).unwrap();
+        assert!(find(&expired_token).is_none());
+        assert!(find(&future_token).is_none());
+        assert!(mint(template(Some("soon"), None)).is_err());
+        revoke(&expired.id);
+        revoke(&future.id);
+    }
+
+    #[test]
+    fn own_mint_cannot_be_approved() {
+        let request = request(template(None, None), "Anna").unwrap();
+        assert!(approve(&request.id, "Anna").is_err());
+        assert!(pending().iter().any(|p| p.id == request.id));
+        let approved = approve(&request.id, "Ben").unwrap().unwrap();
+        assert_eq!(approved.by, "Anna");
+        assert!(approve(&request.id, "Ben").unwrap().is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use log::info;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+// Full request/response logging for Fireplan and DIVERA calls, switched on at runtime by the admin
+// for a limited time; 0 when off, otherwise the unix time it switches off again
+static ENABLED_UNTIL: AtomicI64 = AtomicI64::new(0);
+
+// Tokens and keys in JSON bodies and query strings
+static SECRETS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)("(?:utoken|token|api[-_]?key|api[-_]?token|accesskey)"\s*:\s*")[^"]*(")|((?:accesskey|token|api_key)=)[^&\s]*"#)
+        .unwrap()
+});
+
+pub fn enabled() -> bool {
+    ENABLED_UNTIL.load(Ordering::Relaxed) > chrono::Utc::now().timestamp()
+}
+
+// unix time tracing stops, None when off
+pub fn enabled_until() -> Option<i64> {
+    enabled().then(|| ENABLED_UNTIL.load(Ordering::Relaxed))
+}
+
+pub fn enable(minutes: i64) {
+    let until = chrono::Utc::now().timestamp() + minutes.clamp(1, 24 * 60) * 60;
+    ENABLED_UNTIL.store(until, Ordering::Relaxed);
+    info!("HTTP exchange tracing enabled for {} minutes", minutes.clamp(1, 24 * 60));
+}
+
+pub fn disable() {
+    ENABLED_UNTIL.store(0, Ordering::Relaxed);
+    info!("HTTP exchange tracing disabled");
+}
+
+pub fn mask(text: &str) -> String {
+    SECRETS
+        .replace_all(text, |c: &regex::Captures| match (c.get(1), c.get(2), c.get(3)) {
+            (Some(prefix), Some(suffix), _) => format!("{}***{}", prefix.as_str(), suffix.as_str()),
+            (_, _, Some(prefix)) => format!("{}***", prefix.as_str()),
+            _ => "***".to_string(),
+        })
+        .to_string()
+}
+
+// Log one exchange when tracing is on; header secrets are never passed in, bodies and URLs are masked
+pub fn exchange(target: &str, method: &str, url: &str, request: &str, status: &str, response: &str) {
+    if !enabled() {
+        return;
+    }
+    info!(
+        "TRACE {} {} {} request: {} -> {} response: {}",
+        target,
+        method,
+        mask(url),
+        mask(request),
+        status,
+        mask(response)
+    );
+}
@@ -0,0 +1,83 @@
+use crate::{Configuration, ParsedData};
+
+// `fireplan_alarm_divera verify-fireplan`: fetch a token for every configured Standort and, with verify_ric
+// configured, send it a test alarm; prints one line per Standort, false if any Standort failed
+pub fn fireplan(configuration: &Configuration) -> bool {
+    let mut ok = true;
+    for standort in configuration.standorte() {
+        let result = crate::fireplan::verify_token(&standort, &configuration.fireplan_api_key).and_then(|_| {
+            match &configuration.verify_ric {
+                Some(ric) => crate::fireplan::submit(standort.clone(), configuration.fireplan_api_key.clone(), test_alarm(&standort, ric))
+                    .map(|_| "token received, test alarm accepted"),
+                None => Ok("token received"),
+            }
+        });
+        match result {
+            Ok(detail) => println!("OK     {}: {}", standort, detail),
+            Err(e) => {
+                ok = false;
+                println!("FAILED {}: {}", standort, e);
+            }
+        }
+    }
+    ok
+}
+
+// `fireplan_alarm_divera verify-mail`: asks the running mail listener for its greeting
+pub fn mail(configuration: &Configuration) -> bool {
+    let Some(listener) = &configuration.mail_listener else {
+        println!("FAILED no mail_listener configured");
+        return false;
+    };
+    crate::smtp::load_seen();
+    let standort = listener.standort.clone().unwrap_or_else(|| crate::Route::default().standort);
+    match crate::smtp::probe(listener) {
+        Ok(detail) => {
+            println!("OK     {}: {}", standort, detail);
+            true
+        }
+        Err(e) => {
+            println!("FAILED {}: {}", standort, e);
+            false
+        }
+    }
+}
+
+fn test_alarm(standort: &str, ric: &crate::Ric) -> ParsedData {
+    let now = chrono::Utc::now().timestamp();
+    ParsedData {
+        submission_id: format!("verify-{}", now),
+        received: now,
+        created: now,
+        standort: standort.to_string(),
+        rics: vec![ric.clone()],
+        einsatznrlst: format!("TEST-{}", now),
+        strasse: String::new(),
+        hausnummer: String::new(),
+        ort: String::new(),
+        ortsteil: String::new(),
+        objektname: String::new(),
+        koordinaten: String::new(),
+        einsatzstichwort: "Verbindungstest".to_string(),
+        zusatzinfo: "Test der Fireplan-Anbindung (verify-fireplan), kein Einsatz".to_string(),
+        priority: 0,
+        sequence: None,
+        category: String::new(),
+        drill: None,
+    }
+}
+
+// `fireplan_alarm_divera verify-history`: check the hash chain of the alarm history, false if it is broken
+pub fn history() -> bool {
+    let file = crate::storage::history().name();
+    match crate::history::verify_chain() {
+        Ok(n) => {
+            println!("OK     {}: {} chained entries", file, n);
+            true
+        }
+        Err((line, reason)) => {
+            println!("FAILED {} line {}: {}", file, line, reason);
+            false
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::bus::BusEvent;
+use crate::summaries::SummaryCache;
+use crate::ParsedData;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use regex::{Regex, RegexBuilder};
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+const BRIGHTSKY_URL: &str = "https://api.brightsky.dev";
+const OPENWEATHER_URL: &str = "https://api.openweathermap.org/data/2.5/weather";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    // DWD observations and warnings via Bright Sky, no key needed
+    #[default]
+    Dwd,
+    Openweather,
+}
+
+// Short weather summary for storm and flood alarms, added to the alarm notification and the monitor page
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct WeatherConfig {
+    // case-insensitive regexes on the Einsatzstichwort, e.g. ["UNWETTER", "WASSER", "STURM"]
+    keywords: Vec<String>,
+    provider: Option<Provider>,
+    // needed for openweather
+    api_key: Option<String>,
+    // replaces the provider's base url
+    url: Option<String>,
+    // defaults to 10
+    timeout_secs: Option<u64>,
+}
+
+static CONFIG: OnceCell<(WeatherConfig, Vec<Regex>)> = OnceCell::new();
+static SUMMARIES: SummaryCache = SummaryCache::new("Weather");
+
+pub fn init(config: WeatherConfig) {
+    let patterns = config
+        .keywords
+        .iter()
+        .filter_map(|k| match RegexBuilder::new(k).case_insensitive(true).build() {
+            Ok(re) => Some(re),
+            Err(e) => {
+                error!("Weather: {} is not a proper regular expression: {}", k, e);
+                None
+            }
+        })
+        .collect();
+    let _ = CONFIG.set((config, patterns));
+}
+
+fn wanted(patterns: &[Regex], alarm: &ParsedData) -> Option<(f64, f64)> {
+    if !patterns.iter().any(|re| re.is_match(&alarm.einsatzstichwort)) {
+        return None;
+    }
+    crate::coordinates::parse(&alarm.koordinaten)
+}
+
+fn get(client: &Client, url: &str) -> anyhow::Result<Value> {
+    let r = client.get(url).send()?;
+    anyhow::ensure!(r.status().is_success(), "answered {}", r.status());
+    Ok(r.json()?)
+}
+
+fn number(value: &Value, unit: &str) -> Option<String> {
+    value.as_f64().map(|v| format!("{:.0} {}", v, unit))
+}
+
+// "12 °C, rain, Wind 45 km/h, Böen 80 km/h, 6 mm/h" plus the headlines of current DWD warnings
+fn dwd(client: &Client, base: &str, lat: f64, lng: f64) -> anyhow::Result<String> {
+    let current = get(client, &format!("{}/current_weather?lat={}&lon={}", base, lat, lng))?;
+    let w = &current["weather"];
+    let mut parts: Vec<String> = [
+        number(&w["temperature"], "°C"),
+        w["condition"].as_str().map(str::to_string),
+        number(&w["wind_speed_10"], "km/h").map(|s| format!("Wind {}", s)),
+        number(&w["wind_gust_speed_10"], "km/h").map(|s| format!("Böen {}", s)),
+        number(&w["precipitation_60"], "mm/h"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let alerts = get(client, &format!("{}/alerts?lat={}&lon={}", base, lat, lng))?;
+    for alert in alerts["alerts"].as_array().into_iter().flatten() {
+        if let Some(headline) = alert["headline_de"].as_str().or(alert["headline_en"].as_str()) {
+            parts.push(headline.to_string());
+        }
+    }
+    Ok(parts.join(", "))
+}
+
+// OpenWeather gives m/s, converted to km/h like DWD
+fn openweather(client: &Client, config: &WeatherConfig, lat: f64, lng: f64) -> anyhow::Result<String> {
+    let key = config.api_key.as_deref().ok_or_else(|| anyhow::anyhow!("api_key missing"))?;
+    let base = config.url.as_deref().unwrap_or(OPENWEATHER_URL);
+    let current = get(client, &format!("{}?lat={}&lon={}&appid={}&units=metric&lang=de", base, lat, lng, key))?;
+    let kmh = |v: &Value| v.as_f64().map(|ms| Value::from(ms * 3.6)).unwrap_or(Value::Null);
+    let parts: Vec<String> = [
+        number(&current["main"]["temp"], "°C"),
+        current["weather"][0]["description"].as_str().map(str::to_string),
+        number(&kmh(&current["wind"]["speed"]), "km/h").map(|s| format!("Wind {}", s)),
+        number(&kmh(&current["wind"]["gust"]), "km/h").map(|s| format!("Böen {}", s)),
+        number(&current["rain"]["1h"], "mm/h"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Ok(parts.join(", "))
+}
+
+fn fetch(config: &WeatherConfig, lat: f64, lng: f64) -> anyhow::Result<String> {
+    let client = Client::builder().timeout(Duration::from_secs(config.timeout_secs.unwrap_or(10))).build()?;
+    match config.provider.unwrap_or_default() {
+        Provider::Dwd => dwd(&client, config.url.as_deref().unwrap_or(BRIGHTSKY_URL).trim_end_matches('/'), lat, lng),
+        Provider::Openweather => openweather(&client, config, lat, lng),
+    }
+}
+
+// Summary already fetched for the Einsatz, for the alarm notification and the monitor page
+pub fn cached(einsatznrlst: &str) -> Option<String> {
+    SUMMARIES.get(einsatznrlst)
+}
+
+// Fetch the weather at the alarm coordinates on a thread if the Stichwort matches a keyword, once per Einsatz
+pub fn prefetch(alarm: &ParsedData) {
+    let Some((config, patterns)) = CONFIG.get() else {
+        return;
+    };
+    let Some((lat, lng)) = wanted(patterns, alarm) else {
+        return;
+    };
+    let einsatznrlst = alarm.einsatznrlst.clone();
+    SUMMARIES.fetch(&alarm.einsatznrlst, move || {
+        let summary = fetch(config, lat, lng)?;
+        info!("Weather for {}: {}", einsatznrlst, summary);
+        Ok(if summary.is_empty() { summary } else { format!("Wetter: {}", summary) })
+    });
+}
+
+// Also for alarms sent again from the outbox, for the monitor page
+pub fn on_event(event: &BusEvent) {
+    if let BusEvent::Submitted { alarm } = event {
+        prefetch(alarm);
+    }
+}
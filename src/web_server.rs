@@ -1,12 +1,18 @@
-use std::os::unix::ffi::OsStrExt;
-use log::{error, info};
+use log::{error, info, warn};
 use std::thread::JoinHandle;
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
 
 // Actix Web imports
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{delete, get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use actix_web::middleware::Logger as ActixLogger;
+use actix_web::middleware::{ErrorHandlerResponse, ErrorHandlers};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use crate::error::ParseError;
+use crate::problem::{problem, problem_with};
 
 // rustls (0.23) imports to enable HTTPS
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
@@ -15,12 +21,126 @@ use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 #[derive(Clone)]
 pub struct AppState {
     pub auth_token: String,
+    pub submit_tokens: Vec<crate::SubmitToken>,
+    // None disables the admin endpoints, unless config_approval admins have their own tokens
+    pub admin_token: Option<String>,
+    pub protect_operational: bool,
+    pub idempotency_window: Duration,
+    pub max_json_depth: usize,
+    pub strict_payload: bool,
+    pub configuration: crate::Configuration,
 }
 
+// Idempotency cache: key -> (original response body, stored_at)
+static IDEMPOTENCY_CACHE: Lazy<Mutex<HashMap<String, (serde_json::Value, Instant)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
 // Query parameter for token
 #[derive(serde::Deserialize)]
 struct QueryToken {
     token: String,
+    // input mapping for sources sharing a token, submit tokens may configure their own
+    #[serde(default)]
+    mapping: Option<String>,
+    // url the submission outcome is posted to, see callbacks
+    #[serde(default)]
+    callback: Option<String>,
+}
+
+// Token sent as query parameter, bearer token or basic auth password (any user name)
+fn presented_tokens(req: &HttpRequest) -> Vec<String> {
+    use base64::Engine;
+
+    let mut tokens = vec![];
+    if let Ok(q) = web::Query::<QueryToken>::from_query(req.query_string()) {
+        tokens.push(q.token.clone());
+    }
+    let Some(header) = req.headers().get("Authorization").and_then(|h| h.to_str().ok()) else {
+        return tokens;
+    };
+    if let Some(bearer) = header.strip_prefix("Bearer ") {
+        tokens.push(bearer.trim().to_string());
+    }
+    if let Some(basic) = header.strip_prefix("Basic ") {
+        if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(basic.trim()) {
+            let decoded = String::from_utf8_lossy(&decoded);
+            if let Some((_, pw)) = decoded.split_once(':') {
+                tokens.push(pw.to_string());
+            }
+        }
+    }
+    tokens
+}
+
+// Admin the request authenticates as: "admin" for the admin token, the name for tokens of config_approval admins.
+// The submit tokens never count, DIVERA and everyone with access to its account knows them.
+fn admin_name(req: &HttpRequest, state: &AppState) -> Option<String> {
+    presented_tokens(req).into_iter().find_map(|token| {
+        let admin_token_valid = crate::tokens::valid_now(&None, &state.configuration.admin_token_valid_until);
+        if state.admin_token.as_deref() == Some(token.as_str()) && admin_token_valid {
+            Some("admin".to_string())
+        } else {
+            crate::approval::admin(&token)
+        }
+    })
+}
+
+// Admin staging, applying or approving a change that needs a second admin: with config_approval only the
+// admins' own tokens count, the shared admin_token would pass for another person
+fn approving_admin(req: &HttpRequest, state: &AppState) -> Result<String, HttpResponse> {
+    if !crate::approval::enabled() {
+        return Ok(admin_name(req, state).unwrap_or_default());
+    }
+    match presented_tokens(req).into_iter().find_map(|token| crate::approval::admin(&token)) {
+        Some(name) => Ok(name),
+        None => {
+            audit(req, "config_approval_denied");
+            Err(problem(StatusCode::FORBIDDEN, "own-admin-token-required", "With config_approval, use your own token from config_approval.admins"))
+        }
+    }
+}
+
+fn is_admin(req: &HttpRequest, state: &AppState) -> bool {
+    admin_name(req, state).is_some()
+}
+
+fn peer(req: &HttpRequest) -> String {
+    crate::lockout::client_addr(req.peer_addr(), req.headers())
+}
+
+fn audit(req: &HttpRequest, action: &str) {
+    if action == "auth_failure" {
+        let endpoint = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+        crate::prometheus::inc("auth_failures_total", "Rejected tokens by endpoint", &[("endpoint", &endpoint)]);
+        crate::lockout::failure(&peer(req));
+    }
+    crate::audit::record(action, &peer(req), &format!("{} {}", req.method(), req.path()));
+}
+
+// Admin-only endpoints: audited either way, None if the request may proceed
+fn check_admin(req: &HttpRequest, state: &AppState) -> Option<HttpResponse> {
+    if is_admin(req, state) {
+        audit(req, "admin_call");
+        return None;
+    }
+    if state.admin_token.is_none() && !crate::approval::enabled() {
+        return Some(problem(StatusCode::FORBIDDEN, "admin-disabled", "No admin_token configured, admin endpoints are disabled"));
+    }
+    audit(req, "auth_failure");
+    Some(problem(StatusCode::UNAUTHORIZED, "unauthorized", "Admin token required"))
+}
+
+// Returns an error response if operational endpoints are protected and the request is not authorized
+fn check_operational(req: &HttpRequest, state: &AppState) -> Option<HttpResponse> {
+    if !state.protect_operational || is_admin(req, state) {
+        return None;
+    }
+    audit(req, "auth_failure");
+    let mut response = problem(StatusCode::UNAUTHORIZED, "unauthorized", format!("Unauthorized access to {}", req.path()));
+    response.headers_mut().insert(
+        actix_web::http::header::WWW_AUTHENTICATE,
+        actix_web::http::header::HeaderValue::from_static("Basic realm=\"fireplan_alarm_divera\""),
+    );
+    Some(response)
 }
 
 // ----------------------
@@ -34,34 +154,68 @@ fn escape_html(s: &str) -> String {
 }
 
 #[get("/")]
-async fn root() -> impl Responder {
-    let ts = chrono::Utc::now().to_rfc3339();
+async fn root(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    let ts = crate::localtime::format(chrono::Utc::now().timestamp());
 
     // Read log files and prepare reversed HTML content (newest first)
-    let received = std::fs::read_to_string("/root/fireplan_alarm_divera_received").unwrap_or_default();
+    let received = std::fs::read_to_string(crate::paths::received_log()).unwrap_or_default();
     let received_html: String = received
         .lines()
         .rev()
         .map(|l| format!("<div class=\"line\">{}</div>", escape_html(l)))
         .collect();
 
-    let submitted = std::fs::read_to_string("/root/fireplan_alarm_divera_submitted").unwrap_or_default();
+    let submitted = std::fs::read_to_string(crate::paths::submitted_log()).unwrap_or_default();
     let submitted_html: String = submitted
         .lines()
         .rev()
         .map(|l| format!("<div class=\"line\">{}</div>", escape_html(l)))
         .collect();
 
+    let tz = crate::aao::timezone(&state.configuration.timezone);
+    let stats = match web::block(move || {
+        let history = crate::history::load();
+        crate::stats::compute(&crate::export::operations(&history), &history, None, tz)
+    })
+    .await
+    {
+        Ok(stats) => stats,
+        Err(e) => return problem(StatusCode::INTERNAL_SERVER_ERROR, "history-failed", e.to_string()),
+    };
+    let chart_html = crate::stats::month_chart_html(&stats);
+    let latency = stats
+        .average_dispatch_latency_secs
+        .map(|s| format!("{:.1} s", s))
+        .unwrap_or_else(|| "-".to_string());
+    let total = stats.total;
+
+    let branding = state.configuration.branding.clone().unwrap_or_default();
+    let t = |key| branding.text(key);
+    let (lang, background, accent) = (branding.lang(), branding.background_color(), branding.accent_color());
+    let department = escape_html(&branding.department());
+    // the log panel streams /admin/logs with the token the page was opened with
+    let query = serde_json::to_string(req.query_string())
+        .unwrap_or_else(|_| "\"\"".to_string())
+        .replace("</", "<\\/");
+    let logo_html = branding
+        .logo_url()
+        .map(|url| format!(r#"<img class="logo" src="{}" alt="" />"#, escape_html(url)))
+        .unwrap_or_default();
+
     let html = format!(r#"<!doctype html>
-<html lang="en">
+<html lang="{lang}">
 <head>
   <meta charset="utf-8" />
   <meta name="viewport" content="width=device-width, initial-scale=1" />
-  <title>Fireplan IMAP</title>
+  <title>{department}</title>
   <style>
-    body {{ font-family: system-ui, -apple-system, Segoe UI, Roboto, Ubuntu, Cantarell, Noto Sans, Helvetica, Arial, "Apple Color Emoji", "Segoe UI Emoji"; background: #0f172a; color: #e2e8f0; display: grid; place-items: center; min-height: 100vh; margin: 0; }}
+    body {{ font-family: system-ui, -apple-system, Segoe UI, Roboto, Ubuntu, Cantarell, Noto Sans, Helvetica, Arial, "Apple Color Emoji", "Segoe UI Emoji"; background: {background}; color: #e2e8f0; display: grid; place-items: center; min-height: 100vh; margin: 0; }}
     .card {{ background: #111827; border: 1px solid #1f2937; border-radius: 12px; padding: 28px 32px; box-shadow: 0 10px 30px rgba(0,0,0,.4); max-width: 1200px; width: 100%; box-sizing: border-box; }}
-    h1 {{ margin: 0 0 12px; font-size: 36px; letter-spacing: .5px; }}
+    h1 {{ margin: 0 0 12px; font-size: 36px; letter-spacing: .5px; border-left: 6px solid {accent}; padding-left: 12px; display: flex; align-items: center; gap: 16px; }}
+    .logo {{ height: 48px; }}
     p {{ margin: 8px 0 0; color: #cbd5e1; }}
     code {{ background: #0b1220; padding: 2px 6px; border-radius: 6px; }}
     small {{ color: #94a3b8; display: block; margin-top: 12px; }}
@@ -74,27 +228,71 @@ async fn root() -> impl Responder {
     .panel h2 {{ margin: 0 0 8px; font-size: 18px; color: #cbd5e1; }}
     .logbox {{ background: #0b1220; border: 1px solid #1f2937; border-radius: 8px; padding: 10px; max-height: 500px; overflow: auto; font: 13px/1.45 ui-monospace, SFMono-Regular, Menlo, Consolas, "Liberation Mono", monospace; }}
     .line {{ white-space: pre; color: #e5e7eb; }}
+    .chart {{ display: flex; align-items: flex-end; gap: 8px; height: 160px; padding-top: 8px; }}
+    .barcol {{ flex: 1; display: flex; flex-direction: column; justify-content: flex-end; align-items: center; height: 100%; font-size: 11px; color: #94a3b8; }}
+    .barval {{ width: 100%; background: #3b82f6; border-radius: 4px 4px 0 0; min-height: 2px; }}
+    .barcol b {{ color: #e5e7eb; }}
   </style>
 </head>
 <body>
   <div class="card">
-    <h1>Howdy partner 👋</h1>
-    <p>Welcome to the Fireplan DIVERA proxy service. Your <a href="/metrics">server</a> is up and running over <code>HTTPS</code>.</p>
-    <small class="status"><span class="dot"></span> Healthy · {ts}</small>
+    <h1>{logo_html}{department}</h1>
+    <p>{welcome} <code>HTTPS</code> · <a href="/metrics">{server}</a></p>
+    <small class="status"><span class="dot"></span> {healthy} · {ts}</small>
 
     <div class="columns">
       <div class="panel">
-        <h2>Received</h2>
+        <h2>{received_title}</h2>
         <div class="logbox">{received_html}</div>
       </div>
       <div class="panel">
-        <h2>Submitted</h2>
+        <h2>{submitted_title}</h2>
         <div class="logbox">{submitted_html}</div>
       </div>
     </div>
+
+    <div class="panel" style="margin-top: 16px;">
+      <h2>{statistics}</h2>
+      <p>{total} {operations} · {dispatch_latency} {latency} · <a href="/api/stats">/api/stats</a></p>
+      {chart_html}
+    </div>
+
+    <div class="panel" style="margin-top: 16px;">
+      <h2>{log_title}</h2>
+      <div class="logbox" id="log"></div>
+    </div>
   </div>
+  <script>
+    const query = {query};
+    const log = document.getElementById("log");
+    const logs = new EventSource("/admin/logs?lines=100" + (query ? "&" + query : ""));
+    logs.onmessage = (m) => {{
+      for (const text of m.data.split("\n")) {{
+        const line = document.createElement("div");
+        line.className = "line";
+        line.textContent = text;
+        log.appendChild(line);
+      }}
+      while (log.childElementCount > 500) log.removeChild(log.firstChild);
+      log.scrollTop = log.scrollHeight;
+    }};
+    logs.onerror = () => {{
+      if (!log.childElementCount) {{ log.textContent = "{admin_required}"; logs.close(); }}
+    }};
+  </script>
 </body>
-</html>"#);
+</html>"#,
+        log_title = t("log"),
+        admin_required = t("admin_required"),
+        welcome = t("welcome"),
+        server = t("server"),
+        healthy = t("healthy"),
+        received_title = t("received"),
+        submitted_title = t("submitted"),
+        statistics = t("statistics"),
+        operations = t("operations"),
+        dispatch_latency = t("dispatch_latency"),
+    );
 
     HttpResponse::Ok()
         .content_type("text/html; charset=utf-8")
@@ -107,10 +305,22 @@ async fn health() -> impl Responder {
     HttpResponse::Ok().json(serde_json::json!({"status":"OK","timestamp": ts}))
 }
 
+// READY, or 503 while STARTING or DEGRADED with the reasons
 #[get("/ready")]
 async fn ready() -> impl Responder {
     let ts = chrono::Utc::now().to_rfc3339();
-    HttpResponse::Ok().json(serde_json::json!({"status":"READY","timestamp": ts}))
+    let report = crate::health::report();
+    let body = serde_json::json!({
+        "status": report.status,
+        "timestamp": ts,
+        "reasons": report.reasons,
+        "components": report.components,
+    });
+    if report.status == crate::health::Readiness::Ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
 }
 
 #[get("/version")]
@@ -119,12 +329,29 @@ async fn version() -> impl Responder {
 }
 
 #[get("/status")]
-async fn status() -> impl Responder { HttpResponse::Ok().json(serde_json::json!({"status":"ok"})) }
+async fn status(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().json(serde_json::json!({"status":"ok"}))
+}
+
+#[get("/api/buildinfo")]
+async fn buildinfo(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().json(crate::buildinfo::info(&state.configuration))
+}
 
 #[get("/time")]
 async fn time() -> impl Responder {
     let now = chrono::Utc::now().to_rfc3339();
-    HttpResponse::Ok().json(serde_json::json!({"utc": now}))
+    HttpResponse::Ok().json(serde_json::json!({
+        "utc": now,
+        "local": crate::localtime::now(),
+        "timezone": crate::localtime::timezone().name(),
+    }))
 }
 
 fn fmt_bytes_gib_mib(bytes: u64) -> String {
@@ -146,7 +373,10 @@ fn gauge_html(percent: f64) -> String {
 }
 
 #[get("/metrics")]
-async fn metrics() -> impl Responder {
+async fn metrics(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
     use sysinfo::{System, CpuRefreshKind, RefreshKind, MemoryRefreshKind, ProcessRefreshKind, Disks, Components};
 
     let refresh = RefreshKind::everything()
@@ -168,6 +398,20 @@ async fn metrics() -> impl Responder {
 
     let ts = chrono::Utc::now().to_rfc3339();
 
+    let fmt_secs = |v: Option<i64>| v.map(|s| format!("{} s", s)).unwrap_or_else(|| "-".to_string());
+    // a full history read, over the network with PostgreSQL, kept off the async workers
+    let (latency_p50, latency_p95) = match web::block(|| crate::stats::latency_percentiles(&crate::history::load())).await {
+        Ok(percentiles) => percentiles,
+        Err(e) => return problem(StatusCode::INTERNAL_SERVER_ERROR, "history-failed", e.to_string()),
+    };
+    let latency_p50 = fmt_secs(latency_p50);
+    let latency_p95 = fmt_secs(latency_p95);
+    let pipeline_panics = crate::supervisor::panic_count();
+    let pipeline_events_html: String = crate::stats::pipeline_counts()
+        .iter()
+        .map(|(event, count)| format!("<li>{}: {}</li>", event, count))
+        .collect();
+
     let total_mem_fmt = fmt_bytes_gib_mib(total_mem);
     let used_mem_fmt = fmt_bytes_gib_mib(used_mem);
     let total_swap_fmt = fmt_bytes_gib_mib(total_swap);
@@ -186,7 +430,7 @@ async fn metrics() -> impl Responder {
 
     for disk in disks.iter() {
         let mount = disk.mount_point().to_string_lossy().to_string();
-        let fs = String::from_utf8_lossy(disk.file_system().as_bytes());
+        let fs = disk.file_system().to_string_lossy();
         let total = disk.total_space();
         let free = disk.available_space();
         let used = total.saturating_sub(free);
@@ -302,6 +546,20 @@ async fn metrics() -> impl Responder {
             {temps_html}
           </ul>
         </div>
+        <div class="item">
+          <h2>Alarm latency</h2>
+          <ul>
+            <li>Leitstelle → Fireplan p50: {latency_p50}</li>
+            <li>Leitstelle → Fireplan p95: {latency_p95}</li>
+          </ul>
+        </div>
+        <div class="item">
+          <h2>Pipeline</h2>
+          <ul>
+            <li>Recovered panics: {pipeline_panics}</li>
+            {pipeline_events_html}
+          </ul>
+        </div>
       </div>
       <small class="muted"><a href="/">Back to Home</a></small>
     </div>
@@ -315,52 +573,182 @@ async fn metrics() -> impl Responder {
 }
 
 #[get("/echo/{msg}")]
-async fn echo(path: web::Path<String>) -> impl Responder { HttpResponse::Ok().body(path.into_inner()) }
+async fn echo(req: HttpRequest, path: web::Path<String>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().body(path.into_inner())
+}
 
 #[get("/help")]
 async fn help_page() -> impl Responder {
-    HttpResponse::Ok().body("Use /, /health, /ready, /version, /status, /time, /metrics, /echo/{msg}, /help, /ping")
+    HttpResponse::Ok().body(
+        "Use /, /health, /ready, /version, /status, /time, /metrics, /echo/{msg}, /help, /ping, /submit, /fe2, /manual, \
+         /api/submissions/{id}, /api/selftest, /api/alarms/last, /api/alarms/{einsatznr}/cancel, /monitor, /api/map.svg, \
+         /api/alarms.ics, /api/alarms.csv, /api/stats, /api/audit, /api/buildinfo, /api/trace, /api/dedup, /api/responses, \
+         /api/responses/stream, /api/roster, /api/outbox, /api/outbox/flush, /api/outbox/{id}, /api/tokens, \
+         /api/tokens/{id}, /api/tokens/pending/{id}/approve, /api/config, /api/config-schema, /api/config/validate, \
+         /api/config/stage, /api/config/apply, /api/config/rollback, /api/tenants, /admin/config, /admin/logs, /admin/backup",
+    )
 }
 
 #[get("/ping")]
 async fn ping() -> impl Responder { HttpResponse::Ok().body("pong") }
 
+impl AppState {
+    // The configured or minted submit token, None if it is unknown or outside its validity window
+    fn submit_token(&self, token: &str) -> Option<crate::SubmitToken> {
+        self.submit_tokens
+            .iter()
+            .find(|t| t.token == token && crate::tokens::valid_now(&t.valid_from, &t.valid_until))
+            .cloned()
+            .or_else(|| crate::tokens::find(token))
+    }
+
+    fn is_auth_token(&self, token: &str) -> bool {
+        token == self.auth_token && crate::tokens::valid_now(&None, &self.configuration.auth_token_valid_until)
+    }
+}
+
+// Standort and profile of alarms submitted with this token, None if the token is unknown
+fn route_for_token(state: &AppState, token: &str) -> Option<crate::Route> {
+    if state.is_auth_token(token) {
+        Some(crate::Route::default())
+    } else {
+        state.submit_token(token).map(|t| crate::Route {
+            standort: t.standort.clone(),
+            profile: t.profile.clone(),
+        })
+    }
+}
+
+// An alarm on /submit or /fe2 with a valid token in the query or Authorization header, let through a lockout
+pub fn is_valid_intake(req: &HttpRequest) -> bool {
+    if req.path() != "/submit" && req.path() != "/fe2" {
+        return false;
+    }
+    let Some(state) = req.app_data::<web::Data<AppState>>() else {
+        return false;
+    };
+    presented_tokens(req).iter().any(|token| route_for_token(state, token).is_some())
+}
+
 #[post("/submit")]
 async fn submit(
+    req: HttpRequest,
     query: web::Query<QueryToken>,
     body: web::Bytes,
     state: web::Data<AppState>,
 ) -> impl Responder {
-    if query.token != state.auth_token {
-        error!("Invalid auth token");
-        return HttpResponse::Unauthorized().json(serde_json::json!({
-            "error": "Unauthorized",
-        }));
+    let Some(route) = route_for_token(&state, &query.token) else {
+        audit(&req, "auth_failure");
+        return problem(StatusCode::UNAUTHORIZED, "unauthorized", "Invalid auth token");
+    };
+    let mapping_name = state
+        .submit_token(&query.token)
+        .and_then(|t| t.mapping)
+        .or(query.mapping.clone());
+    if let Err(e) = query.callback.as_deref().map(crate::callback::check).transpose() {
+        return problem(StatusCode::BAD_REQUEST, "invalid-callback", e);
     }
 
     info!("Received /submit request with body length: {}", body.len());
-    info!("Received: {}", String::from_utf8_lossy(&body));
+    info!("Received: {}", crate::privacy::redact(&String::from_utf8_lossy(&body)));
+
+    // JSON by default, form-encoded and multipart bodies for sources that cannot send JSON
+    let content_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let is_form = mime == "application/x-www-form-urlencoded" || mime == "multipart/form-data";
+
+    if !is_form && json_too_deep(&body, state.max_json_depth) {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            "json-too-deep",
+            format!("JSON nesting deeper than {} levels", state.max_json_depth),
+        );
+    }
+
+    let parsed = match mime.as_str() {
+        "application/x-www-form-urlencoded" => crate::form::from_urlencoded(&body),
+        "multipart/form-data" => crate::form::from_multipart(&content_type, &body),
+        _ => serde_json::from_slice::<serde_json::Value>(&body).map_err(ParseError::from),
+    };
+    let mapping = match mapping_name {
+        Some(name) => match state.configuration.input_mappings.iter().flatten().find(|m| m.name == name) {
+            Some(m) => Some(m),
+            None => return problem(StatusCode::BAD_REQUEST, "unknown-mapping", format!("Unknown input mapping {}", name)),
+        },
+        None => None,
+    };
+    let parsed = parsed.map(|value| match mapping {
+        Some(m) => m.apply(&value),
+        None => value,
+    });
+    let validated = parsed
+        .map_err(|e| vec![crate::payload::FieldError { field: String::new(), message: e.to_string() }])
+        .and_then(|value| crate::payload::validate(&value, state.strict_payload))
+        .and_then(|data| match &state.configuration.payload_guard {
+            Some(guard) => match crate::payload::guard(&data, guard) {
+                errors if errors.is_empty() => Ok(data),
+                errors => {
+                    audit(&req, "payload_rejected");
+                    Err(errors)
+                }
+            },
+            None => Ok(data),
+        });
 
-    match serde_json::from_slice::<crate::SubmitPayload>(&body) {
+    match validated {
         Ok(data) => {
-            // Append a line with timestamp and title to the receive log file
-            let ts = chrono::Utc::now().to_rfc3339();
-            let line = format!("{}\t{}\n", ts, data.title);
-            if let Err(e) = OpenOptions::new().create(true).append(true).open("/root/fireplan_alarm_divera_received").and_then(|mut f| f.write_all(line.as_bytes())) {
-                error!("Failed to write receive log: {}", e);
+            // Idempotency-Key header, or DIVERA id + ts_update, so webhook retries don't run the pipeline twice;
+            // payloads without either are never taken for a repeat
+            let key = match req.headers().get("Idempotency-Key").and_then(|h| h.to_str().ok()) {
+                Some(k) => Some(format!("{}:{}", route.standort, k)),
+                None if data.id != 0 => Some(format!("{}:{}-{}", route.standort, data.id, data.ts_update)),
+                None => None,
+            };
+
+            // held until the response is cached, so a retry arriving meanwhile gets it instead of submitting again
+            let mut cache = key.as_ref().map(|_| IDEMPOTENCY_CACHE.lock().unwrap_or_else(|e| e.into_inner()));
+            if let (Some(key), Some(cache)) = (&key, cache.as_mut()) {
+                cache.retain(|_, (_, ts)| ts.elapsed() < state.idempotency_window);
+                if let Some((response, _)) = cache.get(key) {
+                    info!("Repeated submission with idempotency key {}, returning original response", key);
+                    return HttpResponse::Ok()
+                        .insert_header(("Idempotent-Replayed", "true"))
+                        .json(response);
+                }
             }
 
-            let _ = crate::send_event(crate::Event::Submit(data.clone()));
-            info!("Received: {:?}", data);
-            HttpResponse::Ok().json(serde_json::json!({
-                "status": "submitted"
-            }))
+            crate::log_received(&data);
+
+            let incoming = crate::Incoming::new(data.clone(), route.clone());
+            let id = incoming.id.clone();
+            if let Some(url) = &query.callback {
+                crate::callback::register(&id, url);
+            }
+            let _ = crate::send_event(crate::Event::Submit(incoming));
+            info!("Received for Standort {} as submission {}: {}", route.standort, id, crate::privacy::redact(&format!("{:?}", data)));
+            let response = serde_json::json!({
+                "status": "submitted",
+                "id": id,
+            });
+            if let (Some(key), Some(cache)) = (key, cache.as_mut()) {
+                cache.insert(key, (response.clone(), Instant::now()));
+            }
+            drop(cache);
+            HttpResponse::Ok().json(response)
         },
-        Err(e) => {
-            error!("Invalid payload: {}", e);
+        Err(errors) => {
             let example = serde_json::json!({
+                "schema_version": crate::payload::SCHEMA_VERSION,
                 "id": 247,
-                "number": "E-123",
+                "foreign_id": "E-123",
                 "title": "FEUER3",
                 "text": "Unklare Rauchentwicklung im Hafen",
                 "address": "Hauptstraße 247, 12345 Musterstadt",
@@ -373,17 +761,1282 @@ async fn submit(
                 "ts_create": 1769601252,
                 "ts_update": 1769601252
             });
-            HttpResponse::BadRequest().json(serde_json::json!({
-                "error": format!("JSON parse error: {}", e),
-                "example": example,
+            let detail = errors
+                .iter()
+                .map(|e| if e.field.is_empty() { e.message.clone() } else { format!("{}: {}", e.field, e.message) })
+                .collect::<Vec<_>>()
+                .join("; ");
+            problem_with(
+                StatusCode::BAD_REQUEST,
+                "invalid-payload",
+                detail,
+                serde_json::json!({ "errors": errors, "example": example }),
+            )
+        }
+    }
+}
+
+// Alamos FE2 external interface; the token comes as query parameter or in the "authorization" field
+#[post("/fe2")]
+async fn fe2(req: HttpRequest, body: web::Bytes, state: web::Data<AppState>) -> impl Responder {
+    if json_too_deep(&body, state.max_json_depth) {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            "json-too-deep",
+            format!("JSON nesting deeper than {} levels", state.max_json_depth),
+        );
+    }
+    let value = match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(v) => v,
+        Err(e) => return problem(StatusCode::BAD_REQUEST, "invalid-payload", format!("JSON parse error: {}", e)),
+    };
+    let token = web::Query::<QueryToken>::from_query(req.query_string())
+        .map(|q| q.token.clone())
+        .ok()
+        .or_else(|| crate::fe2::authorization(&value))
+        .unwrap_or_default();
+    let Some(route) = route_for_token(&state, &token) else {
+        audit(&req, "auth_failure");
+        return problem(StatusCode::UNAUTHORIZED, "unauthorized", "Invalid auth token");
+    };
+
+    let marker = state.configuration.format.clone().unwrap_or_default().einsatzmittel_marker().to_string();
+    match crate::fe2::to_payload(&value, &marker) {
+        Ok(data) => {
+            crate::log_received(&data);
+            let incoming = crate::Incoming::new(data.clone(), route.clone());
+            let id = incoming.id.clone();
+            let _ = crate::send_event(crate::Event::Submit(incoming));
+            info!("Received FE2 alarm for Standort {} as submission {}: {}", route.standort, id, crate::privacy::redact(&format!("{:?}", data)));
+            HttpResponse::Ok().json(serde_json::json!({ "status": "submitted", "id": id }))
+        }
+        Err(e) => problem(StatusCode::BAD_REQUEST, "invalid-payload", e.to_string()),
+    }
+}
+
+// Manual alarm entry for when the Leitstelle alarms by phone; same tokens as /submit
+#[get("/manual")]
+async fn manual_form(req: HttpRequest, query: web::Query<QueryToken>, state: web::Data<AppState>) -> impl Responder {
+    if route_for_token(&state, &query.token).is_none() {
+        audit(&req, "auth_failure");
+        return problem(StatusCode::UNAUTHORIZED, "unauthorized", "Invalid auth token");
+    }
+    manual_page(&state, &query.token, None)
+}
+
+#[post("/manual")]
+async fn manual_submit(
+    req: HttpRequest,
+    query: web::Query<QueryToken>,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let Some(route) = route_for_token(&state, &query.token) else {
+        audit(&req, "auth_failure");
+        return problem(StatusCode::UNAUTHORIZED, "unauthorized", "Invalid auth token");
+    };
+    let marker = state.configuration.format.clone().unwrap_or_default().einsatzmittel_marker().to_string();
+    match crate::manual::to_payload(&body, &marker) {
+        Ok(data) => {
+            audit(&req, "manual_alarm");
+            crate::log_received(&data);
+            let incoming = crate::Incoming::new(data.clone(), route.clone());
+            let id = incoming.id.clone();
+            if let Err(e) = crate::send_event(crate::Event::Submit(incoming)) {
+                error!("Manual alarm for Standort {} not accepted: {}", route.standort, e);
+                return manual_page(&state, &query.token, Some(Err("the alarm pipeline is not running")));
+            }
+            info!("Manual alarm for Standort {} as submission {}: {}", route.standort, id, crate::privacy::redact(&data.title));
+            manual_page(&state, &query.token, Some(Ok(&id)))
+        }
+        Err(e) => problem(StatusCode::BAD_REQUEST, "invalid-payload", e.to_string()),
+    }
+}
+
+// The entry form, after a submission with its id and a link to its processing state, or the reason it
+// could not be taken and the form again
+fn manual_page(state: &AppState, token: &str, submitted: Option<Result<&str, &str>>) -> HttpResponse {
+    let branding = state.configuration.branding.clone().unwrap_or_default();
+    let t = |key| branding.text(key);
+    let (lang, background, accent) = (branding.lang(), branding.background_color(), branding.accent_color());
+    let title = escape_html(&format!("{} - {}", t("manual"), branding.department()));
+    let token_query = serde_urlencoded::to_string([("token", token)]).unwrap_or_default();
+    let action = escape_html(&format!("/manual?{}", token_query));
+    let rics_html: String = state
+        .configuration
+        .rics
+        .iter()
+        .map(|r| format!(r#"<label><input type="checkbox" name="ric" value="{0}" /> {0}</label>"#, escape_html(&r.text)))
+        .collect();
+    let notice = match submitted {
+        Some(Ok(id)) => {
+            let status_url = format!("/api/submissions/{}?{}", id, token_query);
+            format!(
+                r#"<p class="ok">{}: <a href="{}">{}</a></p><p><a href="{}">{}</a></p>"#,
+                t("triggered"),
+                escape_html(&status_url),
+                escape_html(id),
+                action,
+                t("another")
+            )
+        }
+        Some(Err(reason)) => format!(r#"<p class="failed">{}: {}</p>"#, t("not_triggered"), escape_html(reason)),
+        None => String::new(),
+    };
+    let form = if matches!(submitted, Some(Ok(_))) {
+        String::new()
+    } else {
+        format!(
+            r#"<form method="post" action="{action}">
+      <label>{stichwort}<input name="stichwort" required autofocus /></label>
+      <label>{address}<input name="adresse" placeholder="Hauptstraße 1, 12345 Musterstadt" /></label>
+      <label>{coordinates}<input name="koordinaten" placeholder="49.0069,8.4037" /></label>
+      <label>{einsatznummer}<input name="einsatznummer" /></label>
+      <label>{text}<textarea name="text" rows="4"></textarea></label>
+      <fieldset><legend>{rics}</legend>{rics_html}</fieldset>
+      <button type="submit">{trigger}</button>
+    </form>"#,
+            stichwort = t("stichwort"),
+            address = t("address"),
+            coordinates = t("coordinates"),
+            einsatznummer = t("einsatznummer"),
+            text = t("text"),
+            rics = t("rics"),
+            trigger = t("trigger"),
+        )
+    };
+
+    let html = format!(r#"<!doctype html>
+<html lang="{lang}">
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <title>{title}</title>
+  <style>
+    body {{ font-family: system-ui, -apple-system, Segoe UI, Roboto, Ubuntu, Cantarell, Noto Sans, Helvetica, Arial; background: {background}; color: #e2e8f0; display: grid; place-items: center; min-height: 100vh; margin: 0; }}
+    .card {{ background: #111827; border: 1px solid #1f2937; border-radius: 12px; padding: 28px 32px; max-width: 720px; width: 100%; box-sizing: border-box; }}
+    h1 {{ margin: 0 0 16px; font-size: 30px; border-left: 6px solid {accent}; padding-left: 12px; }}
+    label {{ display: block; margin: 10px 0; }}
+    input, textarea {{ display: block; width: 100%; box-sizing: border-box; margin-top: 4px; padding: 8px; font-size: 18px; background: #0b1220; color: #e2e8f0; border: 1px solid #1f2937; border-radius: 6px; }}
+    fieldset {{ border: 1px solid #1f2937; border-radius: 8px; }}
+    fieldset label {{ display: inline-block; margin: 6px 16px 6px 0; }}
+    fieldset input {{ display: inline; width: auto; }}
+    button {{ margin-top: 16px; padding: 12px 24px; font-size: 20px; font-weight: 700; background: {accent}; color: #fff; border: 0; border-radius: 8px; cursor: pointer; }}
+    .ok {{ color: #22c55e; font-size: 22px; }}
+    .failed {{ color: #ef4444; font-size: 22px; }}
+    a {{ color: #93c5fd; }}
+  </style>
+</head>
+<body>
+  <div class="card">
+    <h1>{heading}</h1>
+    {notice}
+    {form}
+  </div>
+</body>
+</html>"#,
+        heading = t("manual"),
+    );
+
+    let code = if matches!(submitted, Some(Err(_))) { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+    HttpResponse::build(code)
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// Check JSON nesting depth before handing the body to serde, counting brackets outside of strings
+fn json_too_deep(body: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in body {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+// Submit tokens (and the admin token) may query the processing state of their submissions
+fn is_submitter(req: &HttpRequest, state: &AppState) -> bool {
+    let token = web::Query::<QueryToken>::from_query(req.query_string())
+        .map(|q| q.token.clone())
+        .unwrap_or_default();
+    (!token.is_empty() && (state.is_auth_token(&token) || state.submit_token(&token).is_some()))
+        || is_admin(req, state)
+}
+
+#[get("/api/submissions/{id}")]
+async fn submission_status(req: HttpRequest, path: web::Path<String>, state: web::Data<AppState>) -> impl Responder {
+    if !is_submitter(&req, &state) {
+        audit(&req, "auth_failure");
+        return problem(StatusCode::UNAUTHORIZED, "unauthorized", "Invalid auth token");
+    }
+    let id = path.into_inner();
+    match crate::submissions::get(&id) {
+        Some(submission) => HttpResponse::Ok().json(submission),
+        None => problem(StatusCode::NOT_FOUND, "unknown-submission", format!("Unknown submission id {}", id)),
+    }
+}
+
+#[get("/api/selftest")]
+async fn selftest(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let configuration = state.configuration.clone();
+    match web::block(move || crate::selftest::run(&configuration)).await {
+        Ok(checks) => {
+            let ok = checks.iter().all(|c| c.ok);
+            HttpResponse::Ok().json(serde_json::json!({
+                "ok": ok,
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "checks": checks,
             }))
         }
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "selftest-failed", e.to_string()),
     }
 }
 
+#[derive(serde::Deserialize)]
+struct AuditQuery {
+    action: Option<String>,
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+// Audit trail, newest first, e.g. ?action=auth_failure&since=2026-01-01T00:00:00Z&limit=50
+#[get("/api/audit")]
+async fn audit_log(req: HttpRequest, query: web::Query<AuditQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let query = query.into_inner();
+    let result = web::block(move || {
+        crate::audit::query(query.action.as_deref(), query.since.as_deref(), query.limit.unwrap_or(100))
+    })
+    .await;
+    match result {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "audit-failed", e.to_string()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TraceQuery {
+    enabled: Option<bool>,
+    // minutes until tracing switches off again, defaults to 30
+    minutes: Option<i64>,
+}
+
+// Raw Fireplan/DIVERA exchange logging with masked secrets: GET shows the state,
+// POST ?enabled=true&minutes=30 switches it on for a while, ?enabled=false off
+fn trace_response() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "enabled": crate::trace::enabled(),
+        "until": crate::trace::enabled_until()
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.to_rfc3339()),
+    }))
+}
+
+#[get("/api/trace")]
+async fn trace_state(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    trace_response()
+}
+
+#[post("/api/trace")]
+async fn trace_toggle(req: HttpRequest, query: web::Query<TraceQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    if query.enabled.unwrap_or(true) {
+        let minutes = query.minutes.unwrap_or(30);
+        crate::trace::enable(minutes);
+        crate::audit::record("trace_enabled", &peer(&req), &format!("{} minutes", minutes));
+    } else {
+        crate::trace::disable();
+        crate::audit::record("trace_disabled", &peer(&req), "");
+    }
+    trace_response()
+}
+
+// Remembered (Einsatznummer, RIC) pairs, newest first; alarms for these RICs are swallowed as duplicates
+#[get("/api/dedup")]
+async fn dedup_list(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let entries: Vec<serde_json::Value> = crate::dedup::list()
+        .into_iter()
+        .map(|e| {
+            serde_json::json!({
+                "einsatznummer": e.einsatznummer,
+                "ric": e.ric,
+                "standort": e.standort,
+                "alarmed": chrono::DateTime::from_timestamp(e.alarmed, 0).map(|t| t.to_rfc3339()),
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(entries)
+}
+
+#[derive(serde::Deserialize)]
+struct DedupQuery {
+    einsatznummer: String,
+    // all RICs of the Einsatz if omitted
+    ric: Option<String>,
+}
+
+// Forget entries so the next alarm for them is sent again, e.g. ?einsatznummer=123&ric=0123456
+#[delete("/api/dedup")]
+async fn dedup_delete(req: HttpRequest, query: web::Query<DedupQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let removed = crate::dedup::remove(&query.einsatznummer, query.ric.as_deref());
+    crate::audit::record(
+        "dedup_cleared",
+        &peer(&req),
+        &format!("{} {} ({} entries)", query.einsatznummer, query.ric.as_deref().unwrap_or("*"), removed),
+    );
+    if removed == 0 {
+        return problem(StatusCode::NOT_FOUND, "unknown-dedup-entry", "No matching dedup entry");
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "removed": removed }))
+}
+
+fn token_json(token: &crate::SubmitToken, id: Option<&str>, source: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "source": source,
+        "token": crate::tokens::masked(&token.token),
+        "standort": token.standort,
+        "profile": token.profile,
+        "mapping": token.mapping,
+        "valid_from": token.valid_from,
+        "valid_until": token.valid_until,
+        "valid": crate::tokens::valid_now(&token.valid_from, &token.valid_until),
+    })
+}
+
+// Submit tokens with masked values: auth_token, configured submit_tokens and tokens minted here
+#[get("/api/tokens")]
+async fn tokens_list(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let main = crate::SubmitToken {
+        token: state.auth_token.clone(),
+        standort: crate::Route::default().standort,
+        profile: None,
+        mapping: None,
+        valid_from: None,
+        valid_until: state.configuration.auth_token_valid_until.clone(),
+    };
+    let mut tokens = vec![token_json(&main, None, "auth_token")];
+    tokens.extend(state.submit_tokens.iter().map(|t| token_json(t, None, "config")));
+    tokens.extend(crate::tokens::list().iter().map(|m| {
+        let mut json = token_json(&m.token, Some(&m.id), "minted");
+        json["token"] = serde_json::json!(crate::tokens::masked(&m.prefix));
+        json["created"] = serde_json::json!(m.created);
+        json
+    }));
+    tokens.extend(crate::tokens::pending().iter().map(|p| {
+        let mut json = token_json(&p.template, Some(&p.id), "pending");
+        json["token"] = serde_json::Value::Null;
+        json["valid"] = serde_json::json!(false);
+        json["requested_by"] = serde_json::json!(p.by);
+        json["requested"] = serde_json::json!(crate::localtime::format(p.at));
+        json
+    }));
+    HttpResponse::Ok().json(tokens)
+}
+
+#[derive(serde::Deserialize)]
+struct MintRequest {
+    // defaults to the Standort of auth_token
+    standort: Option<String>,
+    profile: Option<String>,
+    mapping: Option<String>,
+    valid_from: Option<String>,
+    valid_until: Option<String>,
+}
+
+// New random submit token, the full value is only in this answer; rotation: mint, switch the source, then
+// revoke the old token or let it expire. With config_approval guarding submit_tokens, a token with its own
+// Standort, profile or mapping waits for a second admin (202 with the id to approve).
+#[post("/api/tokens")]
+async fn tokens_mint(req: HttpRequest, body: web::Json<MintRequest>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let body = body.into_inner();
+    let routed = body.standort.is_some() || body.profile.is_some() || body.mapping.is_some();
+    let template = crate::SubmitToken {
+        token: String::new(),
+        standort: body.standort.unwrap_or_else(|| crate::Route::default().standort),
+        profile: body.profile,
+        mapping: body.mapping,
+        valid_from: body.valid_from,
+        valid_until: body.valid_until,
+    };
+    if routed && crate::approval::guards("submit_tokens") {
+        let by = match approving_admin(&req, &state) {
+            Ok(by) => by,
+            Err(denied) => return denied,
+        };
+        return match crate::tokens::request(template, &by) {
+            Ok(pending) => {
+                crate::audit::record("token_approval_requested", &by, &format!("{} for {}", pending.id, pending.template.standort));
+                HttpResponse::Accepted().json(serde_json::json!({ "pending": pending.id, "standort": pending.template.standort }))
+            }
+            Err(e) => problem(StatusCode::BAD_REQUEST, "invalid-token-request", e.to_string()),
+        };
+    }
+    let by = admin_name(&req, &state).unwrap_or_default();
+    minted_response(&by, None, web::block(move || crate::tokens::mint(template)).await)
+}
+
+// Mint a token requested by another admin, the full value is only in this answer
+#[post("/api/tokens/pending/{id}/approve")]
+async fn tokens_approve(req: HttpRequest, path: web::Path<String>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let by = match approving_admin(&req, &state) {
+        Ok(by) => by,
+        Err(denied) => return denied,
+    };
+    let id = path.into_inner();
+    let request = match crate::tokens::approve(&id, &by) {
+        Ok(Some(request)) => request,
+        Ok(None) => return problem(StatusCode::NOT_FOUND, "unknown-token", format!("No pending token {}", id)),
+        Err(e) => {
+            audit(&req, "config_approval_denied");
+            return problem(StatusCode::FORBIDDEN, "approval-required", e.to_string());
+        }
+    };
+    crate::audit::record("token_approved", &by, &format!("{} requested by {}", request.id, request.by));
+    let requested_by = request.by.clone();
+    minted_response(&by, Some(&requested_by), web::block(move || crate::tokens::mint(request.template)).await)
+}
+
+// The new token with its full value, or why it could not be minted; the audit names the admin who minted it
+// and the one who requested it when it needed approval
+fn minted_response(
+    by: &str,
+    requested_by: Option<&str>,
+    result: Result<anyhow::Result<(crate::tokens::MintedToken, String)>, actix_web::error::BlockingError>,
+) -> HttpResponse {
+    match result {
+        Ok(Ok((minted, token))) => {
+            let mut detail = format!("{} for {} ({})", minted.id, minted.token.standort, crate::tokens::masked(&token));
+            if let Some(requested_by) = requested_by {
+                detail.push_str(&format!(", requested by {}, approved by {}", requested_by, by));
+            }
+            crate::audit::record("token_minted", by, &detail);
+            let mut json = token_json(&minted.token, Some(&minted.id), "minted");
+            json["token"] = serde_json::json!(token);
+            json["created"] = serde_json::json!(minted.created);
+            HttpResponse::Created().json(json)
+        }
+        Ok(Err(e)) => problem(StatusCode::BAD_REQUEST, "invalid-token-request", e.to_string()),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "token-mint-failed", e.to_string()),
+    }
+}
+
+// Revoke a minted token at once; configured tokens are changed in the config file
+#[delete("/api/tokens/{id}")]
+async fn tokens_revoke(req: HttpRequest, path: web::Path<String>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let id = path.into_inner();
+    if !crate::tokens::revoke(&id) {
+        return problem(StatusCode::NOT_FOUND, "unknown-token", format!("No minted token {}", id));
+    }
+    crate::audit::record("token_revoked", &peer(&req), &id);
+    HttpResponse::NoContent().finish()
+}
+
+// Alarms Fireplan did not accept, oldest first, with the last failure reason
+#[get("/api/outbox")]
+async fn outbox_list(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().json(crate::outbox::list())
+}
+
+// Submit the queued alarms again, e.g. once Fireplan is reachable after an outage
+#[post("/api/outbox/flush")]
+async fn outbox_flush(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let api_key = state.configuration.fireplan_api_key.clone();
+    match web::block(move || crate::outbox::flush(&api_key)).await {
+        Ok(result) => {
+            crate::audit::record(
+                "outbox_flushed",
+                &peer(&req),
+                &format!("{} submitted, {} failed", result.submitted.len(), result.failed.len()),
+            );
+            let failed: Vec<serde_json::Value> = result
+                .failed
+                .iter()
+                .map(|(id, standort, reason)| serde_json::json!({ "id": id, "standort": standort, "reason": reason }))
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({ "submitted": result.submitted, "failed": failed }))
+        }
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "outbox-flush-failed", e.to_string()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct OutboxQuery {
+    // only the entry of this Standort, all of the submission if omitted
+    standort: Option<String>,
+}
+
+// Drop a queued alarm without submitting it, e.g. when it was alarmed by other means
+#[delete("/api/outbox/{id}")]
+async fn outbox_delete(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OutboxQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let id = path.into_inner();
+    if !crate::outbox::remove(&id, query.standort.as_deref()) {
+        return problem(StatusCode::NOT_FOUND, "unknown-outbox-entry", format!("No queued alarm {}", id));
+    }
+    crate::audit::record("outbox_deleted", &peer(&req), &id);
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(serde::Deserialize)]
+struct CancelQuery {
+    reason: Option<String>,
+    // send a "Fehlalarm / Einsatzabbruch" notification, defaults to false
+    notify: Option<bool>,
+}
+
+// Einsatz cancelled or false alarm: queued retries and pending digest notifications for it are dropped
+// and later alarms for it are not submitted, e.g. ?notify=true&reason=Fehlalarm
+#[post("/api/alarms/{einsatznr}/cancel")]
+async fn cancel_alarm(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<CancelQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let einsatznummer = path.into_inner();
+    if !crate::cancel::cancel(&einsatznummer) {
+        return problem(StatusCode::CONFLICT, "already-cancelled", format!("Einsatz {} is already cancelled", einsatznummer));
+    }
+    let query = query.into_inner();
+    let reason = query.reason.unwrap_or_else(|| "Fehlalarm / Einsatzabbruch".to_string());
+    let notify = query.notify.unwrap_or(false);
+    crate::audit::record("alarm_cancelled", &peer(&req), &format!("{}: {}", einsatznummer, reason));
+    let nr = einsatznummer.clone();
+    let result = web::block(move || {
+        let outbox = crate::outbox::discard(&nr);
+        let notifications = crate::notify::discard(&nr);
+        let alarm = crate::history::load().into_iter().rev().find(|e| e.alarm.einsatznrlst == nr).map(|e| e.alarm);
+        crate::bus::publish(crate::bus::BusEvent::Cancelled { einsatznummer: nr, reason, notify, alarm });
+        (outbox, notifications)
+    })
+    .await;
+    match result {
+        Ok((outbox, notifications)) => HttpResponse::Ok().json(serde_json::json!({
+            "einsatznummer": einsatznummer,
+            "outbox_removed": outbox,
+            "notifications_discarded": notifications,
+            "notified": notify,
+        })),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "cancel-failed", e.to_string()),
+    }
+}
+
+#[get("/api/alarms/last")]
+async fn last_alarm(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    match web::block(crate::history::last).await {
+        Ok(Some(entry)) => {
+            // weather summary for storm and flood alarms and the route from the station, shown on the monitor page
+            let extras = [
+                ("weather", crate::weather::cached(&entry.alarm.einsatznrlst)),
+                ("eta", crate::eta::cached(&entry.alarm.einsatznrlst)),
+            ];
+            let mut body = serde_json::to_value(&entry).unwrap_or_default();
+            if let Some(object) = body.as_object_mut() {
+                for (key, value) in extras {
+                    if let Some(value) = value {
+                        object.insert(key.to_string(), serde_json::Value::String(value));
+                    }
+                }
+            }
+            HttpResponse::Ok().json(body)
+        }
+        Ok(None) => problem(StatusCode::NOT_FOUND, "no-alarm", "No alarm recorded yet"),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "history-failed", e.to_string()),
+    }
+}
+
+// Headcount of the active DIVERA alarm (divera_responses)
+#[get("/api/responses")]
+async fn responses(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    match crate::responses::current() {
+        Some(headcount) => HttpResponse::Ok().json(headcount),
+        None => problem(StatusCode::NOT_FOUND, "no-responses", "No open DIVERA alarm with responses"),
+    }
+}
+
+// Members available per duty group (duty_roster)
+#[get("/api/roster")]
+async fn roster(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    match crate::roster::snapshot() {
+        Some(available) => HttpResponse::Ok().json(available),
+        None => problem(StatusCode::NOT_FOUND, "no-roster", "Duty roster not configured or not available"),
+    }
+}
+
+// Server-sent events with the headcount, sent on connect and whenever it changes ("null" without open alarm)
+#[get("/api/responses/stream")]
+async fn responses_stream(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    // (headcount last sent, None before the first event; polls since the last event)
+    let events = futures_util::stream::unfold((None, 0u32), |(sent, idle)| async move {
+        let mut idle = idle;
+        loop {
+            let current = crate::responses::current();
+            if sent.as_ref() != Some(&current) {
+                let data = serde_json::to_string(&current).unwrap_or_else(|_| "null".to_string());
+                let event = web::Bytes::from(format!("data: {}\n\n", data));
+                return Some((Ok::<_, actix_web::Error>(event), (Some(current), 0)));
+            }
+            if idle >= 15 {
+                // comment line, keeps proxies from closing the idle connection
+                return Some((Ok(web::Bytes::from_static(b": keepalive\n\n")), (sent, 0)));
+            }
+            actix_web::rt::time::sleep(Duration::from_secs(2)).await;
+            idle += 1;
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+#[derive(serde::Deserialize)]
+struct LogsQuery {
+    lines: Option<usize>,
+}
+
+// Application log for volunteers without SSH access: the last ?lines= (default 200) lines, then new ones live,
+// as server-sent events with one data line per log line
+#[get("/admin/logs")]
+async fn admin_logs(req: HttpRequest, query: web::Query<LogsQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let lines = query.lines.unwrap_or(200);
+    // (next line to send, None before the first event; polls since the last event)
+    let events = futures_util::stream::unfold((None, 0u32), move |(next, idle)| async move {
+        let mut idle = idle;
+        loop {
+            let (new, following) = crate::logtail::since(next, if next.is_none() { lines } else { usize::MAX });
+            if !new.is_empty() {
+                let data: String = new.iter().map(|l| format!("data: {}\n", l)).collect();
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(data + "\n")), (Some(following), 0)));
+            }
+            if idle >= 30 {
+                return Some((Ok(web::Bytes::from_static(b": keepalive\n\n")), (Some(following), 0)));
+            }
+            actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            idle += 1;
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+// iCalendar feed of past operations for subscription in calendar apps
+#[get("/api/alarms.ics")]
+async fn alarms_ics(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    match web::block(|| crate::export::ics(&crate::export::operations(&crate::history::load()))).await {
+        Ok(ics) => HttpResponse::Ok().content_type("text/calendar; charset=utf-8").body(ics),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "history-failed", e.to_string()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RangeQuery {
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+}
+
+// CSV export of past operations for the yearly Einsatzstatistik, optional ?from=YYYY-MM-DD&to=YYYY-MM-DD
+#[get("/api/alarms.csv")]
+async fn alarms_csv(req: HttpRequest, range: web::Query<RangeQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    let tz = crate::aao::timezone(&state.configuration.timezone);
+    let range = range.into_inner();
+    let result = web::block(move || {
+        let operations = crate::export::operations(&crate::history::load());
+        crate::export::csv(&crate::export::filter_range(operations, range.from, range.to, tz), tz)
+    })
+    .await;
+    match result {
+        Ok(Ok(csv)) => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(("Content-Disposition", "attachment; filename=\"einsaetze.csv\""))
+            .body(csv),
+        Ok(Err(e)) => problem(StatusCode::INTERNAL_SERVER_ERROR, "export-failed", e.to_string()),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "export-failed", e.to_string()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct StatsQuery {
+    year: Option<i32>,
+}
+
+// Aggregates over the alarm history, optionally limited to one year (?year=2025)
+#[get("/api/stats")]
+async fn alarm_stats(req: HttpRequest, query: web::Query<StatsQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    let tz = crate::aao::timezone(&state.configuration.timezone);
+    let year = query.year;
+    let result = web::block(move || {
+        let history = crate::history::load();
+        crate::stats::compute(&crate::export::operations(&history), &history, year, tz)
+    })
+    .await;
+    match result {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "history-failed", e.to_string()),
+    }
+}
+
+// JSON Schema of the config file, for external editors
+#[get("/api/config-schema")]
+async fn config_schema(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().json(crate::schema::schema())
+}
+
+// Dry validation of an uploaded config file (TOML body), nothing is applied
+#[post("/api/config/validate")]
+async fn config_validate(req: HttpRequest, body: web::Bytes, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let content = String::from_utf8_lossy(&body).to_string();
+    match web::block(move || crate::schema::validate(&content)).await {
+        Ok(validation) => HttpResponse::Ok().json(validation),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "validation-failed", e.to_string()),
+    }
+}
+
+// Current config file with masked secrets and the staged change, for the config editor
+#[get("/api/config")]
+async fn config_get(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    match crate::config_edit::current() {
+        Ok(content) => HttpResponse::Ok().json(serde_json::json!({
+            "content": crate::config_edit::redact(&content),
+            "staged": crate::config_edit::staged(),
+            "pending": crate::config_edit::pending(),
+        })),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "config-unreadable", format!("{:#}", e)),
+    }
+}
+
+// Validate a changed config (TOML body), compare it with the running one on the recent alarms and stage it when valid
+#[post("/api/config/stage")]
+async fn config_stage(req: HttpRequest, body: web::Bytes, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let by = match approving_admin(&req, &state) {
+        Ok(by) => by,
+        Err(denied) => return denied,
+    };
+    let content = String::from_utf8_lossy(&body).to_string();
+    match web::block(move || crate::config_edit::stage(&content, &by)).await {
+        Ok(Ok(result)) => {
+            audit(&req, "config_staged");
+            HttpResponse::Ok().json(result)
+        }
+        Ok(Err(e)) => problem(StatusCode::BAD_REQUEST, "invalid-config", format!("{:#}", e)),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "stage-failed", e.to_string()),
+    }
+}
+
+#[delete("/api/config/stage")]
+async fn config_discard(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let Some((staged_by, guarded)) = crate::config_edit::discard() else {
+        return problem(StatusCode::NOT_FOUND, "nothing-staged", "No staged config change");
+    };
+    let mut detail = format!("change by {}", staged_by);
+    if !guarded.is_empty() {
+        detail.push_str(&format!(" to {}", guarded.join(", ")));
+    }
+    crate::audit::record("config_discarded", &admin_name(&req, &state).unwrap_or_default(), &detail);
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(serde::Deserialize)]
+struct ApplyQuery {
+    // hash of the pending change the approving admin reviewed, from GET /api/config
+    hash: Option<String>,
+}
+
+// Write the staged change to the config file and restart; a file that does not load is rolled back at once.
+// Changes waiting for approval are applied only by another admin than the one who staged them, with the
+// hash of the change they reviewed.
+#[post("/api/config/apply")]
+async fn config_apply(req: HttpRequest, query: web::Query<ApplyQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    if crate::config_edit::staged().is_none() {
+        return problem(StatusCode::CONFLICT, "nothing-staged", "No staged config change");
+    }
+    let by = match approving_admin(&req, &state) {
+        Ok(by) => by,
+        Err(denied) => return denied,
+    };
+    let hash = query.into_inner().hash;
+    if let Some(reason) = crate::config_edit::approval_denied(&by, hash.as_deref()) {
+        audit(&req, "config_approval_denied");
+        return problem(StatusCode::FORBIDDEN, "approval-required", reason);
+    }
+    match web::block(move || crate::config_edit::apply(&by, hash.as_deref())).await {
+        Ok(Ok(backup)) => {
+            audit(&req, "config_applied");
+            crate::config_edit::restart();
+            HttpResponse::Ok().json(serde_json::json!({ "applied": true, "backup": backup, "restarting": true }))
+        }
+        Ok(Err(e)) => problem(StatusCode::INTERNAL_SERVER_ERROR, "apply-failed", format!("{:#}", e)),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "apply-failed", e.to_string()),
+    }
+}
+
+// Swap the config file with the backup of the last apply and restart; a rollback of guarded keys is staged
+// for the approval of a second admin (202 with the pending change)
+#[post("/api/config/rollback")]
+async fn config_rollback(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let by = match approving_admin(&req, &state) {
+        Ok(by) => by,
+        Err(denied) => return denied,
+    };
+    match web::block(move || crate::config_edit::rollback(&by)).await {
+        Ok(Ok(Some(pending))) => {
+            audit(&req, "config_rollback_staged");
+            HttpResponse::Accepted().json(serde_json::json!({ "rolled_back": false, "pending": pending }))
+        }
+        Ok(Ok(None)) => {
+            audit(&req, "config_rolled_back");
+            crate::config_edit::restart();
+            HttpResponse::Ok().json(serde_json::json!({ "rolled_back": true, "restarting": true }))
+        }
+        Ok(Err(e)) => problem(StatusCode::CONFLICT, "rollback-failed", format!("{:#}", e)),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "rollback-failed", e.to_string()),
+    }
+}
+
+// Departments hosted as tenants: running state, pid, restarts and the last exit
+#[get("/api/tenants")]
+async fn tenant_status(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    HttpResponse::Ok().json(crate::tenants::status())
+}
+
+#[derive(serde::Deserialize)]
+struct BackupQuery {
+    config: Option<crate::backup::ConfigMode>,
+}
+
+// tar.gz of history, audit log, dedup state, outbox, tokens and the config (?config=encrypted for the full
+// config encrypted with the secrets passphrase, masked secrets otherwise), for the restore subcommand
+#[get("/admin/backup")]
+async fn backup_download(req: HttpRequest, query: web::Query<BackupQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    let mode = query.config.unwrap_or_default();
+    let result = web::block(move || crate::config_edit::current().and_then(|config| crate::backup::create(&config, mode))).await;
+    match result {
+        Ok(Ok(archive)) => {
+            audit(&req, "backup_downloaded");
+            let name = format!("fireplan_alarm_divera_backup_{}.tar.gz", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+            HttpResponse::Ok()
+                .content_type("application/gzip")
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", name)))
+                .body(archive)
+        }
+        Ok(Err(e)) => problem(StatusCode::INTERNAL_SERVER_ERROR, "backup-failed", format!("{:#}", e)),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "backup-failed", e.to_string()),
+    }
+}
+
+// Config editor: edit, stage (validation and trial on the recent alarms), apply or roll back
+#[get("/admin/config")]
+async fn config_editor(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_admin(&req, &state) {
+        return denied;
+    }
+    // the API calls use the token the page was opened with
+    let query = serde_json::to_string(req.query_string())
+        .unwrap_or_else(|_| "\"\"".to_string())
+        .replace("</", "<\\/");
+    let branding = state.configuration.branding.clone().unwrap_or_default();
+    let (lang, background, accent) = (branding.lang(), branding.background_color(), branding.accent_color());
+    let department = escape_html(&branding.department());
+
+    let html = format!(r#"<!doctype html>
+<html lang="{lang}">
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <title>Config - {department}</title>
+  <style>
+    body {{ font-family: system-ui, -apple-system, Segoe UI, Roboto, Ubuntu, Cantarell, Noto Sans, Helvetica, Arial; background: {background}; color: #e2e8f0; margin: 0; padding: 24px; }}
+    h1 {{ margin: 0 0 12px; font-size: 28px; border-left: 6px solid {accent}; padding-left: 12px; }}
+    textarea {{ width: 100%; height: 55vh; box-sizing: border-box; background: #0b1220; color: #e5e7eb; border: 1px solid #1f2937; border-radius: 8px; padding: 10px; font: 13px/1.45 ui-monospace, SFMono-Regular, Menlo, Consolas, "Liberation Mono", monospace; }}
+    button {{ margin: 12px 8px 12px 0; padding: 8px 18px; font-size: 16px; background: #1f2937; color: #e2e8f0; border: 1px solid #374151; border-radius: 6px; cursor: pointer; }}
+    button.primary {{ background: {accent}; border-color: {accent}; color: #fff; }}
+    .error {{ color: #f87171; }}
+    .warning {{ color: #eab308; }}
+    .ok {{ color: #22c55e; }}
+    table {{ border-collapse: collapse; width: 100%; font-size: 13px; }}
+    td, th {{ border-bottom: 1px solid #1f2937; padding: 4px 8px; text-align: left; vertical-align: top; }}
+    tr.changed td {{ background: #1e1b4b; }}
+  </style>
+</head>
+<body>
+  <h1>Config · {department}</h1>
+  <textarea id="content" spellcheck="false"></textarea>
+  <div>
+    <button id="stage">Validate and stage</button>
+    <button id="apply" class="primary" disabled>Apply and restart</button>
+    <button id="discard">Discard staged</button>
+    <button id="rollback">Roll back last apply</button>
+  </div>
+  <div id="result"></div>
+  <script>
+    const query = {query};
+    const api = (path, method, body, extra) => fetch(path + "?" + [query, extra].filter(Boolean).join("&"), {{ method, body }})
+      .then(async (r) => ({{ ok: r.ok, body: await r.json().catch(() => ({{}})) }}));
+    // hash of the pending change shown here, an approval applies only this content
+    let reviewed = null;
+    const result = document.getElementById("result");
+    const line = (cls, text) => {{
+      const p = document.createElement("p");
+      p.className = cls;
+      p.textContent = text;
+      result.appendChild(p);
+    }};
+    const outcome = (o) => o ? `${{o.einsatzstichwort}} · ${{o.ort}} ${{o.ortsteil}} · ${{o.category}} · ${{o.rics.join(", ")}}` : "-";
+    api("/api/config", "GET").then((r) => {{
+      document.getElementById("content").value = r.body.staged || r.body.content || "";
+      document.getElementById("apply").disabled = !r.body.staged;
+      if (!r.ok) line("error", r.body.detail || "config not readable");
+      if (r.body.pending) {{
+        reviewed = r.body.pending.hash;
+        line("warning", `Staged by ${{r.body.pending.by}} since ${{r.body.pending.since}}, waits for the approval of a second admin: ${{r.body.pending.keys.join(", ")}}`);
+      }}
+    }});
+    document.getElementById("stage").onclick = async () => {{
+      result.textContent = "";
+      reviewed = null;
+      const r = await api("/api/config/stage", "POST", document.getElementById("content").value);
+      if (!r.ok) return line("error", r.body.detail);
+      r.body.errors.forEach((e) => line("error", e));
+      r.body.warnings.forEach((w) => line("warning", w));
+      if (r.body.pending) line("warning", "Changes to " + r.body.pending.keys.join(", ") + " need the approval of a second admin, who applies them");
+      if (r.body.valid) line("ok", "Valid, staged: " + r.body.samples.filter((s) => s.changed).length + " of " + r.body.samples.length + " recent alarms change");
+      document.getElementById("apply").disabled = !r.body.valid;
+      const table = document.createElement("table");
+      table.innerHTML = "<tr><th>Received</th><th>Title</th><th>Current</th><th>Staged</th></tr>";
+      for (const s of r.body.samples) {{
+        const row = table.insertRow();
+        row.className = s.changed ? "changed" : "";
+        for (const text of [s.received, s.title, outcome(s.current), outcome(s.staged)]) row.insertCell().textContent = text;
+      }}
+      result.appendChild(table);
+    }};
+    document.getElementById("apply").onclick = async () => {{
+      if (!confirm("Apply the staged config and restart the service?")) return;
+      const r = await api("/api/config/apply", "POST", undefined, reviewed ? "hash=" + reviewed : "");
+      result.textContent = "";
+      line(r.ok ? "ok" : "error", r.ok ? "Applied, previous version in " + r.body.backup + ", restarting" : r.body.detail);
+    }};
+    document.getElementById("discard").onclick = async () => {{
+      const r = await api("/api/config/stage", "DELETE");
+      result.textContent = "";
+      line(r.ok ? "ok" : "warning", r.ok ? "Staged change discarded" : r.body.detail);
+      document.getElementById("apply").disabled = true;
+    }};
+    document.getElementById("rollback").onclick = async () => {{
+      if (!confirm("Restore the config before the last apply and restart?")) return;
+      const r = await api("/api/config/rollback", "POST");
+      result.textContent = "";
+      if (r.body.pending) {{
+        return line("warning", "Rollback of " + r.body.pending.keys.join(", ") + " staged, a second admin applies it");
+      }}
+      line(r.ok ? "ok" : "error", r.ok ? "Rolled back, restarting" : r.body.detail);
+    }};
+  </script>
+</body>
+</html>"#);
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+#[derive(serde::Deserialize)]
+struct MapQuery {
+    lat: f64,
+    lng: f64,
+    zoom: Option<u8>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+// Static map rendered from cached tiles, for display networks without internet access
+#[get("/api/map.svg")]
+async fn map_svg(req: HttpRequest, query: web::Query<MapQuery>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    let settings = crate::map::MapSettings::from_config(&state.configuration);
+    let q = query.into_inner();
+    let (width, height) = (q.width.unwrap_or(800).clamp(64, 2048), q.height.unwrap_or(600).clamp(64, 2048));
+    match web::block(move || crate::map::render_svg(&settings, q.lat, q.lng, q.zoom.unwrap_or(16), width, height)).await {
+        Ok(Ok(svg)) => HttpResponse::Ok().content_type("image/svg+xml").body(svg),
+        Ok(Err(e)) => problem(StatusCode::BAD_GATEWAY, "map-failed", e.to_string()),
+        Err(e) => problem(StatusCode::INTERNAL_SERVER_ERROR, "map-failed", e.to_string()),
+    }
+}
+
+// Full-screen kiosk page for the station wall display, polls /api/alarms/last
+#[get("/monitor")]
+async fn monitor(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = check_operational(&req, &state) {
+        return denied;
+    }
+    // pass the token on to the polling request, the kiosk browser usually has no other way to authenticate
+    let query = serde_json::to_string(req.query_string())
+        .unwrap_or_else(|_| "\"\"".to_string())
+        .replace("</", "<\\/");
+    // the OpenStreetMap embed cannot show the water sources, the rendered map can
+    let offline_map = state.configuration.offline_map.unwrap_or(false) || crate::hydrants::enabled();
+    let priority_styles = serde_json::to_string(&crate::priority::styles())
+        .unwrap_or_else(|_| "{}".to_string())
+        .replace("</", "<\\/");
+    let time_zone = crate::localtime::timezone().name();
+    let branding = state.configuration.branding.clone().unwrap_or_default();
+    let t = |key| branding.text(key);
+    let (lang, background, accent) = (branding.lang(), branding.background_color(), branding.accent_color());
+    let title = escape_html(&format!("{} - {}", t("monitor"), branding.department()));
+    let logo_html = branding
+        .logo_url()
+        .map(|url| format!(r#"<img class="logo" src="{}" alt="" />"#, escape_html(url)))
+        .unwrap_or_default();
+    // texts used by the script
+    let texts = serde_json::json!({
+        "operation": t("operation"),
+        "coming": t("coming"),
+        "not_coming": t("not_coming"),
+        "clock": t("clock"),
+    })
+    .to_string()
+    .replace("</", "<\\/");
+
+    let html = format!(r#"<!doctype html>
+<html lang="{lang}">
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <title>{title}</title>
+  <style>
+    html, body {{ margin: 0; height: 100%; background: {background}; color: #e2e8f0; font-family: system-ui, -apple-system, Segoe UI, Roboto, Ubuntu, Cantarell, Noto Sans, Helvetica, Arial; overflow: hidden; }}
+    .wrap {{ display: grid; grid-template-columns: 1fr 1fr; grid-template-rows: auto 1fr; gap: 24px; height: 100vh; padding: 24px; box-sizing: border-box; }}
+    .head {{ grid-column: 1 / 3; display: flex; justify-content: space-between; align-items: baseline; }}
+    .keyword {{ font-size: 96px; font-weight: 800; color: {accent}; }}
+    .logo {{ height: 96px; vertical-align: middle; margin-right: 24px; }}
+    .elapsed {{ font-size: 64px; font-variant-numeric: tabular-nums; color: #eab308; }}
+    .info {{ font-size: 40px; line-height: 1.3; }}
+    .muted {{ color: #94a3b8; font-size: 28px; }}
+    .units span {{ display: inline-block; background: #1f2937; border-radius: 8px; padding: 6px 14px; margin: 6px 8px 0 0; font-size: 30px; }}
+    iframe {{ width: 100%; height: 100%; border: 0; border-radius: 12px; }}
+    .idle .keyword {{ color: #22c55e; }}
+    .headcount {{ font-size: 36px; font-weight: 700; color: #22c55e; }}
+    .label {{ font-size: 40px; font-weight: 700; vertical-align: middle; }}
+  </style>
+</head>
+<body>
+  <div class="wrap" id="wrap">
+    <div class="head"><div>{logo_html}<span class="keyword" id="keyword">{idle}</span> <span class="label" id="priority"></span></div><div class="elapsed" id="elapsed"></div></div>
+    <div class="info">
+      <div id="address"></div>
+      <div class="muted" id="object"></div>
+      <div class="muted" id="number"></div>
+      <div class="headcount" id="headcount"></div>
+      <div class="muted" id="eta"></div>
+      <div class="muted" id="weather"></div>
+      <div class="units" id="units"></div>
+      <p class="muted" id="text"></p>
+    </div>
+    <div><iframe id="map" src="about:blank"></iframe></div>
+  </div>
+  <script>
+    const query = {query};
+    const offlineMap = {offline_map};
+    const priorityStyles = {priority_styles};
+    const timeZone = "{time_zone}";
+    const texts = {texts};
+    let received = null;
+    let coords = "";
+    function pad(n) {{ return String(n).padStart(2, "0"); }}
+    function tick() {{
+      if (received === null) {{ document.getElementById("elapsed").textContent = ""; return; }}
+      const s = Math.max(0, Math.floor(Date.now() / 1000 - received));
+      document.getElementById("elapsed").textContent = Math.floor(s / 3600) + ":" + pad(Math.floor(s / 60) % 60) + ":" + pad(s % 60);
+    }}
+    async function refresh() {{
+      try {{
+        const r = await fetch("/api/alarms/last" + (query ? "?" + query : ""));
+        if (!r.ok) return;
+        const e = await r.json();
+        const a = e.alarm;
+        received = e.received;
+        document.getElementById("keyword").textContent = a.einsatzstichwort || texts.operation;
+        const style = priorityStyles[a.priority] || {{}};
+        document.getElementById("keyword").style.color = style.color || "";
+        document.getElementById("priority").textContent = style.label || "";
+        document.getElementById("priority").style.color = style.color || "";
+        document.getElementById("address").textContent = [a.strasse + " " + a.hausnummer, a.ortsteil, a.ort].filter(x => x.trim()).join(", ");
+        document.getElementById("object").textContent = a.objektname;
+        const time = new Date(e.received * 1000).toLocaleTimeString("{lang}", {{ timeZone, hour: "2-digit", minute: "2-digit" }});
+        document.getElementById("number").textContent = [a.einsatznrlst, (time + " " + texts.clock).trim()].filter(x => x).join(" · ");
+        document.getElementById("units").innerHTML = "";
+        for (const ric of a.rics) {{
+          const span = document.createElement("span");
+          span.textContent = ric.text;
+          document.getElementById("units").appendChild(span);
+        }}
+        document.getElementById("text").textContent = a.zusatzinfo.slice(0, 400);
+        document.getElementById("weather").textContent = e.weather || "";
+        document.getElementById("eta").textContent = e.eta || "";
+        if (a.koordinaten !== coords) {{
+          coords = a.koordinaten;
+          const [lat, lng] = coords.split(",").map(parseFloat);
+          if (!isNaN(lat) && !isNaN(lng) && offlineMap) {{
+            const map = document.getElementById("map");
+            map.src = "/api/map.svg?lat=" + lat + "&lng=" + lng + "&width=" + map.clientWidth + "&height=" + map.clientHeight + (query ? "&" + query : "");
+          }} else if (!isNaN(lat) && !isNaN(lng)) {{
+            const d = 0.01;
+            document.getElementById("map").src = "https://www.openstreetmap.org/export/embed.html?bbox=" +
+              (lng - d) + "," + (lat - d) + "," + (lng + d) + "," + (lat + d) + "&layer=mapnik&marker=" + lat + "," + lng;
+          }}
+        }}
+      }} catch (err) {{ }}
+    }}
+    // DIVERA Rückmeldungen, pushed by the server
+    const responses = new EventSource("/api/responses/stream" + (query ? "?" + query : ""));
+    responses.onmessage = (m) => {{
+      const h = JSON.parse(m.data);
+      document.getElementById("headcount").textContent = h ? h.coming + " " + texts.coming + ", " + h.not_coming + " " + texts.not_coming : "";
+    }};
+    refresh();
+    setInterval(refresh, 10000);
+    setInterval(tick, 1000);
+  </script>
+</body>
+</html>"#,
+        idle = t("idle"),
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+// Body limit violations are raised by the extractor, convert them to problem+json as well
+fn payload_too_large<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let (req, _) = res.into_parts();
+    let response = problem(StatusCode::PAYLOAD_TOO_LARGE, "payload-too-large", "Request body exceeds the configured limit");
+    Ok(ErrorHandlerResponse::Response(ServiceResponse::new(req, response).map_into_right_body()))
+}
+
 // Build rustls ServerConfig from Let's Encrypt files for the configured hostname
 fn build_rustls_config(hostname: &str) -> anyhow::Result<rustls::ServerConfig> {
-    let base = format!("/etc/letsencrypt/live/{hostname}");
+    let base = crate::paths::cert_dir(hostname);
     let cert_path = format!("{base}/fullchain.pem");
     let key_path = format!("{base}/privkey.pem");
 
@@ -448,41 +2101,131 @@ fn build_rustls_config(hostname: &str) -> anyhow::Result<rustls::ServerConfig> {
     Ok(cfg)
 }
 
-pub fn start_https_server(http_host: String, http_port: u16, auth_token: String) -> std::io::Result<JoinHandle<()>> {
+pub fn start_https_server(configuration: &crate::Configuration) -> std::io::Result<JoinHandle<()>> {
+    let http_host = configuration.http_host.clone();
+    let http_port = configuration.http_port;
     let addr = format!("0.0.0.0:{http_port}");
+    let auth_token = configuration.auth_token.clone();
+    let submit_tokens = configuration.submit_tokens.clone().unwrap_or_default();
+    let admin_token = configuration.admin_token.clone();
+    if admin_token.is_none() && configuration.config_approval.is_none() {
+        warn!("No admin_token configured, admin endpoints are disabled");
+        if configuration.protect_operational_endpoints.unwrap_or(false) {
+            warn!("protect_operational_endpoints without admin_token, the operational endpoints cannot be reached");
+        }
+    }
+    let protect_operational = configuration.protect_operational_endpoints.unwrap_or(false);
+    let enable_echo = configuration.enable_echo.unwrap_or(true);
+    let internal_metrics = configuration.metrics_listener.is_some();
+    let idempotency_window = Duration::from_secs(configuration.idempotency_window_secs.unwrap_or(600));
+    let max_body_bytes = configuration.max_body_bytes.unwrap_or(64 * 1024);
+    let max_json_depth = configuration.max_json_depth.unwrap_or(16);
+    let strict_payload = configuration.strict_payload.unwrap_or(false);
+    let configuration = configuration.clone();
 
-    // Build rustls config up-front to fail fast if missing certs
-    let tls_config = match build_rustls_config(&http_host) {
-        Ok(c) => c,
-        Err(e) => {
-            error!("TLS configuration failed: {e}");
-            return Err(std::io::Error::other(e.to_string()));
+    // Build rustls config up-front to fail fast if missing certs; without TLS a proxy in front is expected to terminate it
+    let tls_config = if configuration.tls.unwrap_or(true) {
+        match build_rustls_config(&http_host) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                error!("TLS configuration failed: {e}");
+                return Err(std::io::Error::other(e.to_string()));
+            }
         }
+    } else {
+        None
     };
 
+    // bound here rather than in the server thread, so privileges can be dropped right after startup
+    let listener = std::net::TcpListener::bind(&addr)?;
+
     let handle = std::thread::spawn(move || {
-        info!("Starting HTTPS server on https://{}:{}", http_host, http_port);
+        let scheme = if tls_config.is_some() { "https" } else { "http" };
+        info!("Starting {} server on {}://{}:{}", scheme.to_uppercase(), scheme, http_host, http_port);
         let sys = actix_web::rt::System::new();
         sys.block_on(async move {
-            let app_state = web::Data::new(AppState { auth_token });
+            let app_state = web::Data::new(AppState { auth_token, submit_tokens, admin_token, protect_operational, idempotency_window, max_json_depth, strict_payload, configuration });
             let server = HttpServer::new(move || {
                 App::new()
+                    .wrap(ErrorHandlers::new().handler(StatusCode::PAYLOAD_TOO_LARGE, payload_too_large))
                     .wrap(ActixLogger::default())
+                    .wrap(actix_web::middleware::from_fn(crate::lockout::reject_banned))
+                    .wrap(actix_web::middleware::from_fn(crate::prometheus::track_requests))
                     .app_data(app_state.clone())
+                    // Oversized bodies are rejected with 413 before reaching any POST handler
+                    .app_data(web::PayloadConfig::new(max_body_bytes))
+                    .app_data(web::QueryConfig::default().error_handler(|e, _| {
+                        actix_web::error::InternalError::from_response(
+                            e.to_string(),
+                            problem(StatusCode::BAD_REQUEST, "invalid-query", e.to_string()),
+                        )
+                        .into()
+                    }))
                     .service(root)
                     .service(health)
                     .service(ready)
                     .service(version)
+                    .service(buildinfo)
                     .service(status)
                     .service(time)
-                    .service(metrics)
-                    .service(echo)
+                    .configure(|cfg| {
+                        // operational data only on the internal listener when there is one
+                        if !internal_metrics {
+                            cfg.service(metrics);
+                        }
+                    })
+                    .configure(|cfg| {
+                        if enable_echo {
+                            cfg.service(echo);
+                        }
+                    })
                     .service(help_page)
                     .service(ping)
                     .service(submit)
-            })
-            .bind_rustls_0_23(addr, tls_config)
-            .expect("failed to bind HTTPS socket")
+                    .service(fe2)
+                    .service(manual_form)
+                    .service(manual_submit)
+                    .service(submission_status)
+                    .service(selftest)
+                    .service(audit_log)
+                    .service(trace_state)
+                    .service(trace_toggle)
+                    .service(dedup_list)
+                    .service(dedup_delete)
+                    .service(outbox_list)
+                    .service(outbox_flush)
+                    .service(outbox_delete)
+                    .service(cancel_alarm)
+                    .service(tokens_list)
+                    .service(tokens_mint)
+                    .service(tokens_approve)
+                    .service(tokens_revoke)
+                    .service(last_alarm)
+                    .service(responses)
+                    .service(responses_stream)
+                    .service(roster)
+                    .service(monitor)
+                    .service(admin_logs)
+                    .service(map_svg)
+                    .service(alarms_ics)
+                    .service(alarms_csv)
+                    .service(alarm_stats)
+                    .service(config_schema)
+                    .service(config_validate)
+                    .service(config_get)
+                    .service(config_stage)
+                    .service(config_discard)
+                    .service(config_apply)
+                    .service(config_rollback)
+                    .service(config_editor)
+                    .service(backup_download)
+                    .service(tenant_status)
+            });
+            let server = match tls_config {
+                Some(tls_config) => server.listen_rustls_0_23(listener, tls_config),
+                None => server.listen(listener),
+            }
+            .expect("failed to bind HTTP(S) socket")
             .run();
 
             if let Err(e) = server.await {
@@ -0,0 +1,375 @@
+// End-to-end tests: the built binary runs against a mock Fireplan API on localhost, alarms come in
+// through /submit and the SMTP mail listener.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+// Fireplan stand-in: Register hands out a token, Alarmierung accepts alarms unless told to fail
+struct MockFireplan {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+    failing: Arc<AtomicBool>,
+    // expires_in of the tokens handed out, 0 for none
+    token_lifetime: Arc<AtomicU64>,
+}
+
+impl MockFireplan {
+    fn start() -> MockFireplan {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let failing = Arc::new(AtomicBool::new(false));
+        let token_lifetime = Arc::new(AtomicU64::new(0));
+        let (r, f, t) = (requests.clone(), failing.clone(), token_lifetime.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (r, f, t) = (r.clone(), f.clone(), t.clone());
+                std::thread::spawn(move || serve(stream, r, f, t));
+            }
+        });
+        MockFireplan { url, requests, failing, token_lifetime }
+    }
+
+    fn requests(&self, method: &str, prefix: &str) -> Vec<Request> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.method == method && r.path.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    fn alarms(&self) -> Vec<Request> {
+        self.requests("PUT", "/api/Alarmierung")
+    }
+
+    fn registers(&self) -> Vec<Request> {
+        self.requests("GET", "/api/Register/")
+    }
+}
+
+fn serve(stream: TcpStream, requests: Arc<Mutex<Vec<Request>>>, failing: Arc<AtomicBool>, token_lifetime: Arc<AtomicU64>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or_default().to_string(), parts.next().unwrap_or_default().to_string());
+        let mut headers = vec![];
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        let length = headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, v)| v.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let (status, answer) = if path.starts_with("/api/Register/") {
+            match token_lifetime.load(Ordering::SeqCst) {
+                0 => ("200 OK", r#"{"utoken":"mock-token"}"#.to_string()),
+                secs => ("200 OK", format!(r#"{{"utoken":"mock-token","expires_in":{}}}"#, secs)),
+            }
+        } else if failing.load(Ordering::SeqCst) {
+            ("503 Service Unavailable", "down".to_string())
+        } else {
+            ("200 OK", "\"ok\"".to_string())
+        };
+        requests.lock().unwrap().push(Request { method, path, headers, body: String::from_utf8_lossy(&body).to_string() });
+        let response = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", status, answer.len(), answer);
+        if stream.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+// The service with its own state directory, killed when dropped
+struct Service {
+    child: Child,
+    url: String,
+    smtp_port: u16,
+    dir: PathBuf,
+}
+
+impl Service {
+    fn start(name: &str, fireplan: &MockFireplan) -> Service {
+        let dir = std::env::temp_dir().join(format!("fireplan_alarm_divera_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (http_port, smtp_port) = (free_port(), free_port());
+        let config = format!(
+            r#"fireplan_api_key = "test-key"
+fireplan_url = "{fireplan}"
+regex_ort = 'Ort\s*:\s*(.*)'
+regex_ortsteil = 'Ortsteil\s*:\s*(.*)'
+regex_objektname = 'Objekt\s*:\s*(.*)'
+http_host = "localhost"
+http_port = {http_port}
+auth_token = "test-token"
+admin_token = "test-admin"
+tls = false
+state_dir = "{dir}"
+rics = [ {{ text = "HLF", ric = "123456", subric = "B" }}, {{ text = "DLK", ric = "234567", subric = "C" }} ]
+mail_listener = {{ port = {smtp_port}, allowed_peers = ["127.0.0.1"], regex_einsatznummer = 'Einsatznummer\s*:\s*(\S+)' }}
+callbacks = {{ allowed_hosts = ["127.0.0.1"] }}
+"#,
+            fireplan = fireplan.url,
+            dir = dir.display(),
+        );
+        let config_file = dir.join("test.conf");
+        std::fs::write(&config_file, config).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_fireplan_alarm_divera"))
+            .arg("--config")
+            .arg(&config_file)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let service = Service { child, url: format!("http://127.0.0.1:{}", http_port), smtp_port, dir };
+        wait_for(|| reqwest::blocking::get(format!("{}/health", service.url)).map(|r| r.status().is_success()).unwrap_or(false));
+        service
+    }
+
+    fn submit(&self, foreign_id: &str, text: &str) -> serde_json::Value {
+        self.submit_to(&format!("{}/submit?token=test-token", self.url), foreign_id, text)
+    }
+
+    fn submit_to(&self, url: &str, foreign_id: &str, text: &str) -> serde_json::Value {
+        // a new DIVERA id each time, repeated ids are answered from the idempotency cache
+        static ID: AtomicU64 = AtomicU64::new(1);
+        let body = serde_json::json!({ "id": ID.fetch_add(1, Ordering::SeqCst), "foreign_id": foreign_id, "title": "B3 Brand", "text": text });
+        reqwest::blocking::Client::new()
+            .post(url)
+            .json(&body)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    }
+
+    fn admin(&self, method: reqwest::Method, path: &str) -> serde_json::Value {
+        reqwest::blocking::Client::new()
+            .request(method, format!("{}{}?token=test-admin", self.url, path))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn wait_for(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(15);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// RICs of the alarms in one Alarmierung call
+fn rics(request: &Request) -> Vec<String> {
+    let alarms: Vec<serde_json::Value> = serde_json::from_str(&request.body).unwrap();
+    alarms.iter().map(|a| a["ric"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn alarm_is_sent_per_ric_with_cached_token() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("per_ric", &fireplan);
+
+    service.submit("E-1", "Ort: Musterstadt\nEinsatzmittel: HLF, DLK");
+    wait_for(|| fireplan.alarms().len() == 1);
+    service.submit("E-2", "Ort: Musterstadt\nEinsatzmittel: HLF");
+    wait_for(|| fireplan.alarms().len() == 2);
+
+    let alarms = fireplan.alarms();
+    assert_eq!(rics(&alarms[0]), ["0123456", "0234567", "0999995"]);
+    assert_eq!(rics(&alarms[1]), ["0123456", "0999995"]);
+    let first: Vec<serde_json::Value> = serde_json::from_str(&alarms[0].body).unwrap();
+    assert_eq!(first[0]["einsatznrlst"], "E-1");
+    assert_eq!(first[0]["ort"], "Musterstadt");
+    assert_eq!(first[1]["subRIC"], "C");
+    // one token fetched at startup serves both alarms
+    assert_eq!(fireplan.registers().len(), 1);
+    assert_eq!(fireplan.registers()[0].header("API-Key"), Some("test-key"));
+    assert!(alarms.iter().all(|a| a.header("API-Token") == Some("mock-token")));
+}
+
+#[test]
+fn token_is_renewed_after_the_lifetime_fireplan_announced() {
+    let fireplan = MockFireplan::start();
+    fireplan.token_lifetime.store(1, Ordering::SeqCst);
+    let service = Service::start("token_lifetime", &fireplan);
+    wait_for(|| fireplan.registers().len() == 1);
+
+    std::thread::sleep(Duration::from_millis(1500));
+    service.submit("E-6", "Einsatzmittel: HLF");
+    wait_for(|| fireplan.alarms().len() == 1);
+    assert_eq!(fireplan.registers().len(), 2);
+}
+
+#[test]
+fn repeated_rics_of_an_einsatz_are_not_sent_again() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("dedup", &fireplan);
+
+    service.submit("E-3", "Einsatzmittel: HLF");
+    wait_for(|| fireplan.alarms().len() == 1);
+    let repeated = service.submit("E-3", "Einsatzmittel: HLF, DLK");
+    let id = repeated["id"].as_str().unwrap().to_string();
+    wait_for(|| service.admin(reqwest::Method::GET, &format!("/api/submissions/{}", id))["state"] == "submitted");
+
+    // only the newly alarmed DLK goes out
+    let alarms = fireplan.alarms();
+    assert_eq!(alarms.len(), 2);
+    assert_eq!(rics(&alarms[1]), ["0234567"]);
+
+    service.submit("E-3", "Einsatzmittel: HLF, DLK");
+    std::thread::sleep(Duration::from_secs(1));
+    assert_eq!(fireplan.alarms().len(), 2);
+}
+
+#[test]
+fn concurrent_retries_with_one_idempotency_key_are_submitted_once() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("idempotency", &fireplan);
+
+    let body = serde_json::json!({ "foreign_id": "E-7", "title": "B3 Brand", "text": "Einsatzmittel: HLF" });
+    let ids: Vec<String> = (0..8)
+        .map(|_| {
+            let (url, body) = (format!("{}/submit?token=test-token", service.url), body.clone());
+            std::thread::spawn(move || {
+                let response: serde_json::Value =
+                    reqwest::blocking::Client::new().post(url).header("Idempotency-Key", "retry-1").json(&body).send().unwrap().json().unwrap();
+                response["id"].as_str().unwrap().to_string()
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|t| t.join().unwrap())
+        .collect();
+    assert!(ids.iter().all(|id| id == &ids[0]), "{:?}", ids);
+    wait_for(|| fireplan.alarms().len() == 1);
+    std::thread::sleep(Duration::from_secs(1));
+    assert_eq!(fireplan.alarms().len(), 1);
+}
+
+#[test]
+fn rejected_alarm_is_retried_from_the_outbox() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("outbox", &fireplan);
+    fireplan.failing.store(true, Ordering::SeqCst);
+
+    service.submit("E-4", "Einsatzmittel: HLF");
+    wait_for(|| service.admin(reqwest::Method::GET, "/api/outbox").as_array().map(|a| a.len()) == Some(1));
+    assert_eq!(fireplan.alarms().len(), 1);
+
+    fireplan.failing.store(false, Ordering::SeqCst);
+    let flushed = service.admin(reqwest::Method::POST, "/api/outbox/flush");
+    assert_eq!(flushed["submitted"].as_array().unwrap().len(), 1);
+    assert_eq!(fireplan.alarms().len(), 2);
+    assert_eq!(rics(&fireplan.alarms()[1]), ["0123456", "0999995"]);
+    assert_eq!(service.admin(reqwest::Method::GET, "/api/outbox"), serde_json::json!([]));
+}
+
+#[test]
+fn outcome_is_reported_to_the_callback() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("callback", &fireplan);
+
+    let url = format!("{}/submit?token=test-token&callback={}/callback", service.url, fireplan.url);
+    service.submit_to(&url, "E-7", "Einsatzmittel: DLK");
+    wait_for(|| !fireplan.requests("POST", "/callback").is_empty());
+    let outcome: serde_json::Value = serde_json::from_str(&fireplan.requests("POST", "/callback")[0].body).unwrap();
+    assert_eq!(outcome["outcome"], "submitted");
+    assert_eq!(outcome["einsatznummer"], "E-7");
+    assert_eq!(outcome["rics"][0]["ric"], "0234567");
+    assert_eq!(outcome["rics"][0]["outcome"], "submitted");
+
+    let refused = format!("{}/submit?token=test-token&callback=http://example.org/callback", service.url);
+    assert_eq!(service.submit_to(&refused, "E-8", "Einsatzmittel: DLK")["type"].as_str().map(|t| t.ends_with("invalid-callback")), Some(true));
+}
+
+fn smtp(stream: &mut BufReader<TcpStream>, line: &str) -> String {
+    if !line.is_empty() {
+        stream.get_mut().write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+    }
+    let mut answer = String::new();
+    // multi-line answers continue with "250-"
+    loop {
+        let mut l = String::new();
+        stream.read_line(&mut l).unwrap();
+        answer.push_str(&l);
+        if l.len() < 4 || l.as_bytes()[3] != b'-' {
+            return answer;
+        }
+    }
+}
+
+#[test]
+fn alarm_mail_reaches_fireplan() {
+    let fireplan = MockFireplan::start();
+    let service = Service::start("mail", &fireplan);
+
+    let mut stream = BufReader::new(TcpStream::connect(("127.0.0.1", service.smtp_port)).unwrap());
+    assert!(smtp(&mut stream, "").starts_with("220"));
+    assert!(smtp(&mut stream, "EHLO leitstelle").starts_with("250"));
+    assert!(smtp(&mut stream, "MAIL FROM:<ils@example.org>").starts_with("250"));
+    assert!(smtp(&mut stream, "RCPT TO:<alarm@example.org>").starts_with("250"));
+    assert!(smtp(&mut stream, "DATA").starts_with("354"));
+    let mail = "Subject: THL Baum auf Strasse\r\nMessage-ID: <e5@example.org>\r\n\r\n\
+                Einsatznummer: E-5\r\nOrt: Musterdorf\r\nEinsatzmittel: DLK\r\n.";
+    assert!(smtp(&mut stream, mail).starts_with("250"));
+    smtp(&mut stream, "QUIT");
+
+    wait_for(|| fireplan.alarms().len() == 1);
+    let alarms: Vec<serde_json::Value> = serde_json::from_str(&fireplan.alarms()[0].body).unwrap();
+    assert_eq!(alarms[0]["ric"], "0234567");
+    assert_eq!(alarms[0]["einsatznrlst"], "E-5");
+    assert_eq!(alarms[0]["einsatzstichwort"], "THL Baum auf Strasse");
+    assert_eq!(alarms[0]["ort"], "Musterdorf");
+}